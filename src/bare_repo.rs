@@ -1,68 +1,110 @@
+use crate::clone::run_clone;
+use crate::init::run_init;
 use crate::AbsoluteDirPath;
-use crate::ConfigReadError;
 use crate::GenericRepository;
+use crate::GitError;
+use crate::{CloneOptions, GitCommand, GitRunner, InitError, InitOptions, ProcessRunner};
 use std::path::Path;
-use std::process::Command;
+use std::sync::Arc;
 
 /// Represents a bare repository
-#[derive(Debug)]
-pub struct BareRepository(AbsoluteDirPath);
+#[derive(Clone, Debug)]
+pub struct BareRepository {
+    /// GIT_DIR
+    git_dir: AbsoluteDirPath,
+    /// Executes all git commands
+    runner: Arc<dyn GitRunner>,
+}
 
 impl BareRepository {
-    /// # Panics
-    ///
-    /// When git execution fails
+    /// Create a new bare repository using [git-init(1)](https://git-scm.com/docs/git-init)
     ///
     /// # Errors
     ///
-    /// Returns a string output when something goes horrible wrong
+    /// See [`InitError`]
+    #[inline]
+    pub fn create(path: &Path) -> Result<Self, InitError> {
+        Self::init(path, &InitOptions::default())
+    }
+
+    /// Create a new bare repository at `path` with the given options, missing directories
+    /// are created.
     ///
+    /// # Errors
+    ///
+    /// See [`InitError`]
     #[inline]
-    pub fn create(path: &Path) -> Result<Self, String> {
-        let mut cmd = Command::new("git");
-        let out = cmd
-            .arg("init")
-            .arg("--bare")
-            .current_dir(&path)
-            .output()
-            .expect("Execute git-init(1)");
+    pub fn init(path: &Path, options: &InitOptions) -> Result<Self, InitError> {
+        Self::init_with_runner(path, options, Arc::new(ProcessRunner::default()))
+    }
 
-        if out.status.success() {
-            let git_dir = path.try_into().map_err(|e| format!("{}", e))?;
-            Ok(Self(git_dir))
-        } else {
-            Err(String::from_utf8_lossy(&out.stderr).to_string())
-        }
+    /// Like [`BareRepository::init`], but executes all git commands including the
+    /// initialization through `runner`
+    ///
+    /// # Errors
+    ///
+    /// See [`InitError`]
+    #[inline]
+    pub fn init_with_runner(
+        path: &Path,
+        options: &InitOptions,
+        runner: Arc<dyn GitRunner>,
+    ) -> Result<Self, InitError> {
+        run_init(runner.clone(), path, options, true)?;
+        let git_dir = path.try_into().map_err(GitError::from)?;
+        Ok(Self { git_dir, runner })
     }
 
-    /// Return config value for specified key
+    /// Clone `url` into `path` as a bare repository using
+    /// [git-clone(1)](https://git-scm.com/docs/git-clone)
     ///
     /// # Errors
     ///
-    /// When given invalid key or an invalid config file is read.
+    /// See [`GitError`]
+    #[inline]
+    pub fn clone(url: &str, path: &Path, options: CloneOptions<'_>) -> Result<Self, GitError> {
+        Self::clone_with_runner(url, path, options, Arc::new(ProcessRunner::default()))
+    }
+
+    /// Like [`BareRepository::clone`], but executes all git commands including the clone
+    /// through `runner`
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// Will panic if git exits with an unexpected error code. Expected codes are 0, 1 & 3.
+    /// See [`GitError`]
     #[inline]
-    pub fn config(&self, key: &str) -> Result<String, ConfigReadError> {
-        self.gen_config(key)
+    pub fn clone_with_runner(
+        url: &str,
+        path: &Path,
+        options: CloneOptions<'_>,
+        runner: Arc<dyn GitRunner>,
+    ) -> Result<Self, GitError> {
+        run_clone(runner.clone(), url, path, options, true)?;
+        let git_dir = path.try_into().map_err(GitError::from)?;
+        Ok(Self { git_dir, runner })
     }
 
-    /// Returns a prepared git `Command` struct
+    /// Execute all git commands of this repository through the given runner
     #[must_use]
     #[inline]
-    pub fn git(&self) -> Command {
-        let mut cmd = Command::new("git");
-        let git_dir = self.0 .0.to_str().expect("Convert to string");
-        cmd.env("GIT_DIR", git_dir);
-        cmd
+    pub fn with_runner(mut self, runner: Arc<dyn GitRunner>) -> Self {
+        self.runner = runner;
+        self
     }
 }
 
 impl GenericRepository for BareRepository {
-    fn gen_git(&self) -> Command {
-        self.git()
+    #[inline]
+    fn git(&self) -> GitCommand {
+        let mut cmd = GitCommand::new(self.runner.clone());
+        cmd.env("GIT_DIR", &self.git_dir.0);
+        cmd.env("LC_ALL", "C");
+        cmd
+    }
+
+    #[inline]
+    fn git_dir(&self) -> &Path {
+        &self.git_dir.0
     }
 }
 
@@ -0,0 +1,254 @@
+use crate::{GitCommand, GitError};
+use posix_errors::{PosixError, EEXIST, EINVAL, ENOENT};
+
+/// Failed to manage a branch
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum BranchError {
+    /// A branch with this name already exists
+    #[error("Branch `{0}` already exists")]
+    BranchExists(String),
+    /// Branch, start point or upstream does not exist
+    #[error("Branch or revision `{0}` not found")]
+    NotFound(String),
+    /// Branch contains commits not merged into `HEAD` or its upstream
+    #[error("Branch `{0}` is not fully merged")]
+    NotFullyMerged(String),
+    /// Name is not a valid branch name, see
+    /// [git-check-ref-format(1)](https://git-scm.com/docs/git-check-ref-format)
+    #[error("`{0}` is not a valid branch name")]
+    InvalidName(String),
+    #[allow(missing_docs)]
+    #[error(transparent)]
+    Failure(#[from] GitError),
+}
+
+impl From<BranchError> for GitError {
+    #[inline]
+    fn from(err: BranchError) -> Self {
+        match err {
+            BranchError::Failure(e) => e,
+            BranchError::BranchExists(_)
+            | BranchError::NotFound(_)
+            | BranchError::NotFullyMerged(_)
+            | BranchError::InvalidName(_) => Self::Invalid(format!("{}", err)),
+        }
+    }
+}
+
+impl From<BranchError> for PosixError {
+    #[inline]
+    fn from(err: BranchError) -> Self {
+        match err {
+            BranchError::Failure(e) => e.into(),
+            BranchError::BranchExists(_) => Self::new(EEXIST, format!("{}", err)),
+            BranchError::NotFound(_) => Self::new(ENOENT, format!("{}", err)),
+            BranchError::NotFullyMerged(_) | BranchError::InvalidName(_) => {
+                Self::new(EINVAL, format!("{}", err))
+            }
+        }
+    }
+}
+
+/// Run a git-branch(1) command, translating well known failures into [`BranchError`]
+fn run_branch(mut cmd: GitCommand, name: &str) -> Result<(), BranchError> {
+    let out = cmd.output()?;
+    if out.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    if stderr.contains("already exists") {
+        Err(BranchError::BranchExists(name.to_owned()))
+    } else if stderr.contains("not fully merged") {
+        Err(BranchError::NotFullyMerged(name.to_owned()))
+    } else if stderr.contains("not a valid branch name") {
+        Err(BranchError::InvalidName(name.to_owned()))
+    } else if let Some(missing) = missing_name(&stderr) {
+        Err(BranchError::NotFound(missing))
+    } else {
+        Err(GitError::from_output(&cmd, &out).into())
+    }
+}
+
+/// Extract the quoted name from messages like `branch 'foo' not found` or
+/// `the requested upstream branch 'foo' does not exist`
+fn missing_name(stderr: &str) -> Option<String> {
+    stderr.lines().find_map(|line| {
+        if !(line.contains("not found")
+            || line.contains("does not exist")
+            || line.contains("not a valid object name"))
+        {
+            return None;
+        }
+        let (_, rest) = line.split_once('\'')?;
+        let (name, _) = rest.split_once('\'')?;
+        Some(name.to_owned())
+    })
+}
+
+fn check_branch_name(mut cmd: GitCommand, name: &str) -> Result<(), BranchError> {
+    let out = cmd.args(["check-ref-format", "--branch", name]).output()?;
+    if out.status.success() && !name.starts_with('-') {
+        Ok(())
+    } else {
+        Err(BranchError::InvalidName(name.to_owned()))
+    }
+}
+
+/// See [`crate::GenericRepository::current_branch`]
+pub(crate) fn current_branch(mut cmd: GitCommand) -> Result<Option<String>, GitError> {
+    cmd.args(["symbolic-ref", "--quiet", "--short", "HEAD"]);
+    let out = cmd.output()?;
+    match out.status.code() {
+        Some(0) => Ok(Some(String::from_utf8(out.stdout)?.trim().to_owned())),
+        Some(1) => Ok(None),
+        _ => Err(GitError::from_output(&cmd, &out)),
+    }
+}
+
+/// See [`crate::GenericRepository::create_branch`]
+pub(crate) fn create_branch(
+    mut cmd: GitCommand,
+    name: &str,
+    start: Option<&str>,
+    track: bool,
+) -> Result<(), BranchError> {
+    check_branch_name(cmd.clone(), name)?;
+    cmd.args(["branch", if track { "--track" } else { "--no-track" }, name]);
+    if let Some(start) = start {
+        cmd.arg(start);
+    }
+    run_branch(cmd, name)
+}
+
+/// See [`crate::GenericRepository::delete_branch`]
+pub(crate) fn delete_branch(
+    mut cmd: GitCommand,
+    name: &str,
+    force: bool,
+) -> Result<(), BranchError> {
+    check_branch_name(cmd.clone(), name)?;
+    cmd.args(["branch", if force { "-D" } else { "-d" }, name]);
+    run_branch(cmd, name)
+}
+
+/// See [`crate::GenericRepository::rename_branch`]
+pub(crate) fn rename_branch(mut cmd: GitCommand, old: &str, new: &str) -> Result<(), BranchError> {
+    check_branch_name(cmd.clone(), old)?;
+    check_branch_name(cmd.clone(), new)?;
+    cmd.args(["branch", "--move", old, new]);
+    run_branch(cmd, new)
+}
+
+/// See [`crate::GenericRepository::set_upstream`]
+pub(crate) fn set_upstream(
+    mut cmd: GitCommand,
+    branch: &str,
+    upstream: &str,
+) -> Result<(), BranchError> {
+    check_branch_name(cmd.clone(), branch)?;
+    cmd.arg("branch")
+        .arg(format!("--set-upstream-to={}", upstream))
+        .arg(branch);
+    run_branch(cmd, branch)
+}
+
+/// See [`crate::GenericRepository::unset_upstream`]
+pub(crate) fn unset_upstream(mut cmd: GitCommand, branch: &str) -> Result<(), BranchError> {
+    check_branch_name(cmd.clone(), branch)?;
+    cmd.args(["branch", "--unset-upstream", branch]);
+    run_branch(cmd, branch)
+}
+
+/// See [`crate::GenericRepository::ahead_behind`]
+pub(crate) fn ahead_behind(
+    cmd: GitCommand,
+    branch: &str,
+    upstream: &str,
+) -> Result<(u32, u32), BranchError> {
+    for rev in [branch, upstream] {
+        let out = cmd
+            .clone()
+            .args(["rev-parse", "--verify", "--quiet", "--end-of-options"])
+            .arg(format!("{}^{{commit}}", rev))
+            .output()?;
+        if !out.status.success() {
+            return Err(BranchError::NotFound(rev.to_owned()));
+        }
+    }
+    let out = cmd
+        .clone()
+        .args(["rev-list", "--left-right", "--count"])
+        .arg(format!("{}...{}", branch, upstream))
+        .arg("--")
+        .read()?;
+    let parse_error = || GitError::Parse(format!("Unexpected git-rev-list(1) output `{}`", out));
+    let (ahead, behind) = out.trim().split_once('\t').ok_or_else(parse_error)?;
+    Ok((
+        ahead.parse().map_err(|_| parse_error())?,
+        behind.parse().map_err(|_| parse_error())?,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{setup_test_author, BranchError, GenericRepository, Repository};
+    use tempfile::TempDir;
+
+    #[test]
+    fn manage() {
+        setup_test_author();
+        let tmp_dir = TempDir::new().unwrap();
+        let repo = Repository::create(tmp_dir.path()).expect("Created repository");
+        repo.commit_extended("First", true, true).unwrap();
+        let main = repo.current_branch().unwrap().expect("Branch checked out");
+
+        repo.create_branch("feature", None, false).unwrap();
+        assert_eq!(
+            repo.create_branch("feature", None, false),
+            Err(BranchError::BranchExists("feature".to_owned()))
+        );
+        assert_eq!(
+            repo.create_branch("in valid", None, false),
+            Err(BranchError::InvalidName("in valid".to_owned()))
+        );
+        assert_eq!(
+            repo.create_branch("other", Some("nope"), false),
+            Err(BranchError::NotFound("nope".to_owned()))
+        );
+        repo.rename_branch("feature", "topic").unwrap();
+        let names: Vec<String> = repo
+            .branches()
+            .unwrap()
+            .iter()
+            .map(|b| b.short_name().to_owned())
+            .collect();
+        assert_eq!(names, vec![main.clone(), "topic".to_owned()]);
+
+        repo.git().args(["checkout", "-q", "topic"]).run().unwrap();
+        repo.set_upstream("topic", &main).unwrap();
+        repo.commit_extended("Second", true, true).unwrap();
+        assert_eq!(repo.ahead_behind("topic", &main).unwrap(), (1, 0));
+        assert_eq!(
+            repo.ahead_behind("topic", "nope"),
+            Err(BranchError::NotFound("nope".to_owned()))
+        );
+        repo.unset_upstream("topic").unwrap();
+
+        repo.git().args(["checkout", "-q", &main]).run().unwrap();
+        assert_eq!(
+            repo.delete_branch("topic", false),
+            Err(BranchError::NotFullyMerged("topic".to_owned()))
+        );
+        repo.delete_branch("topic", true).unwrap();
+        assert_eq!(
+            repo.delete_branch("topic", true),
+            Err(BranchError::NotFound("topic".to_owned()))
+        );
+
+        repo.git()
+            .args(["checkout", "-q", "--detach"])
+            .run()
+            .unwrap();
+        assert_eq!(repo.current_branch().unwrap(), None);
+    }
+}
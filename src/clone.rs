@@ -0,0 +1,272 @@
+use crate::{GitCommand, GitError, GitRunner, Progress, ProgressCallback};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Options for [`crate::Repository::clone`] & [`crate::BareRepository::clone`]
+#[derive(Default)]
+pub struct CloneOptions<'a> {
+    depth: Option<u32>,
+    shallow_since: Option<String>,
+    filter: Option<String>,
+    sparse: bool,
+    branch: Option<String>,
+    single_branch: bool,
+    recurse_submodules: bool,
+    mirror: bool,
+    references: Vec<PathBuf>,
+    progress: Option<ProgressCallback<'a>>,
+}
+
+impl std::fmt::Debug for CloneOptions<'_> {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CloneOptions")
+            .field("depth", &self.depth)
+            .field("shallow_since", &self.shallow_since)
+            .field("filter", &self.filter)
+            .field("sparse", &self.sparse)
+            .field("branch", &self.branch)
+            .field("single_branch", &self.single_branch)
+            .field("recurse_submodules", &self.recurse_submodules)
+            .field("mirror", &self.mirror)
+            .field("references", &self.references)
+            .field("progress", &self.progress.is_some())
+            .finish()
+    }
+}
+
+impl<'a> CloneOptions<'a> {
+    /// Create a shallow clone with `depth` commits
+    #[must_use]
+    #[inline]
+    pub const fn depth(mut self, depth: u32) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    /// Create a shallow clone with history after the given date
+    #[must_use]
+    #[inline]
+    pub fn shallow_since(mut self, date: &str) -> Self {
+        self.shallow_since = Some(date.to_owned());
+        self
+    }
+
+    /// Partial clone filter, i.e. `blob:none`
+    #[must_use]
+    #[inline]
+    pub fn filter(mut self, filter: &str) -> Self {
+        self.filter = Some(filter.to_owned());
+        self
+    }
+
+    /// Initialize the sparse-checkout file, so only files in the top directory are checked out
+    #[must_use]
+    #[inline]
+    pub const fn sparse(mut self, sparse: bool) -> Self {
+        self.sparse = sparse;
+        self
+    }
+
+    /// Check out `branch` instead of the remote `HEAD`
+    #[must_use]
+    #[inline]
+    pub fn branch(mut self, branch: &str) -> Self {
+        self.branch = Some(branch.to_owned());
+        self
+    }
+
+    /// Only fetch the history of the checked out branch
+    #[must_use]
+    #[inline]
+    pub const fn single_branch(mut self, single_branch: bool) -> Self {
+        self.single_branch = single_branch;
+        self
+    }
+
+    /// Initialize & clone submodules
+    #[must_use]
+    #[inline]
+    pub const fn recurse_submodules(mut self, recurse: bool) -> Self {
+        self.recurse_submodules = recurse;
+        self
+    }
+
+    /// Mirror all references of the remote, only supported by
+    /// [`crate::BareRepository::clone`]
+    #[must_use]
+    #[inline]
+    pub const fn mirror(mut self, mirror: bool) -> Self {
+        self.mirror = mirror;
+        self
+    }
+
+    /// Borrow objects from the local repository at `path`; may be repeated
+    #[must_use]
+    #[inline]
+    pub fn reference(mut self, path: &Path) -> Self {
+        self.references.push(path.to_path_buf());
+        self
+    }
+
+    /// Call `callback` for every progress update
+    #[must_use]
+    #[inline]
+    pub fn progress(mut self, callback: impl FnMut(&Progress) + 'a) -> Self {
+        self.progress = Some(Box::new(callback));
+        self
+    }
+}
+
+/// Run [git-clone(1)](https://git-scm.com/docs/git-clone) with the given options
+pub(crate) fn run_clone(
+    runner: Arc<dyn GitRunner>,
+    url: &str,
+    path: &Path,
+    mut options: CloneOptions<'_>,
+    bare: bool,
+) -> Result<(), GitError> {
+    if options.mirror && !bare {
+        return Err(GitError::Invalid(
+            "A mirror clone is always bare".to_owned(),
+        ));
+    }
+    let mut cmd = GitCommand::new(runner);
+    cmd.env("LC_ALL", "C");
+    cmd.arg("clone");
+    if options.progress.is_some() {
+        cmd.arg("--progress");
+    }
+    if options.mirror {
+        cmd.arg("--mirror");
+    } else if bare {
+        cmd.arg("--bare");
+    }
+    if let Some(depth) = options.depth {
+        cmd.arg(format!("--depth={}", depth));
+    }
+    if let Some(date) = &options.shallow_since {
+        cmd.arg(format!("--shallow-since={}", date));
+    }
+    if let Some(filter) = &options.filter {
+        cmd.arg(format!("--filter={}", filter));
+    }
+    if options.sparse {
+        cmd.arg("--sparse");
+    }
+    if let Some(branch) = &options.branch {
+        cmd.arg(format!("--branch={}", branch));
+    }
+    if options.single_branch {
+        cmd.arg("--single-branch");
+    }
+    if options.recurse_submodules {
+        cmd.arg("--recurse-submodules");
+    }
+    for reference in &options.references {
+        cmd.arg("--reference").arg(reference);
+    }
+    cmd.arg("--").arg(url).arg(path);
+    cmd.run_with_progress(&mut |line| Progress::notify(&mut options.progress, line))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        setup_test_author, BareRepository, CloneOptions, GenericRepository, GitError,
+        ProcessRunner, RecordingRunner, RefOptions, Repository,
+    };
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    #[test]
+    fn clone() {
+        setup_test_author();
+        let tmp_dir = TempDir::new().unwrap();
+        let origin_dir = tmp_dir.path().join("origin");
+        std::fs::create_dir(&origin_dir).unwrap();
+        let origin = Repository::create(&origin_dir).expect("Created repository");
+        origin.commit_extended("First", true, true).unwrap();
+        let first = origin.head().unwrap();
+        origin.commit_extended("Second", true, true).unwrap();
+        origin.create_branch("other", Some("HEAD~"), false).unwrap();
+        let url = format!("file://{}", origin_dir.display());
+
+        let mut phases = vec![];
+        let options = CloneOptions::default()
+            .depth(1)
+            .branch("other")
+            .single_branch(true)
+            .progress(|p| phases.push(p.phase.clone()));
+        let repo = Repository::clone(&url, &tmp_dir.path().join("shallow"), options)
+            .expect("Cloned repository");
+        assert!(repo.is_shallow().unwrap());
+        assert_eq!(repo.current_branch().unwrap().as_deref(), Some("other"));
+        assert_eq!(repo.head().unwrap(), first);
+        assert!(repo.work_tree().unwrap().ends_with("shallow"));
+        assert!(!phases.is_empty());
+
+        let mirror = BareRepository::clone(
+            &url,
+            &tmp_dir.path().join("mirror.git"),
+            CloneOptions::default().mirror(true),
+        )
+        .expect("Cloned bare repository");
+        let branches = mirror
+            .refs(&["refs/heads"], &RefOptions::default())
+            .unwrap();
+        assert_eq!(branches.len(), 2);
+
+        let err = Repository::clone(
+            &url,
+            &tmp_dir.path().join("invalid"),
+            CloneOptions::default().mirror(true),
+        )
+        .expect_err("Expected failure");
+        assert!(matches!(err, GitError::Invalid(_)));
+        let err = Repository::clone(
+            "file:///does/not/exist",
+            &tmp_dir.path().join("missing"),
+            CloneOptions::default(),
+        )
+        .expect_err("Expected failure");
+        assert_eq!(err.code(), Some(128));
+    }
+
+    #[test]
+    fn clone_with_runner() {
+        setup_test_author();
+        let tmp_dir = TempDir::new().unwrap();
+        let origin_dir = tmp_dir.path().join("origin");
+        std::fs::create_dir(&origin_dir).unwrap();
+        let origin = Repository::create(&origin_dir).expect("Created repository");
+        origin.commit_extended("First", true, true).unwrap();
+        let url = format!("file://{}", origin_dir.display());
+
+        let runner = Arc::new(RecordingRunner::new(Arc::new(ProcessRunner::default())));
+        let repo = Repository::clone_with_runner(
+            &url,
+            &tmp_dir.path().join("work"),
+            CloneOptions::default(),
+            runner.clone(),
+        )
+        .expect("Cloned repository");
+        let invocations = runner.invocations();
+        assert_eq!(invocations.len(), 1);
+        assert_eq!(invocations[0].args[0], "clone");
+        repo.head().unwrap();
+        assert_eq!(runner.invocations().len(), 2);
+
+        let runner = Arc::new(RecordingRunner::new(Arc::new(ProcessRunner::default())));
+        let bare = BareRepository::clone_with_runner(
+            &url,
+            &tmp_dir.path().join("bare.git"),
+            CloneOptions::default(),
+            runner.clone(),
+        )
+        .expect("Cloned bare repository");
+        bare.head().unwrap();
+        assert_eq!(runner.invocations().len(), 2);
+    }
+}
@@ -0,0 +1,587 @@
+use crate::{ConfigReadError, ConfigSetError, GitCommand, GitError};
+use std::path::{Path, PathBuf};
+
+/// Where a configuration value was read from, see `--show-scope`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigScope {
+    /// `$(prefix)/etc/gitconfig`
+    System,
+    /// `~/.gitconfig` or `$XDG_CONFIG_HOME/git/config`
+    Global,
+    /// `$GIT_DIR/config`
+    Local,
+    /// `$GIT_DIR/config.worktree`
+    Worktree,
+    /// Command line or environment, i.e. `-c` or `GIT_CONFIG_PARAMETERS`
+    Command,
+    #[allow(missing_docs)]
+    Unknown,
+}
+
+impl std::fmt::Display for ConfigScope {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::System => "system",
+            Self::Global => "global",
+            Self::Local => "local",
+            Self::Worktree => "worktree",
+            Self::Command => "command",
+            Self::Unknown => "unknown",
+        };
+        f.write_str(name)
+    }
+}
+
+impl std::str::FromStr for ConfigScope {
+    type Err = GitError;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "system" => Ok(Self::System),
+            "global" => Ok(Self::Global),
+            "local" => Ok(Self::Local),
+            "worktree" => Ok(Self::Worktree),
+            "command" => Ok(Self::Command),
+            "unknown" => Ok(Self::Unknown),
+            _ => Err(GitError::Parse(format!("Unknown config scope `{}`", s))),
+        }
+    }
+}
+
+/// A single configuration value as returned by [`Config::list`] & [`Config::get_regexp`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConfigEntry {
+    #[allow(missing_docs)]
+    pub scope: ConfigScope,
+    /// Origin as printed by `--show-origin`, i.e. `file:.git/config` or `command line:`
+    pub origin: String,
+    /// Full key, i.e. `remote.origin.url`
+    pub key: String,
+    /// `None` for keys without `=`, which are implicitly `true`
+    pub value: Option<String>,
+}
+
+impl ConfigEntry {
+    /// The file the value was read from, if it was read from a file
+    #[must_use]
+    #[inline]
+    pub fn origin_file(&self) -> Option<&Path> {
+        self.origin.strip_prefix("file:").map(Path::new)
+    }
+}
+
+/// Parse `git config -z --show-scope --show-origin` output, records are
+/// `<scope>NUL<origin>NUL<key>LF<value>NUL` and the value is missing for implicit booleans.
+fn parse_entries(text: &str) -> Result<Vec<ConfigEntry>, GitError> {
+    let mut fields = text.split_terminator('\0');
+    let mut result = vec![];
+    while let Some(scope) = fields.next() {
+        let (origin, key_value) = match (fields.next(), fields.next()) {
+            (Some(origin), Some(key_value)) => (origin, key_value),
+            _ => {
+                return Err(GitError::Parse(format!(
+                    "Unexpected git-config(1) record `{}`",
+                    scope
+                )))
+            }
+        };
+        let (key, value) = match key_value.split_once('\n') {
+            Some((key, value)) => (key, Some(value.to_owned())),
+            None => (key_value, None),
+        };
+        result.push(ConfigEntry {
+            scope: scope.parse()?,
+            origin: origin.to_owned(),
+            key: key.to_owned(),
+            value,
+        });
+    }
+    Ok(result)
+}
+
+/// Typed access to the configuration of a repository using
+/// [git-config(1)](https://git-scm.com/docs/git-config).
+///
+/// Typed getters pass `--type` to git, so values are normalized the same way git itself
+/// interprets them. A missing key is returned as `None` or an empty list.
+#[derive(Clone, Debug)]
+pub struct Config {
+    cmd: GitCommand,
+    file: Option<PathBuf>,
+}
+
+impl Config {
+    pub(crate) const fn new(cmd: GitCommand) -> Self {
+        Self { cmd, file: None }
+    }
+
+    /// Read only the configuration `file`, i.e. `.gitmodules`
+    pub(crate) fn with_file(mut self, file: &Path) -> Self {
+        self.file = Some(file.to_path_buf());
+        self
+    }
+
+    fn git(&self) -> GitCommand {
+        let mut cmd = self.cmd.clone();
+        cmd.args(["config", "-z"]);
+        if let Some(file) = &self.file {
+            cmd.arg("--file").arg(file);
+        }
+        cmd
+    }
+
+    /// Run `cmd` and return the raw output, `None` if git found no matching key
+    fn read(mut cmd: GitCommand, key: &str) -> Result<Option<String>, ConfigReadError> {
+        let out = cmd.output()?;
+        if out.status.success() {
+            return Ok(Some(String::from_utf8(out.stdout).map_err(GitError::from)?));
+        }
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        match out.status.code() {
+            Some(1) if stderr.is_empty() => Ok(None),
+            Some(1) => Err(ConfigReadError::InvalidSectionOrKey(key.to_owned())),
+            Some(3) => Err(ConfigReadError::InvalidConfigFile(stderr.to_string())),
+            _ if stderr.contains("bad ") && stderr.contains("config value") => {
+                Err(ConfigReadError::InvalidValue(key.to_owned()))
+            }
+            _ => Err(GitError::from_output(&cmd, &out).into()),
+        }
+    }
+
+    fn get_typed(&self, key: &str, kind: Option<&str>) -> Result<Option<String>, ConfigReadError> {
+        let mut cmd = self.git();
+        if let Some(kind) = kind {
+            cmd.arg(format!("--type={}", kind));
+        }
+        cmd.arg("--get").arg(key);
+        Ok(Self::read(cmd, key)?.map(|value| value.trim_end_matches('\0').to_owned()))
+    }
+
+    /// Return the last value of `key` as written in the configuration
+    ///
+    /// # Errors
+    ///
+    /// See [`ConfigReadError`]
+    #[inline]
+    pub fn get(&self, key: &str) -> Result<Option<String>, ConfigReadError> {
+        self.get_typed(key, None)
+    }
+
+    /// Return `key` interpreted as boolean, i.e. `yes`, `on` & `1` are `true`
+    ///
+    /// # Errors
+    ///
+    /// See [`ConfigReadError`]
+    #[inline]
+    pub fn get_bool(&self, key: &str) -> Result<Option<bool>, ConfigReadError> {
+        self.get_typed(key, Some("bool"))?
+            .map(|value| match value.as_str() {
+                "true" => Ok(true),
+                "false" => Ok(false),
+                _ => Err(ConfigReadError::InvalidValue(key.to_owned())),
+            })
+            .transpose()
+    }
+
+    /// Return `key` interpreted as integer, the suffixes `k`, `m` & `g` are expanded
+    ///
+    /// # Errors
+    ///
+    /// See [`ConfigReadError`]
+    #[inline]
+    pub fn get_int(&self, key: &str) -> Result<Option<i64>, ConfigReadError> {
+        self.get_typed(key, Some("int"))?
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| ConfigReadError::InvalidValue(key.to_owned()))
+            })
+            .transpose()
+    }
+
+    /// Return `key` interpreted as path, a leading `~/` is expanded to the home directory
+    ///
+    /// # Errors
+    ///
+    /// See [`ConfigReadError`]
+    #[inline]
+    pub fn get_path(&self, key: &str) -> Result<Option<PathBuf>, ConfigReadError> {
+        Ok(self.get_typed(key, Some("path"))?.map(PathBuf::from))
+    }
+
+    /// Return `key` interpreted as color, i.e. `red bold` as ANSI escape sequence
+    ///
+    /// # Errors
+    ///
+    /// See [`ConfigReadError`]
+    #[inline]
+    pub fn get_color(&self, key: &str) -> Result<Option<String>, ConfigReadError> {
+        self.get_typed(key, Some("color"))
+    }
+
+    /// Return all values of the multi-valued `key` in the order they are defined
+    ///
+    /// # Errors
+    ///
+    /// See [`ConfigReadError`]
+    #[inline]
+    pub fn get_all(&self, key: &str) -> Result<Vec<String>, ConfigReadError> {
+        let mut cmd = self.git();
+        cmd.arg("--get-all").arg(key);
+        Ok(Self::read(cmd, key)?
+            .map(|text| text.split_terminator('\0').map(ToOwned::to_owned).collect())
+            .unwrap_or_default())
+    }
+
+    /// Return all values with keys matching the regular expression `pattern`
+    ///
+    /// # Errors
+    ///
+    /// See [`ConfigReadError`]
+    #[inline]
+    pub fn get_regexp(&self, pattern: &str) -> Result<Vec<ConfigEntry>, ConfigReadError> {
+        let mut cmd = self.git();
+        cmd.args(["--show-scope", "--show-origin", "--get-regexp"])
+            .arg(pattern);
+        match Self::read(cmd, pattern)? {
+            Some(text) => Ok(parse_entries(&text)?),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Return all configuration values with their origin & scope
+    ///
+    /// # Errors
+    ///
+    /// See [`ConfigReadError`]
+    #[inline]
+    pub fn list(&self) -> Result<Vec<ConfigEntry>, ConfigReadError> {
+        let mut cmd = self.git();
+        cmd.args(["--show-scope", "--show-origin", "--list"]);
+        let text = Self::read(cmd, "")?.unwrap_or_default();
+        Ok(parse_entries(&text)?)
+    }
+}
+
+/// Configuration file written by a [`ConfigWriter`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigLocation {
+    /// `$GIT_DIR/config`
+    Local,
+    /// `$GIT_DIR/config.worktree` if `extensions.worktreeConfig` is enabled, otherwise the
+    /// same as [`ConfigLocation::Local`]
+    Worktree,
+    /// `~/.gitconfig`
+    Global,
+    /// `$(prefix)/etc/gitconfig`
+    System,
+    /// An arbitrary file, i.e. `.gitmodules`
+    File(PathBuf),
+}
+
+/// Change configuration values using [git-config(1)](https://git-scm.com/docs/git-config).
+///
+/// Methods accepting a `value_pattern` only touch values matching the regular expression,
+/// prefix it with `!` to match the values which do not match.
+#[derive(Clone, Debug)]
+pub struct ConfigWriter {
+    cmd: GitCommand,
+}
+
+impl ConfigWriter {
+    pub(crate) fn new(mut cmd: GitCommand, location: &ConfigLocation) -> Self {
+        cmd.arg("config");
+        match location {
+            ConfigLocation::Local => cmd.arg("--local"),
+            ConfigLocation::Worktree => cmd.arg("--worktree"),
+            ConfigLocation::Global => cmd.arg("--global"),
+            ConfigLocation::System => cmd.arg("--system"),
+            ConfigLocation::File(file) => cmd.arg("--file").arg(file),
+        };
+        Self { cmd }
+    }
+
+    /// Run git-config(1) with `args` and map the exit code & stderr to a [`ConfigSetError`]
+    fn run<I, S>(&self, args: I, name: &str) -> Result<(), ConfigSetError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<std::ffi::OsStr>,
+    {
+        let mut cmd = self.cmd.clone();
+        cmd.args(args);
+        let out = cmd.output()?;
+        if out.status.success() {
+            return Ok(());
+        }
+        let msg = String::from_utf8_lossy(&out.stderr).trim_end().to_owned();
+        match out.status.code() {
+            Some(1 | 2) => Err(ConfigSetError::InvalidSectionOrKey(msg)),
+            Some(3) => Err(ConfigSetError::InvalidConfigFile(msg)),
+            Some(4) => Err(ConfigSetError::WriteFailed(msg)),
+            Some(5) if msg.contains("multiple values") => Err(ConfigSetError::MultipleValues(msg)),
+            Some(5) => Err(ConfigSetError::NotFound(name.to_owned())),
+            Some(6) => Err(ConfigSetError::InvalidPattern(msg)),
+            _ if msg.contains("no such section") => Err(ConfigSetError::NotFound(name.to_owned())),
+            _ if msg.contains("invalid section name") => {
+                Err(ConfigSetError::InvalidSectionOrKey(msg))
+            }
+            _ if msg.contains("could not lock") || msg.contains("could not write") => {
+                Err(ConfigSetError::WriteFailed(msg))
+            }
+            _ => Err(GitError::from_output(&cmd, &out).into()),
+        }
+    }
+
+    /// Set `key` to `value`. Without a `value_pattern` a multi-valued `key` fails with
+    /// [`ConfigSetError::MultipleValues`], with one the first matching value is replaced.
+    ///
+    /// # Errors
+    ///
+    /// See [`ConfigSetError`]
+    #[inline]
+    pub fn set(
+        &self,
+        key: &str,
+        value: &str,
+        value_pattern: Option<&str>,
+    ) -> Result<(), ConfigSetError> {
+        self.run(
+            [Some(key), Some(value), value_pattern].iter().flatten(),
+            key,
+        )
+    }
+
+    /// Replace all values of `key`, or only those matching `value_pattern`, with `value`
+    ///
+    /// # Errors
+    ///
+    /// See [`ConfigSetError`]
+    #[inline]
+    pub fn replace_all(
+        &self,
+        key: &str,
+        value: &str,
+        value_pattern: Option<&str>,
+    ) -> Result<(), ConfigSetError> {
+        let args = [Some("--replace-all"), Some(key), Some(value), value_pattern];
+        self.run(args.iter().flatten(), key)
+    }
+
+    /// Append `value` to the multi-valued `key`
+    ///
+    /// # Errors
+    ///
+    /// See [`ConfigSetError`]
+    #[inline]
+    pub fn add(&self, key: &str, value: &str) -> Result<(), ConfigSetError> {
+        self.run(["--add", key, value], key)
+    }
+
+    /// Remove the single value of `key`, or the one matching `value_pattern`
+    ///
+    /// # Errors
+    ///
+    /// See [`ConfigSetError`]
+    #[inline]
+    pub fn unset(&self, key: &str, value_pattern: Option<&str>) -> Result<(), ConfigSetError> {
+        let args = [Some("--unset"), Some(key), value_pattern];
+        self.run(args.iter().flatten(), key)
+    }
+
+    /// Remove all values of `key`, or only those matching `value_pattern`
+    ///
+    /// # Errors
+    ///
+    /// See [`ConfigSetError`]
+    #[inline]
+    pub fn unset_all(&self, key: &str, value_pattern: Option<&str>) -> Result<(), ConfigSetError> {
+        let args = [Some("--unset-all"), Some(key), value_pattern];
+        self.run(args.iter().flatten(), key)
+    }
+
+    /// Rename section `old` to `new`, i.e. `remote.origin` to `remote.upstream`
+    ///
+    /// # Errors
+    ///
+    /// See [`ConfigSetError`]
+    #[inline]
+    pub fn rename_section(&self, old: &str, new: &str) -> Result<(), ConfigSetError> {
+        self.run(["--rename-section", old, new], old)
+    }
+
+    /// Remove section `name` with all its values
+    ///
+    /// # Errors
+    ///
+    /// See [`ConfigSetError`]
+    #[inline]
+    pub fn remove_section(&self, name: &str) -> Result<(), ConfigSetError> {
+        self.run(["--remove-section", name], name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        config_file_set, BareRepository, ConfigEntry, ConfigLocation, ConfigReadError, ConfigScope,
+        ConfigSetError, GenericRepository, Repository,
+    };
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    #[test]
+    fn parse() {
+        let entries =
+            super::parse_entries("local\0file:.git/config\0a.b\nc\0command\0command line:\0d.e\0")
+                .unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                ConfigEntry {
+                    scope: ConfigScope::Local,
+                    origin: "file:.git/config".to_owned(),
+                    key: "a.b".to_owned(),
+                    value: Some("c".to_owned()),
+                },
+                ConfigEntry {
+                    scope: ConfigScope::Command,
+                    origin: "command line:".to_owned(),
+                    key: "d.e".to_owned(),
+                    value: None,
+                },
+            ]
+        );
+        assert_eq!(entries[0].origin_file(), Some(Path::new(".git/config")));
+        assert_eq!(entries[1].origin_file(), None);
+        assert!(super::parse_entries("local\0file:.git/config\0").is_err());
+    }
+
+    #[test]
+    fn typed() {
+        let tmp_dir = TempDir::new().unwrap();
+        let repo = Repository::create(tmp_dir.path()).expect("Created repository");
+        for (key, value) in [
+            ("a.size", "2k"),
+            ("a.flag", "yes"),
+            ("a.color", "red bold"),
+            ("a.path", "~/x"),
+        ] {
+            repo.git().args(["config", key, value]).run().unwrap();
+        }
+        for value in ["x", "y"] {
+            repo.git()
+                .args(["config", "--add", "a.multi", value])
+                .run()
+                .unwrap();
+        }
+        let config = repo.configuration();
+        assert_eq!(config.get("a.size").unwrap().as_deref(), Some("2k"));
+        assert_eq!(config.get_int("a.size").unwrap(), Some(2048));
+        assert_eq!(config.get_bool("a.flag").unwrap(), Some(true));
+        assert_eq!(
+            config.get_color("a.color").unwrap().as_deref(),
+            Some("\u{1b}[1;31m")
+        );
+        let home = std::env::var("HOME").unwrap();
+        assert_eq!(
+            config.get_path("a.path").unwrap(),
+            Some(Path::new(&home).join("x"))
+        );
+        assert_eq!(config.get_all("a.multi").unwrap(), vec!["x", "y"]);
+        assert_eq!(config.get("a.multi").unwrap().as_deref(), Some("y"));
+
+        assert_eq!(config.get_int("a.missing").unwrap(), None);
+        assert!(config.get_all("a.missing").unwrap().is_empty());
+        assert_eq!(
+            config.get_int("a.multi"),
+            Err(ConfigReadError::InvalidValue("a.multi".to_owned()))
+        );
+        assert_eq!(
+            config.get("nosection"),
+            Err(ConfigReadError::InvalidSectionOrKey("nosection".to_owned()))
+        );
+
+        let entries = config.get_regexp(r"^a\.multi$").unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].scope, ConfigScope::Local);
+        assert_eq!(entries[1].value.as_deref(), Some("y"));
+        assert!(config.get_regexp("^nomatch").unwrap().is_empty());
+        let list = config.list().unwrap();
+        assert!(list
+            .iter()
+            .any(|e| e.key == "a.flag" && e.scope == ConfigScope::Local));
+    }
+
+    #[test]
+    fn bare() {
+        let tmp_dir = TempDir::new().unwrap();
+        let repo = BareRepository::create(tmp_dir.path()).expect("Created bare repository");
+        assert_eq!(
+            repo.configuration().get_bool("core.bare").unwrap(),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn write() {
+        let tmp_dir = TempDir::new().unwrap();
+        let repo = Repository::create(tmp_dir.path()).expect("Created repository");
+        let writer = repo.config_writer(&ConfigLocation::Local);
+        let config = repo.configuration();
+        writer.set("a.b", "1", None).unwrap();
+        writer.add("a.b", "2").unwrap();
+        writer.add("a.b", "3").unwrap();
+        assert!(matches!(
+            writer.set("a.b", "4", None),
+            Err(ConfigSetError::MultipleValues(_))
+        ));
+        writer.set("a.b", "4", Some("^2$")).unwrap();
+        assert_eq!(config.get_all("a.b").unwrap(), vec!["1", "4", "3"]);
+        writer.unset("a.b", Some("^4$")).unwrap();
+        writer.replace_all("a.b", "5", Some("^3$")).unwrap();
+        assert_eq!(config.get_all("a.b").unwrap(), vec!["1", "5"]);
+        writer.unset_all("a.b", Some("!^1$")).unwrap();
+        assert_eq!(config.get_all("a.b").unwrap(), vec!["1"]);
+        assert_eq!(
+            writer.unset("a.missing", None),
+            Err(ConfigSetError::NotFound("a.missing".to_owned()))
+        );
+        assert!(matches!(
+            writer.unset_all("a.b", Some("[")),
+            Err(ConfigSetError::InvalidPattern(msg)) if msg.contains("invalid pattern")
+        ));
+        assert!(matches!(
+            writer.set("nosection", "1", None),
+            Err(ConfigSetError::InvalidSectionOrKey(msg)) if msg.contains("nosection")
+        ));
+
+        writer.rename_section("a", "c").unwrap();
+        assert_eq!(config.get("c.b").unwrap().as_deref(), Some("1"));
+        writer.remove_section("c").unwrap();
+        assert_eq!(config.get("c.b").unwrap(), None);
+        assert_eq!(
+            writer.remove_section("c"),
+            Err(ConfigSetError::NotFound("c".to_owned()))
+        );
+
+        let file = tmp_dir.path().join("custom");
+        let bare_dir = tmp_dir.path().join("bare.git");
+        let bare = BareRepository::create(&bare_dir).expect("Created bare repository");
+        bare.config_writer(&ConfigLocation::File(file.clone()))
+            .set("x.y", "z", None)
+            .unwrap();
+        assert!(std::fs::read_to_string(&file).unwrap().contains("y = z"));
+    }
+
+    #[test]
+    fn file_set() {
+        let tmp_dir = TempDir::new().unwrap();
+        let file = tmp_dir.path().join("config");
+        config_file_set(&file, "a.b", "c").unwrap();
+        let err = config_file_set(&tmp_dir.path().join("missing/config"), "a.b", "c")
+            .expect_err("Expected failure");
+        assert!(matches!(err, ConfigSetError::WriteFailed(msg) if msg.contains("could not lock")));
+    }
+}
@@ -0,0 +1,633 @@
+use crate::{GenericRepository, GitError, Repository};
+use std::path::PathBuf;
+
+/// One side of a comparison done by [`Repository::diff`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DiffSide {
+    /// A commit or tree, i.e. `HEAD` or `v1.0^{tree}`
+    Tree(String),
+    /// The staging area
+    Index,
+    /// The working tree
+    WorkTree,
+}
+
+/// Options for [`Repository::diff`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiffOptions {
+    /// Lines of context around changes, git default is 3
+    pub context: Option<u32>,
+    /// Detect renames
+    pub renames: bool,
+    /// Detect copies, implies rename detection
+    pub copies: bool,
+}
+
+impl Default for DiffOptions {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            context: None,
+            renames: true,
+            copies: false,
+        }
+    }
+}
+
+/// Kind of change of a [`FileDiff`]
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Deleted,
+    Modified,
+    Renamed,
+    Copied,
+    /// Unmerged path during a conflict, the hunks of the combined diff are not parsed
+    Unmerged,
+}
+
+/// Kind of a [`Line`]
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineKind {
+    Context,
+    Added,
+    Removed,
+}
+
+/// A single line of a [`Hunk`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Line {
+    /// Context, added or removed
+    pub kind: LineKind,
+    /// Line content without the leading marker and without the line ending
+    pub content: String,
+    /// `\ No newline at end of file` follows this line
+    pub no_newline: bool,
+}
+
+/// A hunk of changes in a [`FileDiff`]
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Hunk {
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    /// Text following the second `@@`, i.e. the function name
+    pub section: String,
+    pub lines: Vec<Line>,
+}
+
+/// Changes to a single file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileDiff {
+    /// Kind of change
+    pub kind: ChangeKind,
+    /// Path before the change, `None` for added files
+    pub old_path: Option<PathBuf>,
+    /// Path after the change, `None` for deleted files
+    pub new_path: Option<PathBuf>,
+    /// Mode before the change, `None` for added files
+    pub old_mode: Option<String>,
+    /// Mode after the change, `None` for deleted files
+    pub new_mode: Option<String>,
+    /// Abbreviated blob id before the change
+    pub old_id: Option<String>,
+    /// Abbreviated blob id after the change
+    pub new_id: Option<String>,
+    /// Similarity index in percent for renames & copies
+    pub similarity: Option<u8>,
+    /// Content is binary, there are no hunks
+    pub binary: bool,
+    #[allow(missing_docs)]
+    pub hunks: Vec<Hunk>,
+}
+
+impl FileDiff {
+    fn new(old_path: PathBuf, new_path: PathBuf) -> Self {
+        Self {
+            kind: ChangeKind::Modified,
+            old_path: Some(old_path),
+            new_path: Some(new_path),
+            old_mode: None,
+            new_mode: None,
+            old_id: None,
+            new_id: None,
+            similarity: None,
+            binary: false,
+            hunks: vec![],
+        }
+    }
+
+    fn unmerged(path: PathBuf) -> Self {
+        Self {
+            kind: ChangeKind::Unmerged,
+            ..Self::new(path.clone(), path)
+        }
+    }
+
+    /// Returns `true` if the file mode changed
+    #[must_use]
+    #[inline]
+    pub fn mode_changed(&self) -> bool {
+        self.kind == ChangeKind::Modified && self.old_mode != self.new_mode
+    }
+}
+
+fn parse_error(line: &str) -> GitError {
+    GitError::Parse(format!("Unexpected git-diff(1) line: `{}`", line))
+}
+
+/// Undo the C-style quoting git applies to unusual path names
+fn unquote(path: &str) -> String {
+    let inner = match path.strip_prefix('"').and_then(|p| p.strip_suffix('"')) {
+        Some(inner) => inner,
+        None => return path.to_owned(),
+    };
+    let mut bytes = vec![];
+    let mut chars = inner.bytes().peekable();
+    while let Some(c) = chars.next() {
+        if c != b'\\' {
+            bytes.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(b'n') => bytes.push(b'\n'),
+            Some(b't') => bytes.push(b'\t'),
+            Some(b'a') => bytes.push(0x07),
+            Some(b'b') => bytes.push(0x08),
+            Some(b'f') => bytes.push(0x0c),
+            Some(b'r') => bytes.push(b'\r'),
+            Some(b'v') => bytes.push(0x0b),
+            Some(d @ b'0'..=b'7') => {
+                let mut value = u32::from(d - b'0');
+                for _ in 0..2 {
+                    if let Some(d @ b'0'..=b'7') = chars.peek().copied() {
+                        value = value * 8 + u32::from(d - b'0');
+                        chars.next();
+                    }
+                }
+                bytes.push(u8::try_from(value).unwrap_or(b'?'));
+            }
+            Some(other) => bytes.push(other),
+            None => bytes.push(b'\\'),
+        }
+    }
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+/// Parse a `---` / `+++` path, returning `None` for `/dev/null`
+fn parse_path(value: &str, prefix: &str) -> Option<PathBuf> {
+    let value = unquote(value.trim_end_matches('\t'));
+    if value == "/dev/null" {
+        return None;
+    }
+    Some(PathBuf::from(
+        value.strip_prefix(prefix).unwrap_or(&value).to_owned(),
+    ))
+}
+
+/// Parse the `a/foo b/foo` part of a `diff --git` header
+fn parse_header_paths(value: &str) -> Option<(PathBuf, PathBuf)> {
+    if let Some(quoted) = value.strip_prefix('"') {
+        let end = quoted.find("\" ").map(|i| i + 2)?;
+        let old = parse_path(&value[..end], "a/")?;
+        let new = parse_path(&value[end + 1..], "b/")?;
+        return Some((old, new));
+    }
+    if let Some(index) = value.find(" \"b/") {
+        let old = parse_path(&value[..index], "a/")?;
+        let new = parse_path(&value[index + 1..], "b/")?;
+        return Some((old, new));
+    }
+    // Without renames both names are equal, so split in the middle
+    let middle = value.len() / 2;
+    let (old, new) = (value.get(..middle)?, value.get(middle + 1..)?);
+    if old.strip_prefix("a/") == new.strip_prefix("b/") {
+        return Some((parse_path(old, "a/")?, parse_path(new, "b/")?));
+    }
+    let index = value.find(" b/")?;
+    Some((
+        parse_path(&value[..index], "a/")?,
+        parse_path(&value[index + 1..], "b/")?,
+    ))
+}
+
+fn parse_range(range: &str) -> Option<(u32, u32)> {
+    match range.split_once(',') {
+        Some((start, lines)) => Some((start.parse().ok()?, lines.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+fn parse_hunk_header(line: &str) -> Option<Hunk> {
+    let rest = line.strip_prefix("@@ -")?;
+    let (ranges, section) = rest.split_once(" @@")?;
+    let (old, new) = ranges.split_once(" +")?;
+    let (old_start, old_lines) = parse_range(old)?;
+    let (new_start, new_lines) = parse_range(new)?;
+    Some(Hunk {
+        old_start,
+        old_lines,
+        new_start,
+        new_lines,
+        section: section.trim_start().to_owned(),
+        lines: vec![],
+    })
+}
+
+fn parse_diff(text: &str) -> Result<Vec<FileDiff>, GitError> {
+    let mut result: Vec<FileDiff> = vec![];
+    // Remaining old & new lines of the current hunk
+    let mut remaining: (u32, u32) = (0, 0);
+    // Inside a combined diff of an unmerged path
+    let mut combined = false;
+    for line in text.split_terminator('\n') {
+        if let Some(header) = line.strip_prefix("diff --git ") {
+            let (old, new) = parse_header_paths(header).ok_or_else(|| parse_error(line))?;
+            result.push(FileDiff::new(old, new));
+            remaining = (0, 0);
+            combined = false;
+            continue;
+        }
+        let unmerged = line
+            .strip_prefix("diff --cc ")
+            .or_else(|| line.strip_prefix("diff --combined "))
+            .or_else(|| line.strip_prefix("* Unmerged path "));
+        if let Some(path) = unmerged {
+            result.push(FileDiff::unmerged(PathBuf::from(unquote(path))));
+            remaining = (0, 0);
+            combined = true;
+            continue;
+        }
+        if combined {
+            continue;
+        }
+        let file = result.last_mut().ok_or_else(|| parse_error(line))?;
+
+        if remaining != (0, 0) || line.starts_with('\\') {
+            let hunk = file.hunks.last_mut().ok_or_else(|| parse_error(line))?;
+            let (marker, content) = line.split_at_checked(1).unwrap_or((" ", ""));
+            let kind = match marker {
+                " " => {
+                    remaining = (remaining.0.saturating_sub(1), remaining.1.saturating_sub(1));
+                    LineKind::Context
+                }
+                "-" => {
+                    remaining.0 = remaining.0.saturating_sub(1);
+                    LineKind::Removed
+                }
+                "+" => {
+                    remaining.1 = remaining.1.saturating_sub(1);
+                    LineKind::Added
+                }
+                "\\" => {
+                    let last = hunk.lines.last_mut().ok_or_else(|| parse_error(line))?;
+                    last.no_newline = true;
+                    continue;
+                }
+                _ => return Err(parse_error(line)),
+            };
+            hunk.lines.push(Line {
+                kind,
+                content: content.to_owned(),
+                no_newline: false,
+            });
+            continue;
+        }
+
+        if line.starts_with("@@ ") {
+            let hunk = parse_hunk_header(line).ok_or_else(|| parse_error(line))?;
+            remaining = (hunk.old_lines, hunk.new_lines);
+            file.hunks.push(hunk);
+        } else if let Some(mode) = line.strip_prefix("old mode ") {
+            file.old_mode = Some(mode.to_owned());
+        } else if let Some(mode) = line.strip_prefix("new mode ") {
+            file.new_mode = Some(mode.to_owned());
+        } else if let Some(mode) = line.strip_prefix("deleted file mode ") {
+            file.kind = ChangeKind::Deleted;
+            file.old_mode = Some(mode.to_owned());
+            file.new_path = None;
+        } else if let Some(mode) = line.strip_prefix("new file mode ") {
+            file.kind = ChangeKind::Added;
+            file.new_mode = Some(mode.to_owned());
+            file.old_path = None;
+        } else if let Some(score) = line.strip_prefix("similarity index ") {
+            let score = score.trim_end_matches('%').parse();
+            file.similarity = Some(score.map_err(|_| parse_error(line))?);
+        } else if let Some(path) = line.strip_prefix("rename from ") {
+            file.kind = ChangeKind::Renamed;
+            file.old_path = Some(PathBuf::from(unquote(path)));
+        } else if let Some(path) = line.strip_prefix("rename to ") {
+            file.new_path = Some(PathBuf::from(unquote(path)));
+        } else if let Some(path) = line.strip_prefix("copy from ") {
+            file.kind = ChangeKind::Copied;
+            file.old_path = Some(PathBuf::from(unquote(path)));
+        } else if let Some(path) = line.strip_prefix("copy to ") {
+            file.new_path = Some(PathBuf::from(unquote(path)));
+        } else if let Some(index) = line.strip_prefix("index ") {
+            let (ids, mode) = match index.split_once(' ') {
+                Some((ids, mode)) => (ids, Some(mode)),
+                None => (index, None),
+            };
+            let (old_id, new_id) = ids.split_once("..").ok_or_else(|| parse_error(line))?;
+            file.old_id = Some(old_id.to_owned());
+            file.new_id = Some(new_id.to_owned());
+            if let Some(mode) = mode {
+                file.old_mode = Some(mode.to_owned());
+                file.new_mode = Some(mode.to_owned());
+            }
+        } else if let Some(path) = line.strip_prefix("--- ") {
+            file.old_path = parse_path(path, "a/");
+        } else if let Some(path) = line.strip_prefix("+++ ") {
+            file.new_path = parse_path(path, "b/");
+        } else if line.starts_with("Binary files ") || line == "GIT binary patch" {
+            file.binary = true;
+        } else if line.starts_with("dissimilarity index ") {
+            // Only reported for complete rewrites, nothing to keep
+        } else {
+            return Err(parse_error(line));
+        }
+    }
+    Ok(result)
+}
+
+/// Diff
+impl Repository {
+    /// Compare `from` with `to` using [git-diff(1)](https://git-scm.com/docs/git-diff)
+    ///
+    /// Supported comparisons are tree vs tree, tree vs index (`--cached`), tree vs work tree
+    /// and index vs work tree. An empty `pathspecs` compares all files. Conflicted files are
+    /// reported as [`ChangeKind::Unmerged`] without hunks.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`GitError::Invalid`] for unsupported comparisons, see [`GitError`] otherwise.
+    #[inline]
+    pub fn diff(
+        &self,
+        from: &DiffSide,
+        to: &DiffSide,
+        pathspecs: &[&str],
+        options: &DiffOptions,
+    ) -> Result<Vec<FileDiff>, GitError> {
+        let mut cmd = self.git();
+        cmd.args([
+            "diff",
+            "--no-color",
+            "--no-ext-diff",
+            "--no-textconv",
+            "--submodule=short",
+            "--src-prefix=a/",
+            "--dst-prefix=b/",
+        ]);
+        if let Some(context) = options.context {
+            cmd.arg(format!("--unified={}", context));
+        }
+        if options.copies {
+            cmd.arg("--find-copies");
+        } else if options.renames {
+            cmd.arg("--find-renames");
+        } else {
+            cmd.arg("--no-renames");
+        }
+        match (from, to) {
+            (DiffSide::Tree(a), DiffSide::Tree(b)) => {
+                cmd.args(["--end-of-options", a, b]);
+            }
+            (DiffSide::Tree(a), DiffSide::Index) => {
+                cmd.args(["--cached", "--end-of-options", a]);
+            }
+            (DiffSide::Tree(a), DiffSide::WorkTree) => {
+                cmd.args(["--end-of-options", a]);
+            }
+            (DiffSide::Index, DiffSide::WorkTree) => {}
+            _ => {
+                return Err(GitError::Invalid(format!(
+                    "Unsupported comparison {:?} vs {:?}",
+                    from, to
+                )))
+            }
+        }
+        cmd.arg("--").args(pathspecs);
+        let out = cmd.run()?;
+        parse_diff(&String::from_utf8_lossy(&out.stdout))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        setup_test_author, ChangeKind, DiffOptions, DiffSide, GenericRepository, LineKind,
+        Repository,
+    };
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    fn repo_with_commit(tmp_dir: &TempDir) -> Repository {
+        setup_test_author();
+        let repo = Repository::create(tmp_dir.path()).expect("Created repository");
+        let foo = tmp_dir.path().join("foo.txt");
+        std::fs::write(&foo, "one\ntwo\nthree\n").unwrap();
+        repo.stage(&foo).unwrap();
+        repo.commit("Initial").unwrap();
+        repo
+    }
+
+    #[test]
+    fn work_tree_vs_index() {
+        let tmp_dir = TempDir::new().unwrap();
+        let repo = repo_with_commit(&tmp_dir);
+        std::fs::write(tmp_dir.path().join("foo.txt"), "one\nTWO\nthree").unwrap();
+        let diff = repo
+            .diff(
+                &DiffSide::Index,
+                &DiffSide::WorkTree,
+                &[],
+                &DiffOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(diff.len(), 1);
+        let file = &diff[0];
+        assert_eq!(file.kind, ChangeKind::Modified);
+        assert_eq!(file.new_path, Some(PathBuf::from("foo.txt")));
+        assert_eq!(file.hunks.len(), 1);
+        let hunk = &file.hunks[0];
+        assert_eq!((hunk.old_start, hunk.old_lines), (1, 3));
+        let kinds: Vec<_> = hunk.lines.iter().map(|l| l.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                LineKind::Context,
+                LineKind::Removed,
+                LineKind::Removed,
+                LineKind::Added,
+                LineKind::Added
+            ]
+        );
+        assert_eq!(hunk.lines[3].content, "TWO");
+        assert!(hunk.lines[4].no_newline);
+        assert!(!hunk.lines[2].no_newline);
+    }
+
+    #[test]
+    fn index_vs_head() {
+        let tmp_dir = TempDir::new().unwrap();
+        let repo = repo_with_commit(&tmp_dir);
+        repo.git()
+            .args(["mv", "foo.txt", "bar baz.txt"])
+            .run()
+            .unwrap();
+        let bin = tmp_dir.path().join("data.bin");
+        std::fs::write(&bin, [0_u8, 1, 2, 0, 255]).unwrap();
+        repo.stage(&bin).unwrap();
+        let head = DiffSide::Tree("HEAD".to_owned());
+        let diff = repo
+            .diff(&head, &DiffSide::Index, &[], &DiffOptions::default())
+            .unwrap();
+        assert_eq!(diff.len(), 2);
+        let renamed = diff.iter().find(|f| f.kind == ChangeKind::Renamed).unwrap();
+        assert_eq!(renamed.old_path, Some(PathBuf::from("foo.txt")));
+        assert_eq!(renamed.new_path, Some(PathBuf::from("bar baz.txt")));
+        assert_eq!(renamed.similarity, Some(100));
+        let added = diff.iter().find(|f| f.kind == ChangeKind::Added).unwrap();
+        assert!(added.binary);
+        assert_eq!(added.old_path, None);
+        assert_eq!(added.new_mode, Some("100644".to_owned()));
+
+        let only_bin = repo
+            .diff(
+                &head,
+                &DiffSide::Index,
+                &["data.bin"],
+                &DiffOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(only_bin.len(), 1);
+    }
+
+    #[test]
+    fn tree_vs_tree() {
+        let tmp_dir = TempDir::new().unwrap();
+        let repo = repo_with_commit(&tmp_dir);
+        let foo = tmp_dir.path().join("foo.txt");
+        std::fs::remove_file(&foo).unwrap();
+        repo.stage(&foo).unwrap();
+        repo.commit("Remove foo").unwrap();
+        let diff = repo
+            .diff(
+                &DiffSide::Tree("HEAD~".to_owned()),
+                &DiffSide::Tree("HEAD".to_owned()),
+                &[],
+                &DiffOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(diff.len(), 1);
+        assert_eq!(diff[0].kind, ChangeKind::Deleted);
+        assert_eq!(diff[0].new_path, None);
+        assert_eq!(diff[0].hunks[0].lines.len(), 3);
+
+        let invalid = repo.diff(
+            &DiffSide::WorkTree,
+            &DiffSide::Index,
+            &[],
+            &DiffOptions::default(),
+        );
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn conflict() {
+        let tmp_dir = TempDir::new().unwrap();
+        let repo = repo_with_commit(&tmp_dir);
+        let foo = tmp_dir.path().join("foo.txt");
+        repo.git()
+            .args(["checkout", "--quiet", "-b", "other"])
+            .run()
+            .unwrap();
+        std::fs::write(&foo, "one\nother\nthree\n").unwrap();
+        repo.stage(&foo).unwrap();
+        repo.commit("Other").unwrap();
+        repo.git().args(["checkout", "--quiet", "-"]).run().unwrap();
+        std::fs::write(&foo, "one\nmain\nthree\n").unwrap();
+        std::fs::write(tmp_dir.path().join("bar.txt"), "bar\n").unwrap();
+        repo.git().args(["add", "."]).run().unwrap();
+        repo.commit("Main").unwrap();
+        let merge = repo.git().args(["merge", "other"]).output().unwrap();
+        assert!(!merge.status.success());
+        std::fs::write(tmp_dir.path().join("bar.txt"), "baz\n").unwrap();
+
+        let diff = repo
+            .diff(
+                &DiffSide::Index,
+                &DiffSide::WorkTree,
+                &[],
+                &DiffOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(diff.len(), 2);
+        let unmerged = diff
+            .iter()
+            .find(|f| f.kind == ChangeKind::Unmerged)
+            .unwrap();
+        assert_eq!(unmerged.old_path, Some(PathBuf::from("foo.txt")));
+        assert_eq!(unmerged.new_path, Some(PathBuf::from("foo.txt")));
+        assert!(unmerged.hunks.is_empty());
+        let modified = diff
+            .iter()
+            .find(|f| f.kind == ChangeKind::Modified)
+            .unwrap();
+        assert_eq!(modified.new_path, Some(PathBuf::from("bar.txt")));
+        assert_eq!(modified.hunks.len(), 1);
+
+        let cached = repo
+            .diff(
+                &DiffSide::Tree("HEAD".to_owned()),
+                &DiffSide::Index,
+                &[],
+                &DiffOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(cached.len(), 1);
+        assert_eq!(cached[0].kind, ChangeKind::Unmerged);
+    }
+
+    #[test]
+    fn submodule_log() {
+        let tmp_dir = TempDir::new().unwrap();
+        let repo = repo_with_commit(&tmp_dir);
+        let sub_dir = tmp_dir.path().join("sub");
+        std::fs::create_dir(&sub_dir).unwrap();
+        let sub = Repository::create(&sub_dir).expect("Created repository");
+        sub.commit_extended("First", true, true).unwrap();
+        repo.stage(&sub_dir).unwrap();
+        repo.commit("Add sub").unwrap();
+        sub.commit_extended("Second", true, true).unwrap();
+        repo.git()
+            .args(["config", "diff.submodule", "log"])
+            .run()
+            .unwrap();
+
+        let diff = repo
+            .diff(
+                &DiffSide::Index,
+                &DiffSide::WorkTree,
+                &[],
+                &DiffOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(diff.len(), 1);
+        assert_eq!(diff[0].new_path, Some(PathBuf::from("sub")));
+        assert_eq!(diff[0].new_mode, Some("160000".to_owned()));
+        let lines = &diff[0].hunks[0].lines;
+        assert!(lines[0].content.starts_with("Subproject commit "));
+    }
+
+    #[test]
+    fn unquote() {
+        assert_eq!(super::unquote("\"a/f\\303\\266o\\tbar\""), "a/föo\tbar");
+        assert_eq!(super::unquote("a/plain"), "a/plain");
+    }
+}
@@ -0,0 +1,265 @@
+use crate::{AbsoluteDirPath, GitCommand, RepoError, Repository};
+use std::path::{Path, PathBuf};
+
+/// Returns `true` if `path` looks like a git directory. Linked worktrees have no `objects`
+/// directory, they share it through their `commondir`.
+fn is_git_dir(path: &Path) -> bool {
+    path.join("HEAD").is_file()
+        && (path.join("objects").is_dir() || path.join("commondir").is_file())
+}
+
+/// Follow a `.git` file containing `gitdir: <path>`, as used by linked worktrees & submodules.
+/// A relative path is relative to the directory containing the file.
+fn read_git_file(file: &Path) -> Result<PathBuf, RepoError> {
+    let invalid = || RepoError::InvalidGitFile(file.to_path_buf());
+    let content = std::fs::read_to_string(file).map_err(|_| invalid())?;
+    let target = content
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("gitdir: "))
+        .ok_or_else(invalid)?;
+    let path = file.parent().ok_or_else(invalid)?.join(target.trim_end());
+    if is_git_dir(&path) {
+        path.canonicalize().map_err(|_| invalid())
+    } else {
+        Err(invalid())
+    }
+}
+
+/// Resolve the git directory for the `.git` entry of `dir`, which may be a directory or a file
+pub(crate) fn dot_git(dir: &Path) -> Result<Option<PathBuf>, RepoError> {
+    let candidate = dir.join(".git");
+    if candidate.is_file() {
+        read_git_file(&candidate).map(Some)
+    } else if is_git_dir(&candidate) {
+        Ok(Some(candidate))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Parse a boolean environment variable the way git does
+fn env_flag(name: &str) -> bool {
+    std::env::var(name)
+        .is_ok_and(|value| matches!(value.to_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+}
+
+#[cfg(unix)]
+fn device(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).ok().map(|m| m.dev())
+}
+
+#[cfg(not(unix))]
+const fn device(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Search the git directory starting at `start` & walking up, like git does.
+///
+/// Honours `GIT_CEILING_DIRECTORIES` & `GIT_DISCOVERY_ACROSS_FILESYSTEM`.
+pub(crate) fn search_git_dir(start: &Path) -> Result<AbsoluteDirPath, RepoError> {
+    let ceilings: Vec<PathBuf> = std::env::var_os("GIT_CEILING_DIRECTORIES")
+        .map(|value| {
+            std::env::split_paths(&value)
+                .filter(|p| p.is_absolute())
+                .map(|p| p.canonicalize().unwrap_or(p))
+                .collect()
+        })
+        .unwrap_or_default();
+    let across_fs = env_flag("GIT_DISCOVERY_ACROSS_FILESYSTEM");
+    search_git_dir_bounded(start, &ceilings, across_fs)
+}
+
+fn search_git_dir_bounded(
+    start: &Path,
+    ceilings: &[PathBuf],
+    across_fs: bool,
+) -> Result<AbsoluteDirPath, RepoError> {
+    let path = start
+        .canonicalize()
+        .map_err(|_| RepoError::InvalidDirectory(start.to_path_buf()))?;
+    let start_device = device(&path);
+
+    for (i, dir) in path.ancestors().enumerate() {
+        if i > 0 {
+            if ceilings.iter().any(|c| c == dir) {
+                break;
+            }
+            if !across_fs && device(dir) != start_device {
+                break;
+            }
+        }
+        if let Some(git_dir) = dot_git(dir)? {
+            return git_dir.as_path().try_into();
+        }
+        if is_git_dir(dir) {
+            return dir.try_into();
+        }
+    }
+    Err(RepoError::GitDirNotFound)
+}
+
+/// Find the work tree belonging to `git_dir`.
+///
+/// Uses `core.worktree` if set, the `gitdir` back link of linked worktrees or the parent
+/// directory.
+pub(crate) fn work_tree_from_git_dir(
+    cmd: &GitCommand,
+    git_dir: &AbsoluteDirPath,
+) -> Result<AbsoluteDirPath, RepoError> {
+    let git = || {
+        let mut cmd = cmd.clone();
+        cmd.arg("--git-dir").arg(git_dir.0.as_os_str());
+        cmd
+    };
+    let output = git().args(["rev-parse", "--is-bare-repository"]).output()?;
+    if output.status.success() && String::from_utf8_lossy(&output.stdout).trim() == "true" {
+        return Err(RepoError::BareRepo);
+    }
+
+    let output = git().args(["config", "--get", "core.worktree"]).output()?;
+    if output.status.success() {
+        let value = String::from_utf8_lossy(&output.stdout);
+        let path = git_dir.0.join(value.trim_end());
+        let work_tree = path
+            .canonicalize()
+            .map_err(|_| RepoError::InvalidDirectory(path))?;
+        return work_tree.as_path().try_into();
+    }
+
+    if let Ok(link) = std::fs::read_to_string(git_dir.0.join("gitdir")) {
+        let dot_git = git_dir.0.join(link.trim_end());
+        if let Some(dir) = dot_git.parent() {
+            return dir.try_into();
+        }
+    }
+
+    match git_dir.0.parent() {
+        Some(dir) => Ok(AbsoluteDirPath::try_from(dir)?),
+        None => Err(RepoError::BareRepo),
+    }
+}
+
+pub(crate) fn git_dir_from_work_tree(
+    work_tree: &AbsoluteDirPath,
+) -> Result<AbsoluteDirPath, RepoError> {
+    match dot_git(&work_tree.0)? {
+        Some(git_dir) => git_dir.as_path().try_into(),
+        None => work_tree.0.join(".git").as_path().try_into(),
+    }
+}
+
+/// Worktrees
+impl Repository {
+    /// Return path to the git directory shared by all worktrees, `GIT_COMMON_DIR`. This is
+    /// the same as `GIT_DIR` unless this is a linked worktree.
+    #[must_use]
+    #[inline]
+    pub fn common_dir(&self) -> PathBuf {
+        std::fs::read_to_string(self.git_dir.0.join("commondir")).map_or_else(
+            |_| self.git_dir.0.clone(),
+            |common| {
+                let path = self.git_dir.0.join(common.trim_end());
+                path.canonicalize().unwrap_or(path)
+            },
+        )
+    }
+
+    /// Returns `true` if this is a worktree created by
+    /// [git-worktree(1)](https://git-scm.com/docs/git-worktree) and not the main one
+    #[must_use]
+    #[inline]
+    pub fn is_linked_worktree(&self) -> bool {
+        self.git_dir.0.join("commondir").is_file()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        setup_test_author, GenericRepository, ProcessRunner, RecordingRunner, RepoError, Repository,
+    };
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    #[test]
+    fn linked_worktree() {
+        setup_test_author();
+        let tmp_dir = TempDir::new().unwrap();
+        let root = tmp_dir.path().canonicalize().unwrap();
+        let main_dir = root.join("main");
+        let main = Repository::create(&main_dir).expect("Created repository");
+        main.commit_extended("First", true, true).unwrap();
+        let wt_dir = root.join("wt");
+        main.git()
+            .args(["worktree", "add", "-b", "feature"])
+            .arg(&wt_dir)
+            .run()
+            .unwrap();
+        std::fs::create_dir_all(wt_dir.join("a/b")).unwrap();
+
+        let repo = Repository::discover(&wt_dir.join("a/b")).unwrap();
+        assert_eq!(repo.work_tree(), Some(wt_dir.clone()));
+        assert_eq!(repo.git_dir(), main_dir.join(".git/worktrees/wt"));
+        assert_eq!(repo.common_dir(), main_dir.join(".git"));
+        assert!(repo.is_linked_worktree());
+        assert_eq!(repo.current_branch().unwrap().as_deref(), Some("feature"));
+
+        let repo = Repository::from_args(None, None, Some(wt_dir.to_str().unwrap())).unwrap();
+        assert_eq!(repo.git_dir(), main_dir.join(".git/worktrees/wt"));
+
+        assert_eq!(main.common_dir(), main_dir.join(".git"));
+        assert!(!main.is_linked_worktree());
+
+        std::fs::write(wt_dir.join(".git"), "garbage").unwrap();
+        assert_eq!(
+            Repository::discover(&wt_dir).unwrap_err(),
+            RepoError::InvalidGitFile(wt_dir.join(".git"))
+        );
+    }
+
+    #[test]
+    fn core_worktree() {
+        let tmp_dir = TempDir::new().unwrap();
+        let root = tmp_dir.path().canonicalize().unwrap();
+        let work_dir = root.join("work");
+        std::fs::create_dir_all(&work_dir).unwrap();
+        let repo = Repository::create(&root.join("repo")).expect("Created repository");
+        repo.git()
+            .args(["config", "core.worktree", "../../work"])
+            .run()
+            .unwrap();
+        let git_dir = root.join("repo/.git");
+        let repo = Repository::from_args(None, Some(git_dir.to_str().unwrap()), None).unwrap();
+        assert_eq!(repo.work_tree(), Some(work_dir.clone()));
+
+        let runner = Arc::new(RecordingRunner::new(Arc::new(ProcessRunner::default())));
+        let repo = Repository::discover_with_runner(&git_dir, runner.clone()).unwrap();
+        assert_eq!(repo.work_tree(), Some(work_dir));
+        let invocations = runner.invocations();
+        assert!(invocations
+            .iter()
+            .any(|i| i.args.contains(&"core.worktree".to_owned())));
+        repo.head().unwrap_err();
+        assert!(runner.invocations().len() > invocations.len());
+    }
+
+    #[test]
+    fn ceiling() {
+        let tmp_dir = TempDir::new().unwrap();
+        let root = tmp_dir.path().canonicalize().unwrap();
+        Repository::create(&root).expect("Created repository");
+        let nested = root.join("a/b");
+        std::fs::create_dir_all(&nested).unwrap();
+
+        let found = super::search_git_dir_bounded(&nested, &[], false).unwrap();
+        assert_eq!(found.0, root.join(".git"));
+        let err = super::search_git_dir_bounded(&nested, &[root.join("a")], false)
+            .expect_err("Expected ceiling to stop the search");
+        assert_eq!(err, RepoError::GitDirNotFound);
+        let found =
+            super::search_git_dir_bounded(&root.join("a"), &[root.join("a")], false).unwrap();
+        assert_eq!(found.0, root.join(".git"));
+    }
+}
@@ -0,0 +1,142 @@
+use crate::GitCommand;
+use posix_errors::{PosixError, EINVAL};
+use std::process::Output;
+
+/// Failure while executing [git(1)](https://git-scm.com/docs/git)
+///
+/// All operations of this crate either return this error directly or an operation specific
+/// error which can be converted into it.
+#[derive(thiserror::Error, Debug)]
+pub enum GitError {
+    /// Failed to spawn the git process, i.e. the binary is missing
+    #[error("Failed to execute git: {0}")]
+    Spawn(#[source] std::io::Error),
+    /// git exited with a non-zero exit code
+    #[error("`git {}` exited with code {code}: {}", .args.join(" "), .stderr.trim_end())]
+    Failure {
+        /// Arguments passed to git
+        args: Vec<String>,
+        /// Exit code
+        code: i32,
+        /// Captured `STDOUT`
+        stdout: String,
+        /// Captured `STDERR`
+        stderr: String,
+    },
+    /// git output is not valid UTF-8
+    #[error("Failed to decode git output: {0}")]
+    Utf8(#[from] std::string::FromUtf8Error),
+    /// git returned output we do not understand
+    #[error("Failed to parse git output: {0}")]
+    Parse(String),
+    /// Invalid argument or repository state detected before or after running git
+    #[error("{0}")]
+    Invalid(String),
+}
+
+impl GitError {
+    /// Create a [`GitError::Failure`] from a finished command
+    #[must_use]
+    #[inline]
+    pub fn from_output(cmd: &GitCommand, output: &Output) -> Self {
+        Self::Failure {
+            args: cmd
+                .get_args()
+                .map(|a| a.to_string_lossy().to_string())
+                .collect(),
+            code: output.status.code().unwrap_or(1),
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        }
+    }
+
+    /// Exit code of git, if git was executed and failed
+    #[must_use]
+    #[inline]
+    pub const fn code(&self) -> Option<i32> {
+        match self {
+            Self::Failure { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+
+    /// `STDERR` of git, if git was executed and failed
+    #[must_use]
+    #[inline]
+    pub fn stderr(&self) -> Option<&str> {
+        match self {
+            Self::Failure { stderr, .. } => Some(stderr.as_str()),
+            _ => None,
+        }
+    }
+}
+
+impl PartialEq for GitError {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Spawn(a), Self::Spawn(b)) => a.kind() == b.kind(),
+            (
+                Self::Failure {
+                    args,
+                    code,
+                    stdout,
+                    stderr,
+                },
+                Self::Failure {
+                    args: o_args,
+                    code: o_code,
+                    stdout: o_stdout,
+                    stderr: o_stderr,
+                },
+            ) => args == o_args && code == o_code && stdout == o_stdout && stderr == o_stderr,
+            (Self::Utf8(a), Self::Utf8(b)) => a == b,
+            (Self::Parse(a), Self::Parse(b)) | (Self::Invalid(a), Self::Invalid(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for GitError {}
+
+impl From<GitError> for PosixError {
+    #[inline]
+    fn from(err: GitError) -> Self {
+        match err {
+            GitError::Spawn(e) => e.into(),
+            GitError::Failure { code, .. } => Self::new(code, format!("{}", err)),
+            GitError::Utf8(_) | GitError::Parse(_) | GitError::Invalid(_) => {
+                Self::new(EINVAL, format!("{}", err))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{GitCommand, GitError, ProcessRunner};
+    use posix_errors::PosixError;
+    use std::sync::Arc;
+
+    #[test]
+    fn missing_binary() {
+        let runner = Arc::new(ProcessRunner::new("git-wrapper-does-not-exist"));
+        let err = GitCommand::new(runner)
+            .arg("version")
+            .read()
+            .expect_err("Expected spawn failure");
+        assert!(matches!(err, GitError::Spawn(_)));
+    }
+
+    #[test]
+    fn failure() {
+        let err = GitCommand::new(Arc::new(ProcessRunner::default()))
+            .args(["rev-parse", "--no-such-option-exists"])
+            .env("GIT_DIR", "/nonexistent")
+            .read()
+            .expect_err("Expected git failure");
+        assert_eq!(err.code(), Some(128));
+        let posix: PosixError = err.into();
+        assert_eq!(posix.code(), 128);
+    }
+}
@@ -0,0 +1,477 @@
+use crate::{GitCommand, GitError, Progress, ProgressCallback};
+use std::collections::HashMap;
+
+/// Kind of a reference update reported by [`crate::GenericRepository::fetch`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UpdateFlag {
+    /// `' '` fast-forward update
+    FastForward,
+    /// `'+'` forced update
+    Forced,
+    /// `'-'` reference was pruned
+    Pruned,
+    /// `'t'` tag update
+    TagUpdate,
+    /// `'*'` new reference
+    New,
+    /// `'!'` update was rejected
+    Rejected,
+    /// `'='` reference is up to date
+    UpToDate,
+}
+
+impl TryFrom<char> for UpdateFlag {
+    type Error = GitError;
+
+    #[inline]
+    fn try_from(value: char) -> Result<Self, Self::Error> {
+        match value {
+            ' ' => Ok(Self::FastForward),
+            '+' => Ok(Self::Forced),
+            '-' => Ok(Self::Pruned),
+            't' => Ok(Self::TagUpdate),
+            '*' => Ok(Self::New),
+            '!' => Ok(Self::Rejected),
+            '=' => Ok(Self::UpToDate),
+            _ => Err(GitError::Parse(format!("Unknown update flag `{}`", value))),
+        }
+    }
+}
+
+/// A local reference updated by [`crate::GenericRepository::fetch`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RefUpdate {
+    #[allow(missing_docs)]
+    pub flag: UpdateFlag,
+    /// Previous object id, all zeros for new references
+    pub old: String,
+    /// New object id, all zeros for pruned references. Before git 2.41 the id of rejected
+    /// updates is unknown & all zeros, too.
+    pub new: String,
+    /// Full name of the local reference
+    pub name: String,
+}
+
+/// Options for [`crate::GenericRepository::fetch`]
+#[derive(Default)]
+pub struct FetchOptions<'a> {
+    refspecs: Vec<String>,
+    prune: bool,
+    tags: Option<bool>,
+    depth: Option<u32>,
+    deepen: Option<u32>,
+    unshallow: bool,
+    filter: Option<String>,
+    progress: Option<ProgressCallback<'a>>,
+}
+
+impl std::fmt::Debug for FetchOptions<'_> {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FetchOptions")
+            .field("refspecs", &self.refspecs)
+            .field("prune", &self.prune)
+            .field("tags", &self.tags)
+            .field("depth", &self.depth)
+            .field("deepen", &self.deepen)
+            .field("unshallow", &self.unshallow)
+            .field("filter", &self.filter)
+            .field("progress", &self.progress.is_some())
+            .finish()
+    }
+}
+
+impl<'a> FetchOptions<'a> {
+    /// Fetch the given refspec instead of the configured ones; may be repeated
+    #[must_use]
+    #[inline]
+    pub fn refspec(mut self, refspec: &str) -> Self {
+        self.refspecs.push(refspec.to_owned());
+        self
+    }
+
+    /// Remove remote-tracking references which no longer exist on the remote
+    #[must_use]
+    #[inline]
+    pub const fn prune(mut self, prune: bool) -> Self {
+        self.prune = prune;
+        self
+    }
+
+    /// Fetch all tags (`true`) or none (`false`) instead of only tags pointing into the
+    /// fetched history
+    #[must_use]
+    #[inline]
+    pub const fn tags(mut self, tags: bool) -> Self {
+        self.tags = Some(tags);
+        self
+    }
+
+    /// Limit the history to `depth` commits from the tip of each remote branch
+    #[must_use]
+    #[inline]
+    pub const fn depth(mut self, depth: u32) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    /// Deepen the history of a shallow repository by `depth` commits
+    #[must_use]
+    #[inline]
+    pub const fn deepen(mut self, depth: u32) -> Self {
+        self.deepen = Some(depth);
+        self
+    }
+
+    /// Convert a shallow repository into a complete one
+    #[must_use]
+    #[inline]
+    pub const fn unshallow(mut self, unshallow: bool) -> Self {
+        self.unshallow = unshallow;
+        self
+    }
+
+    /// Partial clone filter, i.e. `blob:none`
+    #[must_use]
+    #[inline]
+    pub fn filter(mut self, filter: &str) -> Self {
+        self.filter = Some(filter.to_owned());
+        self
+    }
+
+    /// Call `callback` for every progress update
+    #[must_use]
+    #[inline]
+    pub fn progress(mut self, callback: impl FnMut(&Progress) + 'a) -> Self {
+        self.progress = Some(Box::new(callback));
+        self
+    }
+}
+
+/// Parse `git fetch --porcelain` output, lines are `<flag> <old> <new> <local-ref>`
+fn parse_updates(text: &str) -> Result<Vec<RefUpdate>, GitError> {
+    let mut result = vec![];
+    for line in text.lines().filter(|l| !l.is_empty()) {
+        let parse_error = || GitError::Parse(format!("Unexpected git-fetch(1) line `{}`", line));
+        let mut chars = line.chars();
+        let flag = chars.next().ok_or_else(parse_error)?.try_into()?;
+        let mut fields = chars
+            .as_str()
+            .strip_prefix(' ')
+            .ok_or_else(parse_error)?
+            .splitn(3, ' ');
+        match (fields.next(), fields.next(), fields.next()) {
+            (Some(old), Some(new), Some(name)) => result.push(RefUpdate {
+                flag,
+                old: old.to_owned(),
+                new: new.to_owned(),
+                name: name.to_owned(),
+            }),
+            _ => return Err(parse_error()),
+        }
+    }
+    Ok(result)
+}
+
+/// Parse the default ref-update lines on `STDERR` into the flag & the shortened local
+/// reference name, i.e. ` + 1a2b3c...4d5e6f main -> origin/main  (forced update)`
+fn parse_summary(text: &str) -> Vec<(UpdateFlag, &str)> {
+    text.split(['\r', '\n'])
+        .filter_map(|line| {
+            let mut chars = line.strip_prefix(' ')?.chars();
+            let flag = UpdateFlag::try_from(chars.next()?).ok()?;
+            let (_, to) = chars.as_str().strip_prefix(' ')?.split_once(" -> ")?;
+            Some((flag, to.split_whitespace().next()?))
+        })
+        .collect()
+}
+
+/// Object ids of all references by full name
+fn ref_ids(mut cmd: GitCommand) -> Result<HashMap<String, String>, GitError> {
+    let out = cmd
+        .args(["for-each-ref", "--format=%(objectname) %(refname)"])
+        .read()?;
+    Ok(out
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .map(|(id, name)| (name.to_owned(), id.to_owned()))
+        .collect())
+}
+
+fn add_options(cmd: &mut GitCommand, remote: &str, options: &FetchOptions<'_>) {
+    if options.progress.is_some() {
+        cmd.arg("--progress");
+    }
+    if options.prune {
+        cmd.arg("--prune");
+    }
+    match options.tags {
+        Some(true) => {
+            cmd.arg("--tags");
+        }
+        Some(false) => {
+            cmd.arg("--no-tags");
+        }
+        None => {}
+    }
+    if let Some(depth) = options.depth {
+        cmd.arg(format!("--depth={}", depth));
+    }
+    if let Some(depth) = options.deepen {
+        cmd.arg(format!("--deepen={}", depth));
+    }
+    if options.unshallow {
+        cmd.arg("--unshallow");
+    }
+    if let Some(filter) = &options.filter {
+        cmd.arg(format!("--filter={}", filter));
+    }
+    cmd.arg("--end-of-options")
+        .arg(remote)
+        .args(&options.refspecs);
+}
+
+/// Fetch without `--porcelain` for git older than 2.41. The full names & ids are looked up
+/// in the references before & after fetching.
+fn fetch_summary(
+    cmd: GitCommand,
+    remote: &str,
+    mut options: FetchOptions<'_>,
+) -> Result<Vec<RefUpdate>, GitError> {
+    let before = ref_ids(cmd.clone())?;
+    let mut fetch = cmd.clone();
+    fetch.arg("fetch");
+    add_options(&mut fetch, remote, &options);
+    let out =
+        fetch.output_with_progress(&mut |line| Progress::notify(&mut options.progress, line))?;
+    let after = ref_ids(cmd)?;
+
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    let updates: Vec<RefUpdate> = parse_summary(&stderr)
+        .into_iter()
+        .map(|(flag, short)| {
+            let candidates: Vec<String> = if short.starts_with("refs/") {
+                vec![short.to_owned()]
+            } else {
+                ["refs/heads/", "refs/remotes/", "refs/tags/"]
+                    .iter()
+                    .map(|prefix| format!("{}{}", prefix, short))
+                    .collect()
+            };
+            // Prefer a changed reference, the shortened name may be ambiguous
+            let name = candidates
+                .iter()
+                .find(|name| before.get(*name) != after.get(*name))
+                .or_else(|| {
+                    candidates
+                        .iter()
+                        .find(|name| before.contains_key(*name) || after.contains_key(*name))
+                })
+                .unwrap_or(&candidates[0])
+                .clone();
+            let old = before.get(&name).cloned();
+            let new = after
+                .get(&name)
+                .cloned()
+                .filter(|_| flag != UpdateFlag::Rejected);
+            let zeros = "0".repeat(old.as_ref().or(new.as_ref()).map_or(40, String::len));
+            RefUpdate {
+                flag,
+                old: old.unwrap_or_else(|| zeros.clone()),
+                new: new.unwrap_or(zeros),
+                name,
+            }
+        })
+        .collect();
+    if out.status.success() || updates.iter().any(|u| u.flag == UpdateFlag::Rejected) {
+        Ok(updates)
+    } else {
+        Err(GitError::from_output(&fetch, &out))
+    }
+}
+
+/// Fetch from `remote`, see [`crate::GenericRepository::fetch`]
+pub(crate) fn fetch(
+    cmd: GitCommand,
+    remote: &str,
+    mut options: FetchOptions<'_>,
+) -> Result<Vec<RefUpdate>, GitError> {
+    let mut fetch = cmd.clone();
+    fetch.args(["fetch", "--porcelain"]);
+    add_options(&mut fetch, remote, &options);
+    let out =
+        fetch.output_with_progress(&mut |line| Progress::notify(&mut options.progress, line))?;
+    // git older than 2.41 fails parsing the options, before fetching anything
+    if out.status.code() == Some(129)
+        && String::from_utf8_lossy(&out.stderr).contains("unknown option `porcelain'")
+    {
+        return fetch_summary(cmd, remote, options);
+    }
+    let updates = parse_updates(&String::from_utf8(out.stdout.clone())?)?;
+    if out.status.success() || updates.iter().any(|u| u.flag == UpdateFlag::Rejected) {
+        Ok(updates)
+    } else {
+        Err(GitError::from_output(&fetch, &out))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        setup_test_author, BareRepository, FetchOptions, GenericRepository, Invocation, Progress,
+        ReplayRunner, Repository, UpdateFlag,
+    };
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    #[test]
+    fn fetch() {
+        let stdout = "* 0000000000000000000000000000000000000000 1111111111111111111111111111111111111111 refs/remotes/origin/main\n\
+                      - 2222222222222222222222222222222222222222 0000000000000000000000000000000000000000 refs/remotes/origin/old\n";
+        let stderr =
+            "Receiving objects:  50% (1/2)\rReceiving objects: 100% (2/2), done.\nFrom ../origin\n";
+        let invocation = Invocation::new(
+            [
+                "fetch",
+                "--porcelain",
+                "--progress",
+                "--prune",
+                "--no-tags",
+                "--depth=1",
+                "--filter=blob:none",
+                "--end-of-options",
+                "origin",
+                "+refs/heads/*:refs/remotes/origin/*",
+            ],
+            stdout,
+        );
+        let runner = Arc::new(ReplayRunner::new([Invocation {
+            stderr: stderr.as_bytes().to_vec(),
+            ..invocation
+        }]));
+        let tmp_dir = TempDir::new().unwrap();
+        let repo = Repository::create(tmp_dir.path())
+            .unwrap()
+            .with_runner(runner.clone());
+
+        let mut updates: Vec<Progress> = vec![];
+        let options = FetchOptions::default()
+            .refspec("+refs/heads/*:refs/remotes/origin/*")
+            .prune(true)
+            .tags(false)
+            .depth(1)
+            .filter("blob:none")
+            .progress(|p| updates.push(p.clone()));
+        let result = repo.fetch("origin", options).unwrap();
+        assert_eq!(runner.remaining(), 0);
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].flag, UpdateFlag::New);
+        assert_eq!(result[0].name, "refs/remotes/origin/main");
+        assert_eq!(result[1].flag, UpdateFlag::Pruned);
+        assert_eq!(updates.len(), 2);
+        assert!(updates[1].done);
+    }
+
+    #[test]
+    fn old_git() {
+        let stderr = "From ../origin\n \
+                      * [new branch]      main       -> origin/main\n \
+                      + 3333333...4444444 force      -> origin/force  (forced update)\n \
+                      - [deleted]         (none)     -> origin/old\n \
+                      ! [rejected]        v1         -> v1  (would clobber existing tag)\n";
+        let before = "2222222222222222222222222222222222222222 refs/remotes/origin/old\n\
+                      3333333333333333333333333333333333333333 refs/remotes/origin/force\n\
+                      5555555555555555555555555555555555555555 refs/tags/v1\n";
+        let after = "1111111111111111111111111111111111111111 refs/remotes/origin/main\n\
+                     4444444444444444444444444444444444444444 refs/remotes/origin/force\n\
+                     5555555555555555555555555555555555555555 refs/tags/v1\n";
+        let refs = ["for-each-ref", "--format=%(objectname) %(refname)"];
+        let runner = Arc::new(ReplayRunner::new([
+            Invocation::new(["fetch", "--porcelain", "--end-of-options", "origin"], "")
+                .failing(129, "error: unknown option `porcelain'\nusage: git fetch\n"),
+            Invocation::new(refs, before),
+            Invocation::new(["fetch", "--end-of-options", "origin"], "").failing(1, stderr),
+            Invocation::new(refs, after),
+        ]));
+        let tmp_dir = TempDir::new().unwrap();
+        let repo = Repository::create(tmp_dir.path())
+            .unwrap()
+            .with_runner(runner.clone());
+        let result = repo.fetch("origin", FetchOptions::default()).unwrap();
+        assert_eq!(runner.remaining(), 0);
+        assert_eq!(result.len(), 4);
+        assert_eq!(result[0].flag, UpdateFlag::New);
+        assert_eq!(result[0].name, "refs/remotes/origin/main");
+        assert_eq!(result[0].old, "0".repeat(40));
+        assert_eq!(result[0].new, "1".repeat(40));
+        assert_eq!(result[1].flag, UpdateFlag::Forced);
+        assert_eq!(result[1].old, "3".repeat(40));
+        assert_eq!(result[1].new, "4".repeat(40));
+        assert_eq!(result[2].flag, UpdateFlag::Pruned);
+        assert_eq!(result[2].name, "refs/remotes/origin/old");
+        assert_eq!(result[2].new, "0".repeat(40));
+        assert_eq!(result[3].flag, UpdateFlag::Rejected);
+        assert_eq!(result[3].name, "refs/tags/v1");
+        assert_eq!(result[3].new, "0".repeat(40));
+    }
+
+    #[test]
+    fn local_remote() {
+        setup_test_author();
+        let tmp_dir = TempDir::new().unwrap();
+        let origin_dir = tmp_dir.path().join("origin.git");
+        let work_dir = tmp_dir.path().join("work");
+        std::fs::create_dir_all(&origin_dir).unwrap();
+        std::fs::create_dir_all(&work_dir).unwrap();
+        BareRepository::create(&origin_dir).expect("Created bare repository");
+        let repo = Repository::create(&work_dir).expect("Created repository");
+        repo.git()
+            .args(["remote", "add", "origin"])
+            .arg(&origin_dir)
+            .run()
+            .unwrap();
+        repo.commit_extended("First", true, true).unwrap();
+        // Push by path, so the remote-tracking branches are not updated
+        repo.git()
+            .args(["push", "-q"])
+            .arg(&origin_dir)
+            .args(["HEAD:refs/heads/main", "HEAD:refs/heads/old"])
+            .run()
+            .unwrap();
+        let refspec = "+refs/heads/*:refs/remotes/origin/*";
+
+        let result = repo
+            .fetch("origin", FetchOptions::default().refspec(refspec))
+            .unwrap();
+        assert_eq!(result.len(), 2);
+        assert!(result.iter().all(|u| u.flag == UpdateFlag::New));
+        assert_eq!(result[0].new, repo.head().unwrap());
+
+        repo.git()
+            .args(["push", "-q"])
+            .arg(&origin_dir)
+            .arg(":refs/heads/old")
+            .run()
+            .unwrap();
+        let result = repo
+            .fetch(
+                "origin",
+                FetchOptions::default().refspec(refspec).prune(true),
+            )
+            .unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].flag, UpdateFlag::Pruned);
+        assert_eq!(result[0].name, "refs/remotes/origin/old");
+    }
+
+    #[test]
+    fn parse() {
+        let updates = super::parse_updates(
+            "  1111111111111111111111111111111111111111 2222222222222222222222222222222222222222 refs/remotes/origin/main\n",
+        )
+        .unwrap();
+        assert_eq!(updates[0].flag, UpdateFlag::FastForward);
+        assert_eq!(updates[0].old, "1111111111111111111111111111111111111111");
+        assert!(super::parse_updates("? a b c\n").is_err());
+    }
+}
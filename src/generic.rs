@@ -0,0 +1,545 @@
+use crate::{
+    BranchError, Config, ConfigLocation, ConfigReadError, ConfigWriter, Description, FetchOptions,
+    GitCommand, GitError, InvalidCommitishError, Log, LogOptions, LsRemoteError, LsRemoteOptions,
+    ObjectReader, PushOptions, PushedRef, RefOptions, RefSearchError, RefTransaction, RefUpdate,
+    Reference, Remote, RemoteError, RemoteRef, Tag, TagError,
+};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Operations shared by [`crate::Repository`] & [`crate::BareRepository`].
+///
+/// Everything which does not need a work tree lives here, so code can be written generic over
+/// bare and non-bare repositories.
+pub trait GenericRepository {
+    /// Returns a prepared [`GitCommand`]
+    ///
+    /// Implementations should set `LC_ALL=C`, errors are detected by matching git messages.
+    #[must_use]
+    fn git(&self) -> GitCommand;
+
+    /// Return path to `GIT_DIR`
+    #[must_use]
+    fn git_dir(&self) -> &Path;
+
+    /// Return config value for specified key
+    ///
+    /// # Errors
+    ///
+    /// When given invalid key or an invalid config file is read.
+    #[inline]
+    fn config(&self, key: &str) -> Result<String, ConfigReadError> {
+        self.configuration()
+            .get(key)?
+            .ok_or_else(|| ConfigReadError::InvalidSectionOrKey(key.to_owned()))
+    }
+
+    /// Return a typed view of the repository configuration, see [`Config`]
+    #[must_use]
+    #[inline]
+    fn configuration(&self) -> Config {
+        Config::new(self.git())
+    }
+
+    /// Return a writer for the configuration file at `location`, see [`ConfigWriter`]
+    #[must_use]
+    #[inline]
+    fn config_writer(&self, location: &ConfigLocation) -> ConfigWriter {
+        ConfigWriter::new(self.git(), location)
+    }
+
+    /// Returns the HEAD commit id
+    ///
+    /// # Errors
+    ///
+    /// See [`GitError`]
+    #[inline]
+    fn head(&self) -> Result<String, GitError> {
+        let out = self.git().args(["rev-parse", "HEAD"]).read()?;
+        Ok(out.trim().to_owned())
+    }
+
+    /// Returns `true` if the repository is a shallow clone
+    ///
+    /// # Errors
+    ///
+    /// See [`GitError`]
+    #[inline]
+    fn is_shallow(&self) -> Result<bool, GitError> {
+        let out = self
+            .git()
+            .args(["rev-parse", "--is-shallow-repository"])
+            .read()?;
+        Ok(out.trim() != "false")
+    }
+
+    /// Returns the abbreviated commit id for the given reference
+    ///
+    /// # Errors
+    ///
+    /// Will return [`GitError::Invalid`] if invalid reference provided
+    #[inline]
+    fn short_ref(&self, long_ref: &str) -> Result<String, GitError> {
+        let mut cmd = self.git();
+        cmd.args([
+            "rev-parse",
+            "--verify",
+            "--short",
+            "--end-of-options",
+            long_ref,
+        ]);
+        let out = cmd.output()?;
+        if !out.status.success() {
+            return Err(GitError::Invalid(format!(
+                "Invalid git reference {}",
+                long_ref
+            )));
+        }
+
+        Ok(String::from_utf8(out.stdout)?.trim().to_owned())
+    }
+
+    /// Read file from `HEAD` using `git-show(1)`, [`crate::Repository`] reads it from the
+    /// work tree
+    ///
+    /// # Errors
+    ///
+    /// When fails throws [`std::io::Error`]
+    #[inline]
+    fn hack_read_file(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        let mut cmd = self.git();
+        cmd.arg("show")
+            .arg(format!("HEAD:{}", path.to_string_lossy()));
+        let out = cmd.output().map_err(std::io::Error::other)?;
+        if out.status.success() {
+            Ok(out.stdout)
+        } else {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                GitError::from_output(&cmd, &out),
+            ))
+        }
+    }
+
+    /// Returns true if the `first` commit is an ancestor of the `second` commit.
+    ///
+    /// # Errors
+    ///
+    /// See [`GitError`]
+    #[inline]
+    fn is_ancestor(&self, first: &str, second: &str) -> Result<bool, GitError> {
+        let mut cmd = self.git();
+        cmd.args(["merge-base", "--is-ancestor", first, second]);
+        let proc = cmd.output()?;
+        match proc.status.code() {
+            Some(0) => Ok(true),
+            Some(1) => Ok(false),
+            _ => Err(GitError::from_output(&cmd, &proc)),
+        }
+    }
+
+    ///  Find best common ancestor between to commits.
+    ///
+    /// # Errors
+    ///
+    /// Will return `InvalidCommitishError::Multiple` when one or multiple provided ids do not
+    /// exist
+    #[inline]
+    fn merge_base(&self, ids: &[&str]) -> Result<Option<String>, InvalidCommitishError> {
+        let mut cmd = self.git();
+        cmd.arg("merge-base").args(ids);
+        let output = cmd.output()?;
+        if output.status.success() {
+            let tmp = String::from_utf8(output.stdout).map_err(GitError::from)?;
+            if tmp.is_empty() {
+                return Ok(None);
+            }
+            let result = tmp.trim_end();
+            return Ok(Some(result.to_owned()));
+        }
+        match output.status.code() {
+            Some(128) => {
+                let tmp = ids.to_vec();
+                let e_ids = tmp.iter().map(ToString::to_string).collect();
+                Err(InvalidCommitishError::Multiple(e_ids))
+            }
+            Some(1) => Ok(None),
+            _ => Err(GitError::from_output(&cmd, &output).into()),
+        }
+    }
+
+    /// # Errors
+    ///
+    /// See [`RefSearchError`]
+    #[inline]
+    fn remote_ref_to_id(&self, remote: &str, git_ref: &str) -> Result<String, RefSearchError> {
+        let stdout = self.git().args(["ls-remote", remote, git_ref]).read()?;
+        if let Some(first_line) = stdout.lines().next() {
+            if let Some(id) = first_line.split('\t').next() {
+                return Ok(id.to_owned());
+            }
+            return Err(RefSearchError::ParsingFailure(first_line.to_owned()));
+        }
+
+        Err(RefSearchError::NotFound(git_ref.to_owned()))
+    }
+
+    /// List references matching `patterns` using
+    /// [git-for-each-ref(1)](https://git-scm.com/docs/git-for-each-ref)
+    ///
+    /// A pattern like `refs/heads` matches all references below it, an empty `patterns` list
+    /// matches all references.
+    ///
+    /// # Errors
+    ///
+    /// See [`GitError`]
+    #[inline]
+    fn refs(&self, patterns: &[&str], options: &RefOptions) -> Result<Vec<Reference>, GitError> {
+        crate::refs::list_refs(self.git(), patterns, options)
+    }
+
+    /// Start an atomic reference transaction, see [`RefTransaction`]
+    #[inline]
+    fn ref_transaction(&self) -> RefTransaction {
+        RefTransaction::new(self.git())
+    }
+
+    /// Return a reader for objects in this repository, see [`ObjectReader`]
+    #[must_use]
+    #[inline]
+    fn object_reader(&self) -> ObjectReader {
+        ObjectReader::new(self.git())
+    }
+
+    /// Read the commit history using [git-log(1)](https://git-scm.com/docs/git-log)
+    ///
+    /// `range` is anything git-log(1) accepts as revision range, i.e. `HEAD` or
+    /// `main..feature`.
+    ///
+    /// # Errors
+    ///
+    /// See [`GitError`]
+    #[inline]
+    fn log(&self, range: &str, options: &LogOptions) -> Result<Log, GitError> {
+        crate::log::log(self.git(), range, options)
+    }
+
+    /// List local branches, see [`GenericRepository::refs`]
+    ///
+    /// # Errors
+    ///
+    /// See [`GitError`]
+    #[inline]
+    fn branches(&self) -> Result<Vec<Reference>, GitError> {
+        crate::refs::list_refs(self.git(), &["refs/heads"], &RefOptions::default())
+    }
+
+    /// Returns the name of the branch `HEAD` points to or `None` if `HEAD` is detached.
+    ///
+    /// An unborn branch is returned as well.
+    ///
+    /// # Errors
+    ///
+    /// See [`GitError`]
+    #[inline]
+    fn current_branch(&self) -> Result<Option<String>, GitError> {
+        crate::branch::current_branch(self.git())
+    }
+
+    /// Create branch `name` at `start` (default `HEAD`). If `track` is set, the upstream is set
+    /// to `start`, which should be a remote-tracking branch.
+    ///
+    /// # Errors
+    ///
+    /// See [`BranchError`]
+    #[inline]
+    fn create_branch(
+        &self,
+        name: &str,
+        start: Option<&str>,
+        track: bool,
+    ) -> Result<(), BranchError> {
+        crate::branch::create_branch(self.git(), name, start, track)
+    }
+
+    /// Delete branch `name`. Unless `force` is set, the branch must be merged.
+    ///
+    /// # Errors
+    ///
+    /// See [`BranchError`]
+    #[inline]
+    fn delete_branch(&self, name: &str, force: bool) -> Result<(), BranchError> {
+        crate::branch::delete_branch(self.git(), name, force)
+    }
+
+    /// Rename branch `old` to `new` including its reflog & configuration
+    ///
+    /// # Errors
+    ///
+    /// See [`BranchError`]
+    #[inline]
+    fn rename_branch(&self, old: &str, new: &str) -> Result<(), BranchError> {
+        crate::branch::rename_branch(self.git(), old, new)
+    }
+
+    /// Set the upstream of `branch` to `upstream`, i.e. `origin/main`
+    ///
+    /// # Errors
+    ///
+    /// See [`BranchError`]
+    #[inline]
+    fn set_upstream(&self, branch: &str, upstream: &str) -> Result<(), BranchError> {
+        crate::branch::set_upstream(self.git(), branch, upstream)
+    }
+
+    /// Remove the upstream configuration of `branch`
+    ///
+    /// # Errors
+    ///
+    /// See [`BranchError`]
+    #[inline]
+    fn unset_upstream(&self, branch: &str) -> Result<(), BranchError> {
+        crate::branch::unset_upstream(self.git(), branch)
+    }
+
+    /// Returns the number of commits only in `branch` and only in `upstream`
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BranchError::NotFound`] if one of the revisions does not exist
+    #[inline]
+    fn ahead_behind(&self, branch: &str, upstream: &str) -> Result<(u32, u32), BranchError> {
+        crate::branch::ahead_behind(self.git(), branch, upstream)
+    }
+
+    /// List local tags
+    ///
+    /// # Errors
+    ///
+    /// See [`GitError`]
+    #[inline]
+    fn tags(&self) -> Result<Vec<Tag>, GitError> {
+        crate::tags::list_tags(self.git())
+    }
+
+    /// Create tag `name` pointing to `target` (default `HEAD`). If a `message` is given an
+    /// annotated tag is created, otherwise a lightweight one.
+    ///
+    /// # Errors
+    ///
+    /// See [`TagError`]
+    #[inline]
+    fn create_tag(
+        &self,
+        name: &str,
+        target: Option<&str>,
+        message: Option<&str>,
+    ) -> Result<(), TagError> {
+        crate::tags::create_tag(self.git(), name, target, message)
+    }
+
+    /// Delete tag `name`
+    ///
+    /// # Errors
+    ///
+    /// See [`TagError`]
+    #[inline]
+    fn delete_tag(&self, name: &str) -> Result<(), TagError> {
+        crate::tags::delete_tag(self.git(), name)
+    }
+
+    /// Find the nearest annotated tag reachable from `rev` using
+    /// [git-describe(1)](https://git-scm.com/docs/git-describe). Returns `None` if there is no
+    /// such tag.
+    ///
+    /// # Errors
+    ///
+    /// See [`TagError`]
+    #[inline]
+    fn describe(&self, rev: &str) -> Result<Option<Description>, TagError> {
+        crate::tags::describe(self.git(), rev)
+    }
+
+    /// Returns all configured remotes by name
+    ///
+    /// # Errors
+    ///
+    /// See [`GitError`]
+    #[inline]
+    fn remotes(&self) -> Result<HashMap<String, Remote>, GitError> {
+        crate::remote::remotes(self.git())
+    }
+
+    /// Add remote `name` fetching from `url` with the default refspec
+    ///
+    /// # Errors
+    ///
+    /// See [`RemoteError`]
+    #[inline]
+    fn add_remote(&self, name: &str, url: &str) -> Result<(), RemoteError> {
+        crate::remote::add_remote(self.git(), name, url)
+    }
+
+    /// Remove remote `name` including its remote-tracking branches & configuration
+    ///
+    /// # Errors
+    ///
+    /// See [`RemoteError`]
+    #[inline]
+    fn remove_remote(&self, name: &str) -> Result<(), RemoteError> {
+        crate::remote::remove_remote(self.git(), name)
+    }
+
+    /// Rename remote `old` to `new` including its remote-tracking branches & refspecs
+    ///
+    /// # Errors
+    ///
+    /// See [`RemoteError`]
+    #[inline]
+    fn rename_remote(&self, old: &str, new: &str) -> Result<(), RemoteError> {
+        crate::remote::rename_remote(self.git(), old, new)
+    }
+
+    /// Set the URL used for fetching from `name`
+    ///
+    /// # Errors
+    ///
+    /// See [`RemoteError`]
+    #[inline]
+    fn set_url(&self, name: &str, url: &str) -> Result<(), RemoteError> {
+        crate::remote::set_url(self.git(), name, url)
+    }
+
+    /// Replace the URLs used for pushing to `name`. An empty list pushes to the fetch URL.
+    ///
+    /// # Errors
+    ///
+    /// See [`RemoteError`]
+    #[inline]
+    fn set_push_urls(&self, name: &str, urls: &[&str]) -> Result<(), RemoteError> {
+        crate::remote::set_push_urls(self.git(), name, urls)
+    }
+
+    /// Returns the configured fetch refspecs of `name`
+    ///
+    /// # Errors
+    ///
+    /// See [`RemoteError`]
+    #[inline]
+    fn remote_refspecs(&self, name: &str) -> Result<Vec<String>, RemoteError> {
+        crate::remote::remote_refspecs(self.git(), name)
+    }
+
+    /// Set the default branch of remote `name`. If `branch` is `None` it is queried from the
+    /// remote.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RemoteError::NotFound`] if the remote or the remote-tracking branch does not
+    /// exist.
+    #[inline]
+    fn set_remote_head(&self, name: &str, branch: Option<&str>) -> Result<(), RemoteError> {
+        crate::remote::set_remote_head(self.git(), name, branch)
+    }
+
+    /// Delete remote-tracking branches of `name` which no longer exist on the remote and
+    /// return their names, i.e. `origin/feature`
+    ///
+    /// # Errors
+    ///
+    /// See [`RemoteError`]
+    #[inline]
+    fn prune_remote(&self, name: &str) -> Result<Vec<String>, RemoteError> {
+        crate::remote::prune_remote(self.git(), name)
+    }
+
+    /// List references of `remote`, which may be a configured remote name, see
+    /// [`crate::ls_remote`]
+    ///
+    /// # Errors
+    ///
+    /// See [`LsRemoteError`]
+    #[inline]
+    fn ls_remote(
+        &self,
+        remote: &str,
+        options: &LsRemoteOptions,
+    ) -> Result<Vec<RemoteRef>, LsRemoteError> {
+        crate::ls_remote::list_remote_refs(self.git(), remote, options)
+    }
+
+    /// Download objects & references from `remote` using
+    /// [git-fetch(1)](https://git-scm.com/docs/git-fetch) and return the updated local
+    /// references.
+    ///
+    /// Uses `--porcelain` with git 2.41 or newer and parses the human readable output
+    /// otherwise. If some updates are rejected, the list including the
+    /// [`crate::UpdateFlag::Rejected`] entries is returned instead of an error.
+    ///
+    /// # Errors
+    ///
+    /// See [`GitError`]
+    #[inline]
+    fn fetch(&self, remote: &str, options: FetchOptions<'_>) -> Result<Vec<RefUpdate>, GitError> {
+        crate::fetch::fetch(self.git(), remote, options)
+    }
+
+    /// Update references on `remote` using [git-push(1)](https://git-scm.com/docs/git-push)
+    /// and return the result for every reference.
+    ///
+    /// If some references are rejected, the list including the rejected entries is returned
+    /// instead of an error, see [`PushedRef::is_rejected`].
+    ///
+    /// # Errors
+    ///
+    /// See [`GitError`]
+    #[inline]
+    fn push(
+        &self,
+        remote: &str,
+        refspecs: &[&str],
+        options: &PushOptions,
+    ) -> Result<Vec<PushedRef>, GitError> {
+        crate::push::push(self.git(), remote, refspecs, options)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        setup_test_author, BareRepository, CloneOptions, GenericRepository, RefOptions, Repository,
+    };
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    fn summary(repo: &impl GenericRepository) -> (String, usize, Vec<u8>) {
+        let head = repo.head().unwrap();
+        let refs = repo.refs(&[], &RefOptions::default()).unwrap();
+        let readme = repo.hack_read_file(Path::new("README.md")).unwrap();
+        (head, refs.len(), readme)
+    }
+
+    #[test]
+    fn generic() {
+        setup_test_author();
+        let tmp_dir = TempDir::new().unwrap();
+        let work_dir = tmp_dir.path().join("work");
+        let repo = Repository::create(&work_dir).expect("Created repository");
+        std::fs::write(work_dir.join("README.md"), "# README\n").unwrap();
+        repo.stage(Path::new("README.md")).unwrap();
+        repo.commit("First").unwrap();
+        assert!(repo.git_dir().ends_with(".git"));
+        let short = repo.short_ref("HEAD").unwrap();
+        assert!(repo.head().unwrap().starts_with(&short));
+        assert!(repo.short_ref("--all").is_err());
+        assert!(repo.short_ref("HEAD..HEAD").is_err());
+
+        let url = format!("file://{}", work_dir.display());
+        let bare_dir = tmp_dir.path().join("bare.git");
+        let bare = BareRepository::clone(&url, &bare_dir, CloneOptions::default())
+            .expect("Cloned bare repository");
+        assert_eq!(bare.git_dir(), bare_dir);
+        assert_eq!(summary(&repo), summary(&bare));
+        assert!(bare.hack_read_file(Path::new("missing")).is_err());
+    }
+}
@@ -0,0 +1,334 @@
+use crate::{GitCommand, GitError, GitRunner};
+use posix_errors::{PosixError, EINVAL, ENOENT, ENOTSUP};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Failed to initialize a repository
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum InitError {
+    /// Initial branch name is not a valid branch name
+    #[error("`{0}` is not a valid branch name")]
+    InvalidBranchName(String),
+    /// Value for `--shared` is neither a known keyword nor an octal mode
+    #[error("Invalid shared permissions `{0}`")]
+    InvalidShared(String),
+    /// Template directory does not exist
+    #[error("Template directory `{0}` not found")]
+    TemplateNotFound(PathBuf),
+    /// The installed git does not support the option
+    #[error("Option `{0}` is not supported by the installed git")]
+    Unsupported(String),
+    #[allow(missing_docs)]
+    #[error(transparent)]
+    Failure(#[from] GitError),
+}
+
+impl From<InitError> for GitError {
+    #[inline]
+    fn from(err: InitError) -> Self {
+        match err {
+            InitError::Failure(e) => e,
+            InitError::InvalidBranchName(_)
+            | InitError::InvalidShared(_)
+            | InitError::TemplateNotFound(_)
+            | InitError::Unsupported(_) => Self::Invalid(format!("{}", err)),
+        }
+    }
+}
+
+impl From<InitError> for PosixError {
+    #[inline]
+    fn from(err: InitError) -> Self {
+        match err {
+            InitError::Failure(e) => e.into(),
+            InitError::TemplateNotFound(_) => Self::new(ENOENT, format!("{}", err)),
+            InitError::Unsupported(_) => Self::new(ENOTSUP, format!("{}", err)),
+            InitError::InvalidBranchName(_) | InitError::InvalidShared(_) => {
+                Self::new(EINVAL, format!("{}", err))
+            }
+        }
+    }
+}
+
+/// Hash algorithm used for object ids
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ObjectFormat {
+    #[allow(missing_docs)]
+    Sha1,
+    #[allow(missing_docs)]
+    Sha256,
+}
+
+/// Storage format of references
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RefFormat {
+    /// Loose files & `packed-refs`
+    Files,
+    #[allow(missing_docs)]
+    Reftable,
+}
+
+/// Options for [`crate::Repository::init`] & [`crate::BareRepository::init`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InitOptions {
+    initial_branch: Option<String>,
+    object_format: Option<ObjectFormat>,
+    template: Option<PathBuf>,
+    shared: Option<String>,
+    separate_git_dir: Option<PathBuf>,
+    ref_format: Option<RefFormat>,
+}
+
+impl InitOptions {
+    /// Name of the unborn branch `HEAD` points to
+    #[must_use]
+    #[inline]
+    pub fn initial_branch(mut self, branch: &str) -> Self {
+        self.initial_branch = Some(branch.to_owned());
+        self
+    }
+
+    #[allow(missing_docs)]
+    #[must_use]
+    #[inline]
+    pub const fn object_format(mut self, format: ObjectFormat) -> Self {
+        self.object_format = Some(format);
+        self
+    }
+
+    /// Copy hooks & other files from the given template directory
+    #[must_use]
+    #[inline]
+    pub fn template(mut self, dir: &Path) -> Self {
+        self.template = Some(dir.to_path_buf());
+        self
+    }
+
+    /// Share the repository with other users: `false`, `true`, `umask`, `group`, `all`,
+    /// `world`, `everybody` or an octal mode like `0640`
+    #[must_use]
+    #[inline]
+    pub fn shared(mut self, permissions: &str) -> Self {
+        self.shared = Some(permissions.to_owned());
+        self
+    }
+
+    /// Create the git directory at `dir` and link it from the work tree, only supported by
+    /// [`crate::Repository::init`]
+    #[must_use]
+    #[inline]
+    pub fn separate_git_dir(mut self, dir: &Path) -> Self {
+        self.separate_git_dir = Some(dir.to_path_buf());
+        self
+    }
+
+    /// Requires git 2.45 or newer
+    #[must_use]
+    #[inline]
+    pub const fn ref_format(mut self, format: RefFormat) -> Self {
+        self.ref_format = Some(format);
+        self
+    }
+
+    /// Directory the git directory is created in, if it is not inside the work tree
+    pub(crate) fn get_separate_git_dir(&self) -> Option<&Path> {
+        self.separate_git_dir.as_deref()
+    }
+}
+
+/// Returns `true` if `value` is accepted by `git init --shared`
+fn is_valid_shared(value: &str) -> bool {
+    const KEYWORDS: [&str; 7] = [
+        "false",
+        "true",
+        "umask",
+        "group",
+        "all",
+        "world",
+        "everybody",
+    ];
+    KEYWORDS.contains(&value)
+        || (!value.is_empty() && value.bytes().all(|b| (b'0'..=b'7').contains(&b)))
+}
+
+/// Run [git-init(1)](https://git-scm.com/docs/git-init) with the given options
+pub(crate) fn run_init(
+    runner: Arc<dyn GitRunner>,
+    path: &Path,
+    options: &InitOptions,
+    bare: bool,
+) -> Result<(), InitError> {
+    if bare && options.separate_git_dir.is_some() {
+        return Err(GitError::Invalid(
+            "A bare repository can not have a separate git dir".to_owned(),
+        )
+        .into());
+    }
+    if let Some(shared) = options.shared.as_ref().filter(|s| !is_valid_shared(s)) {
+        return Err(InitError::InvalidShared(shared.clone()));
+    }
+    let mut cmd = GitCommand::new(runner);
+    cmd.env("LC_ALL", "C");
+    cmd.args(["init", "--quiet"]);
+    if bare {
+        cmd.arg("--bare");
+    }
+    if let Some(branch) = &options.initial_branch {
+        cmd.arg(format!("--initial-branch={}", branch));
+    }
+    match options.object_format {
+        Some(ObjectFormat::Sha1) => {
+            cmd.arg("--object-format=sha1");
+        }
+        Some(ObjectFormat::Sha256) => {
+            cmd.arg("--object-format=sha256");
+        }
+        None => {}
+    }
+    if let Some(template) = &options.template {
+        if !template.is_dir() {
+            return Err(InitError::TemplateNotFound(template.clone()));
+        }
+        cmd.arg("--template").arg(template);
+    }
+    if let Some(shared) = &options.shared {
+        cmd.arg(format!("--shared={}", shared));
+    }
+    if let Some(dir) = &options.separate_git_dir {
+        cmd.arg("--separate-git-dir").arg(dir);
+    }
+    match options.ref_format {
+        Some(RefFormat::Files) => {
+            cmd.arg("--ref-format=files");
+        }
+        Some(RefFormat::Reftable) => {
+            cmd.arg("--ref-format=reftable");
+        }
+        None => {}
+    }
+    cmd.arg("--").arg(path);
+
+    let out = cmd.output()?;
+    if out.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    if let Some(option) = stderr
+        .split_once("unknown option `")
+        .and_then(|(_, rest)| rest.split_once('\''))
+        .map(|(option, _)| option)
+    {
+        let name = option.split_once('=').map_or(option, |(name, _)| name);
+        Err(InitError::Unsupported(format!("--{}", name)))
+    } else if stderr.contains("invalid initial branch name") {
+        Err(InitError::InvalidBranchName(
+            options.initial_branch.clone().unwrap_or_default(),
+        ))
+    } else {
+        Err(GitError::from_output(&cmd, &out).into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        BareRepository, GenericRepository, InitError, InitOptions, ObjectFormat, ProcessRunner,
+        RecordingRunner, Repository,
+    };
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    #[test]
+    fn init() {
+        let tmp_dir = TempDir::new().unwrap();
+        let options = InitOptions::default()
+            .initial_branch("trunk")
+            .object_format(ObjectFormat::Sha256)
+            .shared("group")
+            .separate_git_dir(&tmp_dir.path().join("git-dir"));
+        let repo = Repository::init(&tmp_dir.path().join("work"), &options).unwrap();
+        assert_eq!(repo.current_branch().unwrap().as_deref(), Some("trunk"));
+        let config = |key: &str| repo.git().args(["config", key]).read().unwrap();
+        assert_eq!(config("extensions.objectFormat"), "sha256\n");
+        assert_eq!(config("core.sharedRepository"), "1\n");
+        assert!(tmp_dir.path().join("git-dir").join("HEAD").exists());
+        assert!(tmp_dir.path().join("work").join(".git").is_file());
+
+        let template = tmp_dir.path().join("template");
+        std::fs::create_dir_all(&template).unwrap();
+        std::fs::write(template.join("description"), "Templated\n").unwrap();
+        let bare_dir = tmp_dir.path().join("bare.git");
+        let options = InitOptions::default().template(&template).shared("0640");
+        let bare = BareRepository::init(&bare_dir, &options).unwrap();
+        assert_eq!(bare.config("core.bare").unwrap(), "true");
+        assert_eq!(bare.config("core.sharedRepository").unwrap(), "0640");
+        assert_eq!(
+            std::fs::read_to_string(bare_dir.join("description")).unwrap(),
+            "Templated\n"
+        );
+    }
+
+    #[test]
+    fn init_with_runner() {
+        let tmp_dir = TempDir::new().unwrap();
+        let runner = Arc::new(RecordingRunner::new(Arc::new(ProcessRunner::default())));
+        let repo = Repository::init_with_runner(
+            &tmp_dir.path().join("work"),
+            &InitOptions::default(),
+            runner.clone(),
+        )
+        .unwrap();
+        let invocations = runner.invocations();
+        assert_eq!(invocations.len(), 1);
+        assert_eq!(invocations[0].args[0], "init");
+        repo.is_shallow().unwrap();
+        assert_eq!(runner.invocations().len(), 2);
+
+        let runner = Arc::new(RecordingRunner::new(Arc::new(ProcessRunner::default())));
+        let bare = BareRepository::init_with_runner(
+            &tmp_dir.path().join("bare.git"),
+            &InitOptions::default(),
+            runner.clone(),
+        )
+        .unwrap();
+        bare.is_shallow().unwrap();
+        assert_eq!(runner.invocations().len(), 2);
+    }
+
+    #[test]
+    fn errors() {
+        let tmp_dir = TempDir::new().unwrap();
+        let path = tmp_dir.path().join("repo");
+        assert_eq!(
+            Repository::init(&path, &InitOptions::default().initial_branch("a b")).unwrap_err(),
+            InitError::InvalidBranchName("a b".to_owned())
+        );
+        assert_eq!(
+            Repository::init(&path, &InitOptions::default().shared("nope")).unwrap_err(),
+            InitError::InvalidShared("nope".to_owned())
+        );
+        let runner = Arc::new(RecordingRunner::new(Arc::new(ProcessRunner::default())));
+        assert_eq!(
+            Repository::init_with_runner(
+                &path,
+                &InitOptions::default().shared("0o640"),
+                runner.clone()
+            )
+            .unwrap_err(),
+            InitError::InvalidShared("0o640".to_owned())
+        );
+        assert!(runner.invocations().is_empty());
+        let missing = tmp_dir.path().join("missing");
+        assert_eq!(
+            BareRepository::init(&path, &InitOptions::default().template(&missing)).unwrap_err(),
+            InitError::TemplateNotFound(missing)
+        );
+        assert!(matches!(
+            BareRepository::init(
+                &path,
+                &InitOptions::default().separate_git_dir(tmp_dir.path())
+            ),
+            Err(InitError::Failure(_))
+        ));
+    }
+}
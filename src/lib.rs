@@ -18,85 +18,144 @@
 //! A wrapper around [git(1)](https://git-scm.com/docs/git) inspired by
 //! [`GitPython`](https://github.com/gitpython-developers/GitPython).
 
+use crate::clone::run_clone;
+use crate::init::run_init;
 pub use posix_errors::{PosixError, EACCES, EINVAL, ENOENT};
-use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
-use std::process::Command;
-use std::process::Output;
+use std::sync::Arc;
 
 mod bare_repo;
 pub use crate::bare_repo::*;
 
-/// Experimental stuff
-pub mod x;
+mod error;
+pub use crate::error::GitError;
 
-macro_rules! cmd {
-    ($args:expr) => {
-        Command::new("git").args($args).output()
-    };
-    ($name:expr, $args:expr) => {
-        Command::new("git").arg($name).args($args).output()
-    };
-}
+mod runner;
+pub use crate::runner::*;
 
-/// Wrapper around [git-ls-remote(1)](https://git-scm.com/docs/git-ls-remote)
-///
-/// # Errors
-///
-/// Will return [`PosixError`] if command exits with an error code.
-#[inline]
-pub fn ls_remote(args: &[&str]) -> Result<Output, PosixError> {
-    let result = cmd!("ls-remote", args);
+mod log;
+pub use crate::log::*;
 
-    if let Ok(value) = result {
-        return Ok(value);
-    }
+mod status;
+pub use crate::status::*;
 
-    Err(PosixError::from(result.unwrap_err()))
-}
+mod diff;
+pub use crate::diff::*;
+
+mod refs;
+pub use crate::refs::*;
+
+mod transaction;
+pub use crate::transaction::*;
+
+mod objects;
+pub use crate::objects::*;
+
+mod branch;
+pub use crate::branch::*;
+
+mod tags;
+pub use crate::tags::*;
+
+mod progress;
+pub use crate::progress::*;
+
+mod fetch;
+pub use crate::fetch::*;
+
+mod push;
+pub use crate::push::*;
+
+mod remote;
+pub use crate::remote::*;
+
+mod ls_remote;
+pub use crate::ls_remote::*;
+
+mod clone;
+pub use crate::clone::*;
+
+mod init;
+pub use crate::init::*;
+
+mod config;
+pub use crate::config::*;
+
+mod generic;
+pub use crate::generic::*;
+
+mod discover;
+use discover::{git_dir_from_work_tree, search_git_dir, work_tree_from_git_dir};
 
-/// Returns all tags from a remote
+mod worktree;
+pub use crate::worktree::*;
+
+mod submodule;
+pub use crate::submodule::*;
+
+mod subtree_config;
+pub use crate::subtree_config::*;
+
+mod subtree_status;
+pub use crate::subtree_status::*;
+
+mod subtree_split;
+pub use crate::subtree_split::*;
+
+/// Experimental stuff
+pub mod x;
+
+/// Returns all tag names from a remote
 ///
 /// # Errors
 ///
-/// Will return [`PosixError`] if command exits with an error code.
+/// See [`LsRemoteError`]
 #[inline]
-pub fn tags_from_remote(url: &str) -> Result<Vec<String>, PosixError> {
-    let mut vec = Vec::new();
-    let output = ls_remote(&["--refs", "--tags", url])?;
-    if output.status.success() {
-        let tmp = String::from_utf8(output.stdout).expect("Expected UTF-8");
-        for s in tmp.lines() {
-            let mut split = s.splitn(3, '/');
-            split.next();
-            split.next();
-            let split_result = split.next();
-            if let Some(value) = split_result {
-                vec.push(String::from(value));
-            }
-        }
-        Ok(vec)
-    } else {
-        Err(PosixError::from(output))
-    }
+pub fn tags_from_remote(url: &str) -> Result<Vec<String>, LsRemoteError> {
+    let options = LsRemoteOptions::default().tags(true).refs_only(true);
+    Ok(ls_remote(url, &options)?
+        .into_iter()
+        .filter_map(|r| r.name.strip_prefix("refs/tags/").map(ToOwned::to_owned))
+        .collect())
 }
 
 /// Failed to read config
 #[allow(missing_docs)]
-#[derive(thiserror::Error, Debug)]
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
 pub enum ConfigReadError {
     #[error("Invalid section key in config {0}")]
     InvalidSectionOrKey(String),
     #[error("Invalid config file {0}")]
     InvalidConfigFile(String),
-    #[error("{0}")]
-    Failure(String, i32),
+    #[error("Value of {0} can not be converted to the requested type")]
+    InvalidValue(String),
+    #[error(transparent)]
+    Failure(#[from] GitError),
+}
+
+impl From<ConfigReadError> for GitError {
+    #[inline]
+    fn from(err: ConfigReadError) -> Self {
+        match err {
+            ConfigReadError::Failure(e) => e,
+            ConfigReadError::InvalidSectionOrKey(_)
+            | ConfigReadError::InvalidConfigFile(_)
+            | ConfigReadError::InvalidValue(_) => Self::Invalid(format!("{}", err)),
+        }
+    }
+}
+
+impl From<ConfigReadError> for PosixError {
+    #[inline]
+    fn from(err: ConfigReadError) -> Self {
+        GitError::from(err).into()
+    }
 }
 
 /// Failed to change configuration file
 #[allow(missing_docs)]
-#[derive(thiserror::Error, Debug)]
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
 pub enum ConfigSetError {
     #[error("{0}")]
     InvalidSectionOrKey(String),
@@ -104,91 +163,67 @@ pub enum ConfigSetError {
     InvalidConfigFile(String),
     #[error("{0}")]
     WriteFailed(String),
+    /// Key or section to change does not exist
+    #[error("`{0}` not found")]
+    NotFound(String),
+    /// More than one value matched, use a value pattern or replace all values
+    #[error("{0}")]
+    MultipleValues(String),
+    /// Value pattern is not a valid regular expression
     #[error("{0}")]
-    Failure(String, i32),
+    InvalidPattern(String),
+    #[error(transparent)]
+    Failure(#[from] GitError),
 }
 
-/// # Errors
-///
-/// Throws [`ConfigSetError`] on errors
-///
-/// # Panics
-///
-/// When git-config(1) execution fails
-#[inline]
-pub fn config_file_set(file: &Path, key: &str, value: &str) -> Result<(), ConfigSetError> {
-    let args = &["--file", file.to_str().expect("UTF-8 encoding"), key, value];
-    let mut cmd = Command::new("git");
-    cmd.arg("config").args(args);
-    let out = cmd.output().expect("Failed to execute git-config(1)");
-    if out.status.success() {
-        Ok(())
-    } else {
-        let msg = String::from_utf8(out.stdout).expect("UTF-8 encoding");
-        match out.status.code().unwrap_or(1) {
-            1 => Err(ConfigSetError::InvalidSectionOrKey(msg)),
-            3 => Err(ConfigSetError::InvalidConfigFile(msg)),
-            4 => Err(ConfigSetError::WriteFailed(msg)),
-            code => Err(ConfigSetError::Failure(msg, code)),
+impl From<ConfigSetError> for GitError {
+    #[inline]
+    fn from(err: ConfigSetError) -> Self {
+        match err {
+            ConfigSetError::Failure(e) => e,
+            ConfigSetError::NotFound(_) => Self::Invalid(format!("{}", err)),
+            ConfigSetError::InvalidSectionOrKey(msg)
+            | ConfigSetError::InvalidConfigFile(msg)
+            | ConfigSetError::WriteFailed(msg)
+            | ConfigSetError::MultipleValues(msg)
+            | ConfigSetError::InvalidPattern(msg) => Self::Invalid(msg),
         }
     }
 }
 
-/// Return all `.gitsubtrees` files in the working directory.
-///
-/// Uses [git-ls-files(1)](https://git-scm.com/docs/git-ls-files)
+impl From<ConfigSetError> for PosixError {
+    #[inline]
+    fn from(err: ConfigSetError) -> Self {
+        GitError::from(err).into()
+    }
+}
+
+/// Set `key` to `value` in the configuration `file`, see [`ConfigWriter::set`]
 ///
 /// # Errors
 ///
-/// Will return [`PosixError`] if command exits with an error code.
+/// Throws [`ConfigSetError`] on errors
+#[inline]
+pub fn config_file_set(file: &Path, key: &str, value: &str) -> Result<(), ConfigSetError> {
+    let mut cmd = GitCommand::new(Arc::new(ProcessRunner::default()));
+    cmd.env("LC_ALL", "C");
+    ConfigWriter::new(cmd, &ConfigLocation::File(file.to_path_buf())).set(key, value, None)
+}
+
 /// Figure out the default branch for given remote.
 ///
 /// # Errors
 ///
-/// Will return [`PosixError`] if command exits with an error code.
-/// TODO Return a custom error type
+/// Returns [`LsRemoteError::NoHead`] if the remote `HEAD` does not point to a branch
 #[inline]
-pub fn resolve_head(remote: &str) -> Result<String, PosixError> {
-    let proc =
-        cmd!("ls-remote", vec!["--symref", remote, "HEAD"]).expect("Failed to execute git command");
-    if proc.status.success() {
-        let stdout = String::from_utf8(proc.stdout).expect("UTF-8 encoding");
-        let mut lines = stdout.lines();
-        let first_line = lines.next().expect("Failed to parse HEAD from remote");
-        let mut split = first_line
-            .split('\t')
-            .next()
-            .expect("Failed to parse HEAD from remote")
-            .splitn(3, '/');
-        split.next();
-        split.next();
-        return Ok(split
-            .next()
-            .expect("Failed to parse default branch")
-            .to_owned());
-    }
-
-    Err(PosixError::from(proc))
-}
-
-enum RemoteDir {
-    Fetch,
-    Push,
-}
-
-struct RemoteLine {
-    name: String,
-    url: String,
-    dir: RemoteDir,
-}
-
-/// Represents a git remote
-#[allow(missing_docs)]
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct Remote {
-    pub name: String,
-    pub push: Option<String>,
-    pub fetch: Option<String>,
+pub fn resolve_head(remote: &str) -> Result<String, LsRemoteError> {
+    let options = LsRemoteOptions::default().pattern("HEAD");
+    ls_remote(remote, &options)?
+        .into_iter()
+        .find(|r| r.name == "HEAD")
+        .and_then(|r| r.symref_target)
+        .and_then(|target| target.strip_prefix("refs/heads/").map(ToOwned::to_owned))
+        .ok_or_else(|| LsRemoteError::NoHead(remote.to_owned()))
 }
 
 fn cwd() -> Result<PathBuf, RepoError> {
@@ -227,50 +262,6 @@ impl TryFrom<&Path> for AbsoluteDirPath {
     }
 }
 
-trait GenericRepository {
-    /// Return config value for specified key
-    ///
-    /// # Errors
-    ///
-    /// When given invalid key or an invalid config file is read.
-    ///
-    /// # Panics
-    ///
-    /// Will panic if git exits with an unexpected error code. Expected codes are 0, 1 & 3.
-    #[inline]
-    fn gen_config(&self, key: &str) -> Result<String, ConfigReadError> {
-        let out = self
-            .gen_git()
-            .arg("config")
-            .arg(key)
-            .output()
-            .expect("Failed to execute git-config(1)");
-        if out.status.success() {
-            Ok(String::from_utf8(out.stdout)
-                .expect("UTF-8 encoding")
-                .trim()
-                .to_owned())
-        } else {
-            match out.status.code().unwrap_or(3) {
-                1 => Err(ConfigReadError::InvalidSectionOrKey(key.to_owned())),
-                3 => {
-                    let msg = String::from_utf8_lossy(out.stderr.as_ref()).to_string();
-                    Err(ConfigReadError::InvalidConfigFile(msg))
-                }
-                code => {
-                    let msg = String::from_utf8_lossy(out.stderr.as_ref());
-                    Err(ConfigReadError::Failure(msg.to_string(), code))
-                }
-            }
-        }
-    }
-
-    /// Returns a prepared git `Command` struct
-    /// TODO move to generic repo trait
-    #[must_use]
-    fn gen_git(&self) -> Command;
-}
-
 /// The main repository object.
 ///
 /// This wrapper allows to keep track of optional *git-dir* and *work-tree* directories when
@@ -281,6 +272,8 @@ pub struct Repository {
     git_dir: AbsoluteDirPath,
     /// WORK_TREE
     work_tree: AbsoluteDirPath,
+    /// Executes all git commands
+    runner: Arc<dyn GitRunner>,
 }
 
 /// Error during repository instantiation
@@ -297,6 +290,11 @@ pub enum RepoError {
     AbsolutionError(PathBuf),
     #[error("Failed to access current working directory")]
     FailAccessCwd,
+    /// A `.git` file does not point to a valid git directory
+    #[error("Invalid gitdir file: `{0}`")]
+    InvalidGitFile(PathBuf),
+    #[error(transparent)]
+    Git(#[from] GitError),
 }
 
 impl From<RepoError> for PosixError {
@@ -305,173 +303,56 @@ impl From<RepoError> for PosixError {
         let msg = format!("{}", e);
         match e {
             RepoError::GitDirNotFound | RepoError::InvalidDirectory(_) => Self::new(ENOENT, msg),
-            RepoError::AbsolutionError(_) => Self::new(EINVAL, msg),
+            RepoError::AbsolutionError(_) | RepoError::InvalidGitFile(_) => Self::new(EINVAL, msg),
             RepoError::FailAccessCwd => Self::new(EACCES, msg),
             RepoError::BareRepo => Self::new(EINVAL, format!("{}", e)),
+            RepoError::Git(err) => err.into(),
         }
     }
 }
 
-fn search_git_dir(start: &Path) -> Result<AbsoluteDirPath, RepoError> {
-    let path;
-    if start.is_absolute() {
-        path = start.to_path_buf();
-    } else {
-        match start.canonicalize() {
-            Ok(path_buf) => path = path_buf,
-            Err(_) => return Err(RepoError::InvalidDirectory(start.to_path_buf())),
-        }
-    }
-
-    if let (Ok(head_path), Ok(objects_path)) = (
-        path.join("HEAD").canonicalize(),
-        path.join("objects").canonicalize(),
-    ) {
-        if head_path.is_file() && objects_path.is_dir() {
-            return AbsoluteDirPath::try_from(path.as_path());
-        }
-    }
-
-    for parent in path.ancestors() {
-        let candidate = parent.join(".git");
-        if candidate.is_dir() && candidate.exists() {
-            return candidate.as_path().try_into();
-        }
-    }
-    Err(RepoError::GitDirNotFound)
-}
-
-fn work_tree_from_git_dir(git_dir: &AbsoluteDirPath) -> Result<AbsoluteDirPath, RepoError> {
-    let mut cmd = Command::new("git");
-    cmd.arg("--git-dir");
-    cmd.arg(git_dir.0.as_os_str());
-    cmd.args(&["rev-parse", "--is-bare-repository"]);
-    let output = cmd.output().expect("failed to execute rev-parse");
-    if output.status.success() {
-        let tmp = String::from_utf8_lossy(&output.stdout);
-        if tmp.trim() == "true" {
-            return Err(RepoError::BareRepo);
+impl From<RepoError> for GitError {
+    #[inline]
+    fn from(err: RepoError) -> Self {
+        match err {
+            RepoError::Git(e) => e,
+            RepoError::GitDirNotFound
+            | RepoError::BareRepo
+            | RepoError::InvalidDirectory(_)
+            | RepoError::AbsolutionError(_)
+            | RepoError::InvalidGitFile(_)
+            | RepoError::FailAccessCwd => Self::Invalid(format!("{}", err)),
         }
     }
-
-    match git_dir.0.parent() {
-        Some(dir) => Ok(AbsoluteDirPath::try_from(dir)?),
-        None => Err(RepoError::BareRepo),
-    }
-}
-
-fn git_dir_from_work_tree(work_tree: &AbsoluteDirPath) -> Result<AbsoluteDirPath, RepoError> {
-    let result = work_tree.0.join(".git");
-    result.as_path().try_into()
 }
 
-/// Invalid git reference was provided
-#[allow(missing_docs)]
-#[derive(thiserror::Error, Debug, PartialEq, Eq)]
-#[error("Invalid git reference {0}")]
-pub struct InvalidRefError(String);
-
 /// Getters
 impl Repository {
-    /// # Panics
+    /// Returns `true` if there are no staged, unstaged or untracked changes
     ///
-    /// Panics of executing git-diff(1) fails
-    #[must_use]
-    #[inline]
-    pub fn is_clean(&self) -> bool {
-        let output = self
-            .git()
-            .args(&["diff", "--quiet", "HEAD"])
-            .output()
-            .expect("Failed to execute git-diff(1)");
-        output.status.success()
-    }
-
-    /// # Panics
+    /// Use [`Repository::status`] for a configurable untracked & ignored files handling.
     ///
-    /// Panics of executing git-rev-parse(1) fails
-    #[must_use]
+    /// # Errors
+    ///
+    /// See [`GitError`]
     #[inline]
-    pub fn is_shallow(&self) -> bool {
-        let out = self
-            .git()
-            .args(&["rev-parse", "--is-shallow-repository"])
-            .output()
-            .expect("Failed to execute git-rev-parse(1)");
-        String::from_utf8_lossy(&out.stdout).trim() != "false"
+    pub fn is_clean(&self) -> Result<bool, GitError> {
+        Ok(self.status(StatusOptions::default())?.is_clean())
     }
 
-    /// Returns a `HashMap` of git remotes
-    #[must_use]
-    #[inline]
-    pub fn remotes(&self) -> Option<HashMap<String, Remote>> {
-        let args = &["remote", "-v"];
-        let mut cmd = self.git();
-        let out = cmd
-            .args(args)
-            .output()
-            .expect("Failed to execute git-remote(1)");
-        if !out.status.success() {
-            return None;
-        }
-
-        let text = String::from_utf8_lossy(&out.stdout);
-        let mut my_map: HashMap<String, Remote> = HashMap::new();
-        let mut remote_lines: Vec<RemoteLine> = vec![];
-        for line in text.lines() {
-            let mut split = line.trim().split('\t');
-            let name = split.next().expect("Remote name").to_owned();
-            let rest = split.next().expect("Remote rest");
-            let mut rest_split = rest.split(' ');
-            let url = rest_split.next().expect("Remote url").to_owned();
-            let dir = if rest_split.next().expect("Remote direction") == "(fetch)" {
-                RemoteDir::Fetch
-            } else {
-                RemoteDir::Push
-            };
-            remote_lines.push(RemoteLine { name, url, dir });
-        }
-        for remote_line in remote_lines {
-            let mut remote = my_map.remove(&remote_line.name).unwrap_or(Remote {
-                name: remote_line.name.clone(),
-                push: None,
-                fetch: None,
-            });
-            match remote_line.dir {
-                RemoteDir::Fetch => remote.fetch = Some(remote_line.url.clone()),
-                RemoteDir::Push => remote.push = Some(remote_line.url.clone()),
-            }
-            my_map.insert(remote_line.name.clone(), remote);
-        }
-
-        Some(my_map)
-    }
-
-    /// Returns the HEAD commit id if ref HEAD exists
-    // TODO return a Result with custom error type
-    //
-    /// # Panics
-    ///
-    /// Panics when fails to resolve HEAD
-    #[must_use]
-    #[inline]
-    pub fn head(&self) -> String {
-        let args = &["rev-parse", "HEAD"];
-        let mut cmd = self.git();
-        let out = cmd
-            .args(args)
-            .output()
-            .expect("Failed to execute git-rev-parse(1)");
-        assert!(
-            out.status.success(),
-            "git rev-parse returned unexpected error"
-        );
-        String::from_utf8_lossy(&out.stdout).trim().to_owned()
+    /// Returns `true` if `HEAD` exists and there are no staged or unstaged changes, untracked
+    /// files are ignored. Guards the subtree commands.
+    fn is_clean_tracked(&self) -> Result<bool, GitError> {
+        let options = StatusOptions {
+            untracked: UntrackedFiles::No,
+            ..StatusOptions::default()
+        };
+        let status = self.status(options)?;
+        Ok(status.branch.oid.is_some() && status.is_clean())
     }
 
     /// Return path to git `WORK_TREE`
     ///
-    /// TODO move to generic repo trait
     /// TODO Remove optional
     #[must_use]
     #[inline]
@@ -483,86 +364,153 @@ impl Repository {
     #[must_use]
     #[inline]
     pub fn is_sparse(&self) -> bool {
-        let path = self.git_dir_path().join("info").join("sparse-checkout");
+        let path = self.git_dir.0.join("info").join("sparse-checkout");
         path.exists()
     }
+}
 
-    /// TODO move to generic repo trait
-    const fn git_dir(&self) -> &AbsoluteDirPath {
-        &self.git_dir
+/// Constructors
+impl Repository {
+    fn new(git_dir: AbsoluteDirPath, work_tree: AbsoluteDirPath) -> Self {
+        Self {
+            git_dir,
+            work_tree,
+            runner: Arc::new(ProcessRunner::default()),
+        }
     }
 
-    const fn git_dir_path(&self) -> &PathBuf {
-        &self.git_dir.0
+    /// Execute all git commands of this repository through the given runner
+    #[must_use]
+    #[inline]
+    pub fn with_runner(mut self, runner: Arc<dyn GitRunner>) -> Self {
+        self.runner = runner;
+        self
     }
 
     /// # Errors
     ///
-    /// Will return [`InvalidRefError`] if invalid reference provided
+    /// Will return [`RepoError`] when fails to find repository
     #[inline]
-    pub fn short_ref(&self, long_ref: &str) -> Result<String, InvalidRefError> {
-        let args = vec!["rev-parse", "--short", long_ref];
-        let mut cmd = self.git();
-        let out = cmd
-            .args(args)
-            .output()
-            .expect("Failed to execute git-commit(1)");
-        if !out.status.success() {
-            return Err(InvalidRefError(long_ref.to_owned()));
-        }
-
-        let short_ref = String::from_utf8_lossy(&out.stderr).to_string();
-        Ok(short_ref)
+    pub fn discover(path: &Path) -> Result<Self, RepoError> {
+        Self::discover_with_runner(path, Arc::new(ProcessRunner::default()))
     }
-}
 
-/// Constructors
-impl Repository {
+    /// Like [`Repository::discover`], but executes all git commands including the discovery
+    /// ones through `runner`
+    ///
     /// # Errors
     ///
     /// Will return [`RepoError`] when fails to find repository
     #[inline]
-    pub fn discover(path: &Path) -> Result<Self, RepoError> {
+    pub fn discover_with_runner(
+        path: &Path,
+        runner: Arc<dyn GitRunner>,
+    ) -> Result<Self, RepoError> {
         let git_dir = search_git_dir(path)?;
-        let work_tree = work_tree_from_git_dir(&git_dir)?;
-        Ok(Self { git_dir, work_tree })
+        Self::open_git_dir(git_dir, runner)
+    }
+
+    /// Open `git_dir` and find its work tree using `runner`
+    fn open_git_dir(
+        git_dir: AbsoluteDirPath,
+        runner: Arc<dyn GitRunner>,
+    ) -> Result<Self, RepoError> {
+        let work_tree = work_tree_from_git_dir(&GitCommand::new(runner.clone()), &git_dir)?;
+        Ok(Self::new(git_dir, work_tree).with_runner(runner))
     }
 
     /// # Errors
     ///
     /// Will return [`RepoError`] when fails to find repository
+    #[allow(clippy::should_implement_trait)]
     #[inline]
     pub fn default() -> Result<Self, RepoError> {
         Self::from_args(None, None, None)
     }
 
-    /// # Panics
+    /// Create a new repository using [git-init(1)](https://git-scm.com/docs/git-init)
     ///
-    /// When git execution fails
+    /// # Errors
+    ///
+    /// See [`InitError`]
+    #[inline]
+    pub fn create(path: &Path) -> Result<Self, InitError> {
+        Self::init(path, &InitOptions::default())
+    }
+
+    /// Create a new repository at `path` with the given options, missing directories are
+    /// created.
     ///
     /// # Errors
     ///
-    /// Returns a string output when something goes horrible wrong
+    /// See [`InitError`]
     #[inline]
-    pub fn create(path: &Path) -> Result<Self, String> {
-        let mut cmd = Command::new("git");
-        let out = cmd
-            .arg("init")
-            .current_dir(&path)
-            .output()
-            .expect("Executed git-init(1)");
-
-        if out.status.success() {
-            let work_tree = path.try_into().map_err(|e| format!("{}", e))?;
-            let git_dir = path
-                .join(".git")
-                .as_path()
-                .try_into()
-                .map_err(|e| format!("{}", e))?;
-            Ok(Self { git_dir, work_tree })
-        } else {
-            Err(String::from_utf8_lossy(&out.stderr).to_string())
-        }
+    pub fn init(path: &Path, options: &InitOptions) -> Result<Self, InitError> {
+        Self::init_with_runner(path, options, Arc::new(ProcessRunner::default()))
+    }
+
+    /// Like [`Repository::init`], but executes all git commands including the initialization
+    /// through `runner`
+    ///
+    /// # Errors
+    ///
+    /// See [`InitError`]
+    #[inline]
+    pub fn init_with_runner(
+        path: &Path,
+        options: &InitOptions,
+        runner: Arc<dyn GitRunner>,
+    ) -> Result<Self, InitError> {
+        run_init(runner.clone(), path, options, false)?;
+        let work_tree = path.try_into().map_err(GitError::from)?;
+        let git_dir = options
+            .get_separate_git_dir()
+            .map_or_else(|| path.join(".git"), Path::to_path_buf)
+            .as_path()
+            .try_into()
+            .map_err(GitError::from)?;
+        Ok(Self {
+            git_dir,
+            work_tree,
+            runner,
+        })
+    }
+
+    /// Clone `url` into `path` using [git-clone(1)](https://git-scm.com/docs/git-clone)
+    ///
+    /// # Errors
+    ///
+    /// See [`GitError`]
+    #[inline]
+    pub fn clone(url: &str, path: &Path, options: CloneOptions<'_>) -> Result<Self, GitError> {
+        Self::clone_with_runner(url, path, options, Arc::new(ProcessRunner::default()))
+    }
+
+    /// Like [`Repository::clone`], but executes all git commands including the clone through
+    /// `runner`
+    ///
+    /// # Errors
+    ///
+    /// See [`GitError`]
+    #[inline]
+    pub fn clone_with_runner(
+        url: &str,
+        path: &Path,
+        options: CloneOptions<'_>,
+        runner: Arc<dyn GitRunner>,
+    ) -> Result<Self, GitError> {
+        run_clone(runner.clone(), url, path, options, false)?;
+        let work_tree = path.try_into().map_err(GitError::from)?;
+        let git_dir = path
+            .join(".git")
+            .as_path()
+            .try_into()
+            .map_err(GitError::from)?;
+        Ok(Self {
+            git_dir,
+            work_tree,
+            runner,
+        })
     }
 
     /// # Errors
@@ -581,35 +529,32 @@ impl Repository {
                 search_git_dir(&cwd()?)?
             };
 
-            let work_tree = if let Ok(wt) = std::env::var("GIT_WORK_TREE") {
-                AbsoluteDirPath::try_from(wt.as_ref())?
+            if let Ok(wt) = std::env::var("GIT_WORK_TREE") {
+                let work_tree = AbsoluteDirPath::try_from(wt.as_ref())?;
+                Ok(Self::new(git_dir, work_tree))
             } else {
-                work_tree_from_git_dir(&git_dir)?
-            };
-
-            Ok(Self { git_dir, work_tree })
+                Self::open_git_dir(git_dir, Arc::new(ProcessRunner::default()))
+            }
         } else {
             let root = change.map_or_else(PathBuf::new, PathBuf::from);
             match (git, work) {
                 (Some(g_dir), None) => {
                     let git_dir = root.join(g_dir).as_path().try_into()?;
-                    let work_tree = work_tree_from_git_dir(&git_dir)?;
-                    Ok(Self { git_dir, work_tree })
+                    Self::open_git_dir(git_dir, Arc::new(ProcessRunner::default()))
                 }
                 (None, Some(w_dir)) => {
                     let work_tree = root.join(w_dir).as_path().try_into()?;
                     let git_dir = git_dir_from_work_tree(&work_tree)?;
-                    Ok(Self { git_dir, work_tree })
+                    Ok(Self::new(git_dir, work_tree))
                 }
                 (Some(g_dir), Some(w_dir)) => {
                     let git_dir = root.join(g_dir).as_path().try_into()?;
                     let work_tree = root.join(w_dir).as_path().try_into()?;
-                    Ok(Self { git_dir, work_tree })
+                    Ok(Self::new(git_dir, work_tree))
                 }
                 (None, None) => {
                     let git_dir = search_git_dir(&root)?;
-                    let work_tree = work_tree_from_git_dir(&git_dir)?;
-                    Ok(Self { git_dir, work_tree })
+                    Self::open_git_dir(git_dir, Arc::new(ProcessRunner::default()))
                 }
             }
         }
@@ -624,8 +569,8 @@ pub enum SubtreeAddError {
     BareRepository,
     #[error("Working tree dirty")]
     WorkTreeDirty,
-    #[error("{0}")]
-    Failure(String, i32),
+    #[error(transparent)]
+    Failure(#[from] GitError),
 }
 
 impl From<SubtreeAddError> for PosixError {
@@ -635,7 +580,19 @@ impl From<SubtreeAddError> for PosixError {
             SubtreeAddError::BareRepository | SubtreeAddError::WorkTreeDirty => {
                 Self::new(EINVAL, format!("{}", err))
             }
-            SubtreeAddError::Failure(msg, code) => Self::new(code, msg),
+            SubtreeAddError::Failure(e) => e.into(),
+        }
+    }
+}
+
+impl From<SubtreeAddError> for GitError {
+    #[inline]
+    fn from(err: SubtreeAddError) -> Self {
+        match err {
+            SubtreeAddError::Failure(e) => e,
+            SubtreeAddError::BareRepository | SubtreeAddError::WorkTreeDirty => {
+                Self::Invalid(format!("{}", err))
+            }
         }
     }
 }
@@ -646,16 +603,49 @@ impl From<SubtreeAddError> for PosixError {
 pub enum SubtreePullError {
     #[error("Working tree dirty")]
     WorkTreeDirty,
-    #[error("{0}")]
-    Failure(String, i32),
+    #[error(transparent)]
+    Failure(#[from] GitError),
+}
+
+impl From<SubtreePullError> for PosixError {
+    #[inline]
+    fn from(err: SubtreePullError) -> Self {
+        GitError::from(err).into()
+    }
+}
+
+impl From<SubtreePullError> for GitError {
+    #[inline]
+    fn from(err: SubtreePullError) -> Self {
+        match err {
+            SubtreePullError::Failure(e) => e,
+            SubtreePullError::WorkTreeDirty => Self::Invalid(format!("{}", err)),
+        }
+    }
 }
 
 /// Failed to push changes from subtree to remote
 #[allow(missing_docs)]
 #[derive(thiserror::Error, Debug, PartialEq, Eq)]
 pub enum SubtreePushError {
-    #[error("{0}")]
-    Failure(String, i32),
+    #[error(transparent)]
+    Failure(#[from] GitError),
+}
+
+impl From<SubtreePushError> for PosixError {
+    #[inline]
+    fn from(err: SubtreePushError) -> Self {
+        GitError::from(err).into()
+    }
+}
+
+impl From<SubtreePushError> for GitError {
+    #[inline]
+    fn from(err: SubtreePushError) -> Self {
+        match err {
+            SubtreePushError::Failure(e) => e,
+        }
+    }
 }
 
 /// Failed to split subtree
@@ -664,16 +654,39 @@ pub enum SubtreePushError {
 pub enum SubtreeSplitError {
     #[error("Work tree is dirty")]
     WorkTreeDirty,
-    #[error("{0}")]
-    Failure(String, i32),
+    #[error("Subtree prefix `{0}` does not exist")]
+    PrefixNotFound(String),
+    #[error("Squashing a split requires rejoin")]
+    SquashRequiresRejoin,
+    #[error(transparent)]
+    Failure(#[from] GitError),
+}
+
+impl From<SubtreeSplitError> for PosixError {
+    #[inline]
+    fn from(err: SubtreeSplitError) -> Self {
+        GitError::from(err).into()
+    }
+}
+
+impl From<SubtreeSplitError> for GitError {
+    #[inline]
+    fn from(err: SubtreeSplitError) -> Self {
+        match err {
+            SubtreeSplitError::Failure(e) => e,
+            SubtreeSplitError::WorkTreeDirty
+            | SubtreeSplitError::PrefixNotFound(_)
+            | SubtreeSplitError::SquashRequiresRejoin => Self::Invalid(format!("{}", err)),
+        }
+    }
 }
 
 /// Failure to stage
 #[allow(missing_docs)]
 #[derive(thiserror::Error, Debug, PartialEq, Eq)]
 pub enum StagingError {
-    #[error("`{0}`")]
-    Failure(String, i32),
+    #[error(transparent)]
+    Failure(#[from] GitError),
     #[error("File does not exist: `{0}`")]
     FileDoesNotExist(PathBuf),
 }
@@ -684,38 +697,77 @@ impl From<StagingError> for PosixError {
         let msg = format!("{}", e);
         match e {
             StagingError::FileDoesNotExist(_) => Self::new(ENOENT, msg),
-            StagingError::Failure(_, code) => Self::new(code, msg),
+            StagingError::Failure(err) => err.into(),
+        }
+    }
+}
+
+impl From<StagingError> for GitError {
+    #[inline]
+    fn from(err: StagingError) -> Self {
+        match err {
+            StagingError::Failure(e) => e,
+            StagingError::FileDoesNotExist(_) => Self::Invalid(format!("{}", err)),
         }
     }
 }
 
 /// Error during stashing operation
 #[allow(missing_docs)]
-#[derive(thiserror::Error, Debug)]
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
 pub enum StashingError {
     #[error("Failed to stash changes in GIT_WORK_TREE")]
-    Save(i32, String),
+    Save(#[source] GitError),
     #[error("Failed to pop stashed changes in GIT_WORK_TREE")]
-    Pop(i32, String),
+    Pop(#[source] GitError),
+}
+
+impl From<StashingError> for PosixError {
+    #[inline]
+    fn from(err: StashingError) -> Self {
+        GitError::from(err).into()
+    }
+}
+
+impl From<StashingError> for GitError {
+    #[inline]
+    fn from(err: StashingError) -> Self {
+        match err {
+            StashingError::Save(e) | StashingError::Pop(e) => e,
+        }
+    }
 }
 
 /// Error during committing
 #[allow(missing_docs)]
-#[derive(thiserror::Error, Debug)]
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
 pub enum CommitError {
-    #[error("`{0}`")]
-    Failure(String, i32),
+    #[error(transparent)]
+    Failure(#[from] GitError),
+}
+
+impl From<CommitError> for PosixError {
+    #[inline]
+    fn from(err: CommitError) -> Self {
+        GitError::from(err).into()
+    }
+}
+
+impl From<CommitError> for GitError {
+    #[inline]
+    fn from(err: CommitError) -> Self {
+        match err {
+            CommitError::Failure(e) => e,
+        }
+    }
 }
 
 /// Failed to find reference on remote
-#[derive(thiserror::Error, Debug)]
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
 pub enum RefSearchError {
     /// Thrown when `git-ls-remote(1)` fails to execute.
-    #[error("{0}")]
-    Failure(String),
-    /// Generic IO error
-    #[error("{0}")]
-    IOError(#[from] std::io::Error),
+    #[error(transparent)]
+    Failure(#[from] GitError),
     /// Failed to find reference
     #[error("Failed to find reference {0}")]
     NotFound(String),
@@ -728,14 +780,24 @@ impl From<RefSearchError> for PosixError {
     #[inline]
     fn from(err: RefSearchError) -> Self {
         match err {
-            RefSearchError::Failure(msg) => Self::new(ENOENT, msg),
-            RefSearchError::IOError(e) => e.into(),
+            RefSearchError::Failure(e) => e.into(),
             RefSearchError::NotFound(s) => Self::new(ENOENT, s),
             RefSearchError::ParsingFailure(e) => Self::new(EINVAL, e),
         }
     }
 }
 
+impl From<RefSearchError> for GitError {
+    #[inline]
+    fn from(err: RefSearchError) -> Self {
+        match err {
+            RefSearchError::Failure(e) => e,
+            RefSearchError::NotFound(_) => Self::Invalid(format!("{}", err)),
+            RefSearchError::ParsingFailure(msg) => Self::Parse(msg),
+        }
+    }
+}
+
 /// Functions
 impl Repository {
     /// Return config value for specified key
@@ -743,22 +805,9 @@ impl Repository {
     /// # Errors
     ///
     /// See [`CommitError`]
-    ///
-    /// # Panics
-    ///
-    /// When `git-commit(1)` fails to execute
     #[inline]
     pub fn commit(&self, message: &str) -> Result<(), CommitError> {
-        let out = self
-            .git()
-            .args(&["commit", "-m", message])
-            .output()
-            .expect("Executed git-commit(1)");
-        if out.status.code().unwrap_or(1) != 0 {
-            let msg = String::from_utf8_lossy(out.stderr.as_ref()).to_string();
-            let code = out.status.code().unwrap_or(1);
-            return Err(CommitError::Failure(msg, code));
-        }
+        self.git().args(["commit", "-m", message]).run()?;
         Ok(())
     }
 
@@ -773,7 +822,7 @@ impl Repository {
         no_verify: bool,
     ) -> Result<(), CommitError> {
         let mut cmd = self.git();
-        cmd.args(&["commit", "--quiet", "--no-edit"]);
+        cmd.args(["commit", "--quiet", "--no-edit"]);
 
         if allow_empty {
             cmd.arg("--allow-empty");
@@ -783,117 +832,37 @@ impl Repository {
             cmd.arg("--no-verify");
         }
 
-        cmd.args(&["--message", message]);
+        cmd.args(["--message", message]);
 
-        let out = cmd.output().expect("Failed to execute git-commit(1)");
-        if out.status.code().expect("Expected exit code") != 0 {
-            let msg = String::from_utf8_lossy(out.stderr.as_ref()).to_string();
-            let code = out.status.code().unwrap_or(1);
-            return Err(CommitError::Failure(msg, code));
-        }
+        cmd.run()?;
         Ok(())
     }
-    /// Read file from workspace or use `git-show(1)` if bare repository
-    ///
-    /// # Panics
-    ///
-    /// When UTF-8 encoding path fails
-    ///
     /// # Errors
     ///
-    /// When fails throws [`std::io::Error`]
-    /// TODO move to generic repo trait
+    /// See [`GitError`]
     #[inline]
-    pub fn hack_read_file(&self, path: &Path) -> std::io::Result<Vec<u8>> {
-        let absolute_path = self.work_tree.0.join(path);
-        std::fs::read(absolute_path)
-    }
-
-    /// Returns true if the `first` commit is an ancestor of the `second` commit.
-    #[must_use]
-    #[inline]
-    pub fn is_ancestor(&self, first: &str, second: &str) -> bool {
-        let args = vec!["merge-base", "--is-ancestor", first, second];
-        let mut cmd = self.git();
-        cmd.args(args);
-        let proc = cmd.output().expect("Failed to run git-merge-base(1)");
-        proc.status.success()
-    }
-
-    /// # Errors
-    ///
-    /// See [`RefSearchError`]
-    #[inline]
-    pub fn remote_ref_to_id(&self, remote: &str, git_ref: &str) -> Result<String, RefSearchError> {
-        let proc = self.git().args(&["ls-remote", remote, git_ref]).output()?;
-        if !proc.status.success() {
-            let msg = String::from_utf8_lossy(proc.stderr.as_ref()).to_string();
-            return Err(RefSearchError::Failure(msg));
-        }
-        let stdout = String::from_utf8_lossy(&proc.stdout);
-        if let Some(first_line) = stdout.lines().next() {
-            if let Some(id) = first_line.split('\t').next() {
-                return Ok(id.to_owned());
-            }
-            return Err(RefSearchError::ParsingFailure(first_line.to_owned()));
-        }
-
-        Err(RefSearchError::NotFound(git_ref.to_owned()))
-    }
-
-    /// # Errors
-    ///
-    /// When fails will return a String describing the issue.
-    ///
-    /// # Panics
-    ///
-    /// When git-sparse-checkout(1) execution fails
-    #[inline]
-    pub fn sparse_checkout_add(&self, pattern: &str) -> Result<(), String> {
-        let out = self
-            .git()
+    pub fn sparse_checkout_add(&self, pattern: &str) -> Result<(), GitError> {
+        self.git()
             .args(["sparse-checkout", "add"])
             .arg(pattern)
-            .output()
-            .expect("Failed to execute git sparse-checkout");
-
-        if out.status.success() {
-            Ok(())
-        } else {
-            Err(String::from_utf8_lossy(out.stderr.as_ref()).to_string())
-        }
+            .run()?;
+        Ok(())
     }
 
     /// # Errors
     ///
     /// See [`StagingError`]
-    ///
-    /// # Panics
-    ///
-    /// Panics if fails to execute `git-add(1)`
     #[inline]
     pub fn stage(&self, path: &Path) -> Result<(), StagingError> {
-        let relative_path = if path.is_absolute() {
-            path.strip_prefix(self.work_tree().expect("Non bare repo"))
-                .expect("Stripped path prefix")
-        } else {
-            path
-        };
+        let relative_path = path.strip_prefix(&self.work_tree.0).unwrap_or(path);
 
-        let file = relative_path.as_os_str();
-        let out = self
-            .git()
-            .args(&["add", "--"])
-            .arg(file)
-            .output()
-            .expect("Executed git-add(1)");
+        let mut cmd = self.git();
+        cmd.args(["add", "--"]).arg(relative_path.as_os_str());
+        let out = cmd.output()?;
         match out.status.code().unwrap_or(1) {
             0 => Ok(()),
             128 => Err(StagingError::FileDoesNotExist(relative_path.to_path_buf())),
-            e => {
-                let msg = String::from_utf8_lossy(&out.stdout).to_string();
-                Err(StagingError::Failure(msg, e))
-            }
+            _ => Err(GitError::from_output(&cmd, &out).into()),
         }
     }
 
@@ -907,14 +876,9 @@ impl Repository {
         let mut cmd = self.git();
         cmd.arg("stash");
         cmd.arg("--quiet");
-        cmd.args(&["--include-untracked", "-m", message]);
+        cmd.args(["--include-untracked", "-m", message]);
 
-        let out = cmd.output().expect("Failed to execute git-stash(1)");
-        if !out.status.success() {
-            let stderr = String::from_utf8_lossy(&out.stderr).to_string();
-            let code = out.status.code().unwrap_or(1);
-            return Err(StashingError::Save(code, stderr));
-        }
+        cmd.run().map_err(StashingError::Save)?;
         Ok(())
     }
 
@@ -926,26 +890,14 @@ impl Repository {
     #[inline]
     pub fn stash_pop(&self) -> Result<(), StashingError> {
         let mut cmd = self.git();
-        let out = cmd
-            .args(&["stash", "pop", "--quiet", "--index"])
-            .output()
-            .expect("Failed to execute git-stash(1)");
-
-        if !out.status.success() {
-            let stderr = String::from_utf8_lossy(&out.stderr).to_string();
-            let code = out.status.code().unwrap_or(1);
-            return Err(StashingError::Pop(code, stderr));
-        }
+        cmd.args(["stash", "pop", "--quiet", "--index"]);
+        cmd.run().map_err(StashingError::Pop)?;
         Ok(())
     }
 
     /// # Errors
     ///
     /// Fails if current repo is bare or dirty. In error cases see the provided string.
-    ///
-    /// # Panics
-    ///
-    /// When git-subtree(1) execution fails
     #[inline]
     pub fn subtree_add(
         &self,
@@ -954,68 +906,37 @@ impl Repository {
         revision: &str,
         message: &str,
     ) -> Result<(), SubtreeAddError> {
-        if !self.is_clean() {
+        if !self.is_clean_tracked()? {
             return Err(SubtreeAddError::WorkTreeDirty);
         }
 
         let args = vec!["-q", "-P", prefix, url, revision, "-m", message];
         let mut cmd = self.git();
         cmd.arg("subtree").arg("add").args(args);
-        let out = cmd.output().expect("Failed to execute git-subtree(1)");
-        if out.status.success() {
-            Ok(())
-        } else {
-            let msg = String::from_utf8_lossy(out.stderr.as_ref()).to_string();
-            let code = out.status.code().unwrap_or(1);
-            Err(SubtreeAddError::Failure(msg, code))
-        }
+        cmd.run()?;
+        Ok(())
     }
 
-    /// # Errors
+    /// Split the history of `prefix` into a separate history, returns the split commit id
     ///
-    /// Fails if current repo is bare or dirty. In error cases see the provided string.
-    ///
-    /// # Panics
+    /// # Errors
     ///
-    /// When git-subtree(1) execution fails
+    /// Fails if the prefix does not exist or the work tree is dirty when rejoining.
     #[inline]
-    pub fn subtree_split(&self, prefix: &str) -> Result<(), SubtreeSplitError> {
-        if !self.is_clean() {
+    pub fn subtree_split(
+        &self,
+        prefix: &str,
+        options: SubtreeSplitOptions<'_>,
+    ) -> Result<String, SubtreeSplitError> {
+        if options.get_rejoin() && !self.is_clean_tracked()? {
             return Err(SubtreeSplitError::WorkTreeDirty);
         }
-
-        let args = vec!["-P", prefix, "--rejoin", "HEAD"];
-        let mut cmd = self.git();
-        cmd.arg("subtree").arg("split").args(args);
-        let result = cmd
-            .spawn()
-            .expect("Failed to execute git-subtree(1)")
-            .wait();
-        match result {
-            Ok(code) => {
-                if code.success() {
-                    Ok(())
-                } else {
-                    Err(SubtreeSplitError::Failure(
-                        "git-subtree split failed".to_owned(),
-                        1,
-                    ))
-                }
-            }
-            Err(e) => {
-                let msg = format!("{}", e);
-                Err(SubtreeSplitError::Failure(msg, 1))
-            }
-        }
+        crate::subtree_split::run_split(self.git(), prefix, options)
     }
 
     /// # Errors
     ///
     /// Fails if current repo is bare or dirty. In error cases see the provided string.
-    ///
-    /// # Panics
-    ///
-    /// When git-subtree(1) execution fails
     #[inline]
     pub fn subtree_pull(
         &self,
@@ -1024,20 +945,14 @@ impl Repository {
         git_ref: &str,
         message: &str,
     ) -> Result<(), SubtreePullError> {
-        if !self.is_clean() {
+        if !self.is_clean_tracked()? {
             return Err(SubtreePullError::WorkTreeDirty);
         }
         let args = vec!["-q", "-P", prefix, remote, git_ref, "-m", message];
         let mut cmd = self.git();
         cmd.arg("subtree").arg("pull").args(args);
-        let out = cmd.output().expect("Failed to execute git-subtree(1)");
-        if out.status.success() {
-            Ok(())
-        } else {
-            let msg = String::from_utf8_lossy(out.stderr.as_ref()).to_string();
-            let code = out.status.code().unwrap_or(1);
-            Err(SubtreePullError::Failure(msg, code))
-        }
+        cmd.run()?;
+        Ok(())
     }
 
     /// # Errors
@@ -1053,88 +968,63 @@ impl Repository {
         let args = vec!["subtree", "push", "-q", "-P", prefix, remote, git_ref];
         let mut cmd = self.git();
         cmd.args(args);
-        let out = cmd.output().expect("Failed to execute git-subtree(1)");
-        if out.status.success() {
-            Ok(())
-        } else {
-            let msg = String::from_utf8_lossy(out.stderr.as_ref()).to_string();
-            let code = out.status.code().unwrap_or(1);
-            Err(SubtreePushError::Failure(msg, code))
-        }
+        cmd.run()?;
+        Ok(())
     }
 }
 
 /// Failed to resolve given value to a commit id
 #[allow(missing_docs)]
-#[derive(thiserror::Error, Debug)]
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
 pub enum InvalidCommitishError {
     #[error("Invalid reference or commit id: `{0}`")]
     One(String),
     #[error("One or Multiple invalid reference or commit ids: `{0:?}`")]
     Multiple(Vec<String>),
-    #[error("{0}")]
-    Failure(String, i32),
+    #[error(transparent)]
+    Failure(#[from] GitError),
 }
 
-/// Commit Functions
-impl Repository {
-    ///  Find best common ancestor between to commits.
-    ///
-    /// # Errors
-    ///
-    /// Will return `InvalidCommitishError::Multiple` when one or multiple provided ids do not
-    /// exist
-    ///
-    /// # Panics
-    ///
-    /// When exit code of git-merge-base(1) is not 0 or 128
+impl From<InvalidCommitishError> for PosixError {
     #[inline]
-    pub fn merge_base(&self, ids: &[&str]) -> Result<Option<String>, InvalidCommitishError> {
-        let output = self
-            .git()
-            .arg("merge-base")
-            .args(ids)
-            .output()
-            .expect("Executing git-merge-base(1)");
-        if output.status.success() {
-            let tmp = String::from_utf8_lossy(&output.stdout);
-            if tmp.is_empty() {
-                return Ok(None);
-            }
-            let result = tmp.trim_end();
-            return Ok(Some(result.to_owned()));
-        }
-        match output.status.code().expect("Getting status code") {
-            128 => {
-                let tmp = ids.to_vec();
-                let e_ids = tmp.iter().map(ToString::to_string).collect();
-                Err(InvalidCommitishError::Multiple(e_ids))
-            }
-            1 => Ok(None),
-            code => {
-                let msg = String::from_utf8_lossy(&output.stdout);
-                Err(InvalidCommitishError::Failure(msg.to_string(), code))
+    fn from(err: InvalidCommitishError) -> Self {
+        GitError::from(err).into()
+    }
+}
+
+impl From<InvalidCommitishError> for GitError {
+    #[inline]
+    fn from(err: InvalidCommitishError) -> Self {
+        match err {
+            InvalidCommitishError::Failure(e) => e,
+            InvalidCommitishError::One(_) | InvalidCommitishError::Multiple(_) => {
+                Self::Invalid(format!("{}", err))
             }
         }
     }
+}
 
-    /// Returns a prepared git `Command` struct
-    /// TODO move to generic repo trait
-    #[must_use]
+impl GenericRepository for Repository {
     #[inline]
-    pub fn git(&self) -> Command {
-        let mut cmd = Command::new("git");
-        let git_dir = self.git_dir().0.to_str().expect("Convert to string");
-        cmd.env("GIT_DIR", git_dir);
+    fn git(&self) -> GitCommand {
+        let mut cmd = GitCommand::new(self.runner.clone());
+        cmd.env("GIT_DIR", &self.git_dir.0);
         cmd.env("GIT_WORK_TREE", &self.work_tree.0);
+        cmd.env("LC_ALL", "C");
         cmd.current_dir(&self.work_tree.0);
         cmd
     }
-}
 
-impl GenericRepository for Repository {
-    fn gen_git(&self) -> Command {
-        self.git()
+    #[inline]
+    fn git_dir(&self) -> &Path {
+        &self.git_dir.0
+    }
+
+    /// Read file from the work tree
+    #[inline]
+    fn hack_read_file(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        let absolute_path = self.work_tree.0.join(path);
+        std::fs::read(absolute_path)
     }
 }
 
@@ -1184,7 +1074,10 @@ mod test {
             let readme = repo_path.join("README.md");
             std::fs::File::create(&readme).unwrap();
             std::fs::write(&readme, "# README").unwrap();
-            assert!(!repo.is_clean(), "Repo is unclean if sth. is unstaged");
+            assert!(
+                !repo.is_clean().unwrap(),
+                "Repo is unclean if sth. is unstaged"
+            );
         }
 
         #[test]
@@ -1196,12 +1089,15 @@ mod test {
             let readme = repo_path.join("README.md");
             std::fs::File::create(&readme).unwrap();
             repo.stage(&readme).unwrap();
-            assert!(!repo.is_clean(), "Repo is unclean if sth. is staged");
+            assert!(
+                !repo.is_clean().unwrap(),
+                "Repo is unclean if sth. is staged"
+            );
         }
     }
 
     mod config {
-        use crate::BareRepository;
+        use crate::{BareRepository, GenericRepository};
         use tempfile::TempDir;
 
         #[test]
@@ -1217,7 +1113,7 @@ mod test {
     }
 
     mod sparse_checkout {
-        use crate::Repository;
+        use crate::{GenericRepository, Repository};
         use std::process::Command;
         use tempfile::TempDir;
 
@@ -1228,7 +1124,7 @@ mod test {
             let repo = Repository::create(repo_path).expect("Created repository");
             let mut cmd = Command::new("git");
             let out = cmd
-                .args(&["sparse-checkout", "init"])
+                .args(["sparse-checkout", "init"])
                 .current_dir(repo_path)
                 .output()
                 .unwrap();
@@ -1343,7 +1239,7 @@ mod test {
 
     mod remote_ref_resolution {
         use crate::RefSearchError;
-        use crate::Repository;
+        use crate::{GenericRepository, Repository};
         use tempfile::TempDir;
 
         #[test]
@@ -0,0 +1,348 @@
+use crate::{GitCommand, GitError};
+use std::path::PathBuf;
+
+/// Fields requested from [git-log(1)](https://git-scm.com/docs/git-log), separated by `NUL`.
+const FORMAT: &str = "%H%x00%P%x00%T%x00%an%x00%ae%x00%ad%x00%cn%x00%ce%x00%cd%x00%s%x00%b%x00%(trailers:only,unfold)";
+const FIELDS: usize = 12;
+
+/// Author or committer of a commit
+#[allow(missing_docs)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Signature {
+    pub name: String,
+    pub email: String,
+    /// Seconds since the UNIX epoch
+    pub time: i64,
+    /// Timezone offset from UTC in minutes
+    pub offset: i32,
+}
+
+/// A commit trailer like `Signed-off-by: Max Musterman <max@example.com>`
+#[allow(missing_docs)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Trailer {
+    pub key: String,
+    pub value: String,
+}
+
+/// A commit as returned by [`crate::GenericRepository::log`]
+#[allow(missing_docs)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Commit {
+    pub id: String,
+    pub parents: Vec<String>,
+    pub tree: String,
+    pub author: Signature,
+    pub committer: Signature,
+    pub subject: String,
+    /// Commit message without the subject, including trailers
+    pub body: String,
+    pub trailers: Vec<Trailer>,
+}
+
+impl Commit {
+    /// Returns `true` if the commit has more than one parent
+    #[must_use]
+    #[inline]
+    pub fn is_merge(&self) -> bool {
+        self.parents.len() > 1
+    }
+}
+
+/// Restrict the log to merge or non-merge commits
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MergeFilter {
+    /// Show all commits
+    #[default]
+    All,
+    /// `--merges`
+    MergesOnly,
+    /// `--no-merges`
+    NoMerges,
+}
+
+/// Options for [`crate::GenericRepository::log`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LogOptions {
+    paths: Vec<PathBuf>,
+    first_parent: bool,
+    max_count: Option<usize>,
+    since: Option<String>,
+    until: Option<String>,
+    author: Option<String>,
+    grep: Option<String>,
+    merges: MergeFilter,
+}
+
+impl LogOptions {
+    /// Only show commits touching the given path
+    #[must_use]
+    #[inline]
+    pub fn path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.paths.push(path.into());
+        self
+    }
+
+    /// `--first-parent`
+    #[must_use]
+    #[inline]
+    pub const fn first_parent(mut self) -> Self {
+        self.first_parent = true;
+        self
+    }
+
+    /// `--max-count`
+    #[must_use]
+    #[inline]
+    pub const fn max_count(mut self, count: usize) -> Self {
+        self.max_count = Some(count);
+        self
+    }
+
+    /// `--since`, accepts any date understood by git
+    #[must_use]
+    #[inline]
+    pub fn since(mut self, date: &str) -> Self {
+        self.since = Some(date.to_owned());
+        self
+    }
+
+    /// `--until`, accepts any date understood by git
+    #[must_use]
+    #[inline]
+    pub fn until(mut self, date: &str) -> Self {
+        self.until = Some(date.to_owned());
+        self
+    }
+
+    /// `--author`
+    #[must_use]
+    #[inline]
+    pub fn author(mut self, pattern: &str) -> Self {
+        self.author = Some(pattern.to_owned());
+        self
+    }
+
+    /// `--grep`
+    #[must_use]
+    #[inline]
+    pub fn grep(mut self, pattern: &str) -> Self {
+        self.grep = Some(pattern.to_owned());
+        self
+    }
+
+    /// `--merges` or `--no-merges`
+    #[must_use]
+    #[inline]
+    pub const fn merges(mut self, filter: MergeFilter) -> Self {
+        self.merges = filter;
+        self
+    }
+
+    fn to_args(&self) -> Vec<String> {
+        let mut result = vec![];
+        if self.first_parent {
+            result.push("--first-parent".to_owned());
+        }
+        if let Some(count) = self.max_count {
+            result.push(format!("--max-count={}", count));
+        }
+        if let Some(date) = &self.since {
+            result.push(format!("--since={}", date));
+        }
+        if let Some(date) = &self.until {
+            result.push(format!("--until={}", date));
+        }
+        if let Some(pattern) = &self.author {
+            result.push(format!("--author={}", pattern));
+        }
+        if let Some(pattern) = &self.grep {
+            result.push(format!("--grep={}", pattern));
+        }
+        match self.merges {
+            MergeFilter::All => {}
+            MergeFilter::MergesOnly => result.push("--merges".to_owned()),
+            MergeFilter::NoMerges => result.push("--no-merges".to_owned()),
+        }
+        result
+    }
+}
+
+/// Iterator over the commits returned by [`crate::GenericRepository::log`]
+#[derive(Clone, Debug)]
+pub struct Log {
+    data: String,
+    pos: usize,
+}
+
+impl Iterator for Log {
+    type Item = Result<Commit, GitError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = &self.data[self.pos..];
+        if rest.trim_start_matches(['\0', '\n']).is_empty() {
+            return None;
+        }
+        let mut fields = Vec::with_capacity(FIELDS);
+        let mut consumed = 0;
+        for field in rest.splitn(FIELDS + 1, '\0').take(FIELDS) {
+            consumed += field.len() + 1;
+            fields.push(field);
+        }
+        self.pos = (self.pos + consumed).min(self.data.len());
+        if fields.len() != FIELDS {
+            self.pos = self.data.len();
+            return Some(Err(GitError::Parse(format!(
+                "Expected {} fields, got {}",
+                FIELDS,
+                fields.len()
+            ))));
+        }
+        Some(parse_commit(&fields))
+    }
+}
+
+pub(crate) fn parse_signature(name: &str, email: &str, date: &str) -> Result<Signature, GitError> {
+    let parse_error = || GitError::Parse(format!("Invalid raw date `{}`", date));
+    let (time, offset) = date.split_once(' ').ok_or_else(parse_error)?;
+    let time = time.parse().map_err(|_| parse_error())?;
+    let offset = parse_offset(offset).ok_or_else(parse_error)?;
+    Ok(Signature {
+        name: name.to_owned(),
+        email: email.to_owned(),
+        time,
+        offset,
+    })
+}
+
+/// Parse a timezone offset like `+0130` into minutes
+fn parse_offset(offset: &str) -> Option<i32> {
+    let (sign, digits) = match offset.split_at_checked(1)? {
+        ("+", digits) => (1, digits),
+        ("-", digits) => (-1, digits),
+        _ => return None,
+    };
+    if digits.len() != 4 {
+        return None;
+    }
+    let hours: i32 = digits[..2].parse().ok()?;
+    let minutes: i32 = digits[2..].parse().ok()?;
+    Some(sign * (hours * 60 + minutes))
+}
+
+fn parse_trailers(text: &str) -> Vec<Trailer> {
+    text.lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| Trailer {
+            key: key.trim().to_owned(),
+            value: value.trim().to_owned(),
+        })
+        .collect()
+}
+
+fn parse_commit(fields: &[&str]) -> Result<Commit, GitError> {
+    Ok(Commit {
+        id: fields[0].trim_start_matches('\n').to_owned(),
+        parents: fields[1]
+            .split_whitespace()
+            .map(ToOwned::to_owned)
+            .collect(),
+        tree: fields[2].to_owned(),
+        author: parse_signature(fields[3], fields[4], fields[5])?,
+        committer: parse_signature(fields[6], fields[7], fields[8])?,
+        subject: fields[9].to_owned(),
+        body: fields[10].trim_end().to_owned(),
+        trailers: parse_trailers(fields[11]),
+    })
+}
+
+/// Read the commit history, see [`crate::GenericRepository::log`]
+pub(crate) fn log(mut cmd: GitCommand, range: &str, options: &LogOptions) -> Result<Log, GitError> {
+    cmd.args(["log", "-z", "--date=raw", "--no-color"])
+        .arg(format!("--format={}", FORMAT))
+        .args(options.to_args())
+        .args(["--end-of-options", range, "--"])
+        .args(&options.paths);
+    let data = cmd.read()?;
+    Ok(Log { data, pos: 0 })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{setup_test_author, GenericRepository, LogOptions, MergeFilter, Repository};
+    use tempfile::TempDir;
+
+    fn repo_with_history(tmp_dir: &TempDir) -> Repository {
+        setup_test_author();
+        let repo = Repository::create(tmp_dir.path()).expect("Created repository");
+        for (file, message) in [
+            ("README.md", "Add README"),
+            (
+                "foo.txt",
+                "Add foo\n\nSome body\n\nSigned-off-by: Max <max@example.com>",
+            ),
+        ] {
+            let path = tmp_dir.path().join(file);
+            std::fs::write(&path, message).unwrap();
+            repo.stage(&path).unwrap();
+            repo.commit(message).unwrap();
+        }
+        repo
+    }
+
+    #[test]
+    fn log() {
+        let tmp_dir = TempDir::new().unwrap();
+        let repo = repo_with_history(&tmp_dir);
+        let commits: Vec<_> = repo
+            .log("HEAD", &LogOptions::default())
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(commits.len(), 2);
+        let head = &commits[0];
+        assert_eq!(head.id, repo.head().unwrap());
+        assert_eq!(head.parents, vec![commits[1].id.clone()]);
+        assert_eq!(head.subject, "Add foo");
+        assert_eq!(head.author.email, "max@example.com");
+        assert_eq!(head.trailers.len(), 1);
+        assert_eq!(head.trailers[0].key, "Signed-off-by");
+        assert_eq!(head.trailers[0].value, "Max <max@example.com>");
+        assert!(commits[1].parents.is_empty());
+        assert!(commits[1].trailers.is_empty());
+    }
+
+    #[test]
+    fn options() {
+        let tmp_dir = TempDir::new().unwrap();
+        let repo = repo_with_history(&tmp_dir);
+        let by_path: Vec<_> = repo
+            .log("HEAD", &LogOptions::default().path("README.md"))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(by_path.len(), 1);
+        assert_eq!(by_path[0].subject, "Add README");
+
+        let limited = repo
+            .log("HEAD", &LogOptions::default().max_count(1))
+            .unwrap();
+        assert_eq!(limited.count(), 1);
+
+        let merges = repo
+            .log(
+                "HEAD",
+                &LogOptions::default().merges(MergeFilter::MergesOnly),
+            )
+            .unwrap();
+        assert_eq!(merges.count(), 0);
+    }
+
+    #[test]
+    fn offset() {
+        assert_eq!(super::parse_offset("+0130"), Some(90));
+        assert_eq!(super::parse_offset("-0800"), Some(-480));
+        assert_eq!(super::parse_offset("0800"), None);
+    }
+}
@@ -0,0 +1,219 @@
+use crate::{GitCommand, GitError, ProcessRunner};
+use posix_errors::{PosixError, ENOENT};
+use std::sync::Arc;
+
+/// Failed to list or resolve remote references
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum LsRemoteError {
+    /// The remote `HEAD` does not exist or does not point to a branch
+    #[error("Remote `{0}` has no HEAD branch")]
+    NoHead(String),
+    #[allow(missing_docs)]
+    #[error(transparent)]
+    Failure(#[from] GitError),
+}
+
+impl From<LsRemoteError> for GitError {
+    #[inline]
+    fn from(err: LsRemoteError) -> Self {
+        match err {
+            LsRemoteError::Failure(e) => e,
+            LsRemoteError::NoHead(_) => Self::Invalid(format!("{}", err)),
+        }
+    }
+}
+
+impl From<LsRemoteError> for PosixError {
+    #[inline]
+    fn from(err: LsRemoteError) -> Self {
+        match err {
+            LsRemoteError::Failure(e) => e.into(),
+            LsRemoteError::NoHead(_) => Self::new(ENOENT, format!("{}", err)),
+        }
+    }
+}
+
+/// A reference advertised by a remote, see [`ls_remote`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemoteRef {
+    /// Object id the reference points to
+    pub id: String,
+    /// Full name, i.e. `refs/heads/main` or `HEAD`
+    pub name: String,
+    /// Object an annotated tag peels to
+    pub peeled: Option<String>,
+    /// Referenced name for symbolic references, i.e. `refs/heads/main` for `HEAD`
+    pub symref_target: Option<String>,
+}
+
+/// Options for [`ls_remote`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LsRemoteOptions {
+    heads: bool,
+    tags: bool,
+    refs_only: bool,
+    patterns: Vec<String>,
+}
+
+impl LsRemoteOptions {
+    /// Only list branches, can be combined with [`LsRemoteOptions::tags`]
+    #[must_use]
+    #[inline]
+    pub const fn heads(mut self, heads: bool) -> Self {
+        self.heads = heads;
+        self
+    }
+
+    /// Only list tags, can be combined with [`LsRemoteOptions::heads`]
+    #[must_use]
+    #[inline]
+    pub const fn tags(mut self, tags: bool) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// Do not list `HEAD` & peeled tags
+    #[must_use]
+    #[inline]
+    pub const fn refs_only(mut self, refs_only: bool) -> Self {
+        self.refs_only = refs_only;
+        self
+    }
+
+    /// Only list references matching `pattern`, i.e. `main` or `refs/tags/v*`; may be
+    /// repeated
+    #[must_use]
+    #[inline]
+    pub fn pattern(mut self, pattern: &str) -> Self {
+        self.patterns.push(pattern.to_owned());
+        self
+    }
+}
+
+/// Parse `git ls-remote --symref` output.
+///
+/// Lines are `<id>\t<name>`, peeled tags are listed as `<id>\t<name>^{}` and symbolic
+/// references are preceded by `ref: <target>\t<name>`.
+fn parse_remote_refs(text: &str) -> Result<Vec<RemoteRef>, GitError> {
+    let mut result: Vec<RemoteRef> = vec![];
+    let mut symrefs: Vec<(String, String)> = vec![];
+    for line in text.lines().filter(|l| !l.is_empty()) {
+        let (id, name) = line.split_once('\t').ok_or_else(|| {
+            GitError::Parse(format!("Unexpected git-ls-remote(1) line `{}`", line))
+        })?;
+        if let Some(target) = id.strip_prefix("ref: ") {
+            symrefs.push((name.to_owned(), target.to_owned()));
+        } else if let Some(tag) = name.strip_suffix("^{}") {
+            if let Some(reference) = result.iter_mut().rev().find(|r| r.name == tag) {
+                reference.peeled = Some(id.to_owned());
+            }
+        } else {
+            let symref_target = symrefs
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, target)| target.clone());
+            result.push(RemoteRef {
+                id: id.to_owned(),
+                name: name.to_owned(),
+                peeled: None,
+                symref_target,
+            });
+        }
+    }
+    Ok(result)
+}
+
+pub(crate) fn list_remote_refs(
+    mut cmd: GitCommand,
+    url: &str,
+    options: &LsRemoteOptions,
+) -> Result<Vec<RemoteRef>, LsRemoteError> {
+    cmd.args(["ls-remote", "--symref"]);
+    if options.heads {
+        cmd.arg("--heads");
+    }
+    if options.tags {
+        cmd.arg("--tags");
+    }
+    if options.refs_only {
+        cmd.arg("--refs");
+    }
+    cmd.arg("--").arg(url).args(&options.patterns);
+    Ok(parse_remote_refs(&cmd.read()?)?)
+}
+
+/// List references of the remote repository `url` using
+/// [git-ls-remote(1)](https://git-scm.com/docs/git-ls-remote)
+///
+/// # Errors
+///
+/// See [`LsRemoteError`]
+#[inline]
+pub fn ls_remote(url: &str, options: &LsRemoteOptions) -> Result<Vec<RemoteRef>, LsRemoteError> {
+    let cmd = GitCommand::new(Arc::new(ProcessRunner::default()));
+    list_remote_refs(cmd, url, options)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ls_remote, resolve_head, setup_test_author, tags_from_remote, GenericRepository,
+        LsRemoteError, LsRemoteOptions, RemoteRef, Repository,
+    };
+    use tempfile::TempDir;
+
+    #[test]
+    fn parse() {
+        let refs = super::parse_remote_refs(
+            "ref: refs/heads/main\tHEAD\n\
+             1111\tHEAD\n\
+             1111\trefs/heads/main\n\
+             2222\trefs/tags/a/b\n\
+             1111\trefs/tags/a/b^{}\n",
+        )
+        .unwrap();
+        assert_eq!(refs.len(), 3);
+        assert_eq!(refs[0].symref_target.as_deref(), Some("refs/heads/main"));
+        assert_eq!(
+            refs[2],
+            RemoteRef {
+                id: "2222".to_owned(),
+                name: "refs/tags/a/b".to_owned(),
+                peeled: Some("1111".to_owned()),
+                symref_target: None,
+            }
+        );
+        assert!(super::parse_remote_refs("garbage\n").is_err());
+    }
+
+    #[test]
+    fn local() {
+        setup_test_author();
+        let tmp_dir = TempDir::new().unwrap();
+        let repo = Repository::create(tmp_dir.path()).expect("Created repository");
+        let url = tmp_dir.path().to_str().unwrap();
+        assert_eq!(
+            resolve_head(url),
+            Err(LsRemoteError::NoHead(url.to_owned()))
+        );
+
+        repo.commit_extended("First", true, true).unwrap();
+        repo.create_tag("release/v1", None, Some("Release"))
+            .unwrap();
+        let branch = repo.current_branch().unwrap().unwrap();
+        assert_eq!(resolve_head(url).unwrap(), branch);
+        assert_eq!(tags_from_remote(url).unwrap(), vec!["release/v1"]);
+
+        let refs = ls_remote(url, &LsRemoteOptions::default().tags(true)).unwrap();
+        assert_eq!(refs.len(), 1);
+        assert_eq!(refs[0].peeled, Some(repo.head().unwrap()));
+        let refs = repo
+            .ls_remote(url, &LsRemoteOptions::default().pattern("HEAD"))
+            .unwrap();
+        assert_eq!(refs[0].name, "HEAD");
+        assert_eq!(
+            refs[0].symref_target,
+            Some(format!("refs/heads/{}", branch))
+        );
+    }
+}
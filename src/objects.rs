@@ -0,0 +1,391 @@
+use crate::{GitCommand, GitError};
+use posix_errors::{PosixError, EINVAL, ENOENT};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, ChildStdout};
+
+/// Failed to read an object
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum ObjectReadError {
+    /// Object does not exist
+    #[error("Object `{0}` does not exist")]
+    Missing(String),
+    /// Short object id or name matches multiple objects
+    #[error("Object name `{0}` is ambiguous")]
+    Ambiguous(String),
+    /// Object exists, but is of a different type
+    #[error("Object `{name}` is a {actual}, expected a {expected}")]
+    UnexpectedType {
+        #[allow(missing_docs)]
+        name: String,
+        #[allow(missing_docs)]
+        expected: ObjectType,
+        #[allow(missing_docs)]
+        actual: ObjectType,
+    },
+    #[allow(missing_docs)]
+    #[error(transparent)]
+    Failure(#[from] GitError),
+}
+
+impl From<ObjectReadError> for GitError {
+    #[inline]
+    fn from(err: ObjectReadError) -> Self {
+        match err {
+            ObjectReadError::Failure(e) => e,
+            ObjectReadError::Missing(_)
+            | ObjectReadError::Ambiguous(_)
+            | ObjectReadError::UnexpectedType { .. } => Self::Invalid(format!("{}", err)),
+        }
+    }
+}
+
+impl From<ObjectReadError> for PosixError {
+    #[inline]
+    fn from(err: ObjectReadError) -> Self {
+        match err {
+            ObjectReadError::Failure(e) => e.into(),
+            ObjectReadError::Missing(_) => Self::new(ENOENT, format!("{}", err)),
+            ObjectReadError::Ambiguous(_) | ObjectReadError::UnexpectedType { .. } => {
+                Self::new(EINVAL, format!("{}", err))
+            }
+        }
+    }
+}
+
+/// Type of a git object
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ObjectType {
+    Blob,
+    Tree,
+    Commit,
+    Tag,
+}
+
+impl std::fmt::Display for ObjectType {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Blob => "blob",
+            Self::Tree => "tree",
+            Self::Commit => "commit",
+            Self::Tag => "tag",
+        };
+        f.write_str(name)
+    }
+}
+
+impl std::str::FromStr for ObjectType {
+    type Err = GitError;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "blob" => Ok(Self::Blob),
+            "tree" => Ok(Self::Tree),
+            "commit" => Ok(Self::Commit),
+            "tag" => Ok(Self::Tag),
+            _ => Err(GitError::Parse(format!("Unknown object type `{}`", s))),
+        }
+    }
+}
+
+/// An entry of a tree object
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TreeEntry {
+    /// File mode, i.e. `100644` or `40000`
+    pub mode: String,
+    /// Object type derived from the mode, submodules are [`ObjectType::Commit`]
+    pub kind: ObjectType,
+    /// Object id
+    pub id: String,
+    /// File name, not the full path
+    pub name: String,
+}
+
+/// Header line of the batch output
+struct Header {
+    id: String,
+    kind: ObjectType,
+    size: usize,
+}
+
+struct BatchProcess {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl BatchProcess {
+    fn start(mut cmd: GitCommand, mode: &str) -> Result<Self, GitError> {
+        let mut child = cmd.args(["cat-file", mode]).spawn()?;
+        let missing = || GitError::Spawn(std::io::Error::other("git cat-file without pipes"));
+        let stdin = child.stdin.take().ok_or_else(missing)?;
+        let stdout = child.stdout.take().ok_or_else(missing)?;
+        Ok(Self {
+            child,
+            stdin: Some(stdin),
+            stdout: BufReader::new(stdout),
+        })
+    }
+
+    /// Send a query and parse the header line of the answer
+    fn query(&mut self, name: &str) -> Result<Header, ObjectReadError> {
+        if name.contains('\n') {
+            return Err(ObjectReadError::Missing(name.to_owned()));
+        }
+        let stdin = self
+            .stdin
+            .as_mut()
+            .ok_or_else(|| GitError::Invalid("git cat-file already closed".to_owned()))?;
+        writeln!(stdin, "{}", name)
+            .and_then(|()| stdin.flush())
+            .map_err(GitError::Spawn)?;
+
+        let mut line = String::new();
+        self.stdout.read_line(&mut line).map_err(GitError::Spawn)?;
+        if line.is_empty() {
+            return Err(GitError::Parse("git cat-file exited unexpectedly".to_owned()).into());
+        }
+        let line = line.trim_end_matches('\n');
+        if let Some(object) = line.strip_suffix(" missing") {
+            return Err(ObjectReadError::Missing(object.to_owned()));
+        }
+        if let Some(object) = line.strip_suffix(" ambiguous") {
+            return Err(ObjectReadError::Ambiguous(object.to_owned()));
+        }
+        let parse_error = || GitError::Parse(format!("Unexpected git cat-file header `{}`", line));
+        let mut split = line.split(' ');
+        match (split.next(), split.next(), split.next(), split.next()) {
+            (Some(id), Some(kind), Some(size), None) => Ok(Header {
+                id: id.to_owned(),
+                kind: kind.parse()?,
+                size: size.parse().map_err(|_| parse_error())?,
+            }),
+            _ => Err(parse_error().into()),
+        }
+    }
+
+    /// Read the object content following a header including the trailing newline
+    fn content(&mut self, header: &Header) -> Result<Vec<u8>, GitError> {
+        let mut buf = vec![0; header.size + 1];
+        self.stdout.read_exact(&mut buf).map_err(GitError::Spawn)?;
+        buf.pop();
+        Ok(buf)
+    }
+}
+
+impl Drop for BatchProcess {
+    fn drop(&mut self) {
+        // Closing STDIN makes git-cat-file(1) exit
+        drop(self.stdin.take());
+        if self.child.wait().is_err() {
+            let _ = self.child.kill();
+        }
+    }
+}
+
+/// Reads objects through long-lived
+/// [git-cat-file(1)](https://git-scm.com/docs/git-cat-file) `--batch` and `--batch-check`
+/// processes.
+///
+/// The processes are started on first use and stopped when the reader is dropped.
+pub struct ObjectReader {
+    cmd: GitCommand,
+    batch: Option<BatchProcess>,
+    check: Option<BatchProcess>,
+}
+
+impl std::fmt::Debug for ObjectReader {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ObjectReader")
+            .field("cmd", &self.cmd)
+            .field("batch", &self.batch.is_some())
+            .field("check", &self.check.is_some())
+            .finish()
+    }
+}
+
+impl ObjectReader {
+    pub(crate) fn new(cmd: GitCommand) -> Self {
+        Self {
+            cmd,
+            batch: None,
+            check: None,
+        }
+    }
+
+    fn batch(&mut self) -> Result<&mut BatchProcess, GitError> {
+        if self.batch.is_none() {
+            self.batch = Some(BatchProcess::start(self.cmd.clone(), "--batch")?);
+        }
+        self.batch
+            .as_mut()
+            .ok_or_else(|| GitError::Invalid("git cat-file not running".to_owned()))
+    }
+
+    fn check(&mut self) -> Result<&mut BatchProcess, GitError> {
+        if self.check.is_none() {
+            self.check = Some(BatchProcess::start(self.cmd.clone(), "--batch-check")?);
+        }
+        self.check
+            .as_mut()
+            .ok_or_else(|| GitError::Invalid("git cat-file not running".to_owned()))
+    }
+
+    /// Read an object of the expected type, returning its id & content
+    fn read(
+        &mut self,
+        name: &str,
+        expected: ObjectType,
+    ) -> Result<(String, Vec<u8>), ObjectReadError> {
+        let process = self.batch()?;
+        let header = process.query(name)?;
+        let content = process.content(&header)?;
+        if header.kind != expected {
+            return Err(ObjectReadError::UnexpectedType {
+                name: name.to_owned(),
+                expected,
+                actual: header.kind,
+            });
+        }
+        Ok((header.id, content))
+    }
+
+    /// Read the content of the file at `path` in revision `rev`
+    ///
+    /// # Errors
+    ///
+    /// See [`ObjectReadError`]
+    #[inline]
+    pub fn read_blob(&mut self, rev: &str, path: &Path) -> Result<Vec<u8>, ObjectReadError> {
+        let name = format!("{}:{}", rev, path.to_string_lossy());
+        Ok(self.read(&name, ObjectType::Blob)?.1)
+    }
+
+    /// Return the type of the object `name`, i.e. `HEAD` or `HEAD:README.md`
+    ///
+    /// # Errors
+    ///
+    /// See [`ObjectReadError`]
+    #[inline]
+    pub fn object_type(&mut self, name: &str) -> Result<ObjectType, ObjectReadError> {
+        Ok(self.check()?.query(name)?.kind)
+    }
+
+    /// Return the size in bytes of the object `name`
+    ///
+    /// # Errors
+    ///
+    /// See [`ObjectReadError`]
+    #[inline]
+    pub fn object_size(&mut self, name: &str) -> Result<usize, ObjectReadError> {
+        Ok(self.check()?.query(name)?.size)
+    }
+
+    /// Return the entries of the tree `rev`, i.e. `HEAD` or `HEAD:src`
+    ///
+    /// # Errors
+    ///
+    /// See [`ObjectReadError`]
+    #[inline]
+    pub fn read_tree(&mut self, rev: &str) -> Result<Vec<TreeEntry>, ObjectReadError> {
+        let name = if self.object_type(rev)? == ObjectType::Tree {
+            rev.to_owned()
+        } else {
+            format!("{}^{{tree}}", rev)
+        };
+        let (id, content) = self.read(&name, ObjectType::Tree)?;
+        Ok(parse_tree(&content, id.len() / 2)?)
+    }
+}
+
+/// Parse binary tree object content, entries are `<mode> <name>\0<raw id>`
+fn parse_tree(mut content: &[u8], id_len: usize) -> Result<Vec<TreeEntry>, GitError> {
+    let parse_error = || GitError::Parse("Malformed tree object".to_owned());
+    let mut result = vec![];
+    while !content.is_empty() {
+        let space = content
+            .iter()
+            .position(|b| *b == b' ')
+            .ok_or_else(parse_error)?;
+        let nul = content
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or_else(parse_error)?;
+        if nul < space || content.len() < nul + 1 + id_len {
+            return Err(parse_error());
+        }
+        let mode = String::from_utf8_lossy(&content[..space]).to_string();
+        let name = String::from_utf8_lossy(&content[space + 1..nul]).to_string();
+        let id: String = content[nul + 1..nul + 1 + id_len]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        let kind = match mode.as_str() {
+            "40000" => ObjectType::Tree,
+            "160000" => ObjectType::Commit,
+            _ => ObjectType::Blob,
+        };
+        result.push(TreeEntry {
+            mode,
+            kind,
+            id,
+            name,
+        });
+        content = &content[nul + 1 + id_len..];
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{setup_test_author, GenericRepository, ObjectReadError, ObjectType, Repository};
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    #[test]
+    fn read() {
+        setup_test_author();
+        let tmp_dir = TempDir::new().unwrap();
+        let repo = Repository::create(tmp_dir.path()).expect("Created repository");
+        std::fs::create_dir(tmp_dir.path().join("src")).unwrap();
+        for (file, content) in [("README.md", "# README\n"), ("src/lib.rs", "")] {
+            let path = tmp_dir.path().join(file);
+            std::fs::write(&path, content).unwrap();
+            repo.stage(&path).unwrap();
+        }
+        repo.commit("Initial").unwrap();
+
+        let mut reader = repo.object_reader();
+        for _ in 0..3 {
+            let content = reader.read_blob("HEAD", Path::new("README.md")).unwrap();
+            assert_eq!(content, b"# README\n");
+        }
+        let empty = reader.read_blob("HEAD", Path::new("src/lib.rs")).unwrap();
+        assert!(empty.is_empty());
+        assert_eq!(reader.object_type("HEAD").unwrap(), ObjectType::Commit);
+        assert_eq!(reader.object_size("HEAD:README.md").unwrap(), 9);
+
+        let tree = reader.read_tree("HEAD").unwrap();
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree[0].name, "README.md");
+        assert_eq!(tree[1].name, "src");
+        assert_eq!(tree[1].kind, ObjectType::Tree);
+        assert_eq!(tree[1].id.len(), 40);
+        let sub_tree = reader.read_tree("HEAD:src").unwrap();
+        assert_eq!(sub_tree[0].name, "lib.rs");
+
+        let missing = reader.read_blob("HEAD", Path::new("nope"));
+        assert!(matches!(missing, Err(ObjectReadError::Missing(_))));
+        let not_blob = reader.read_blob("HEAD", Path::new("src"));
+        assert!(matches!(
+            not_blob,
+            Err(ObjectReadError::UnexpectedType { .. })
+        ));
+        // The process is still usable after errors
+        assert!(reader.read_blob("HEAD", Path::new("README.md")).is_ok());
+    }
+}
@@ -0,0 +1,93 @@
+/// Callback receiving [`Progress`] updates of long running commands
+pub type ProgressCallback<'a> = Box<dyn FnMut(&Progress) + 'a>;
+
+/// A progress update printed by git on `STDERR` when running with `--progress`
+///
+/// Parsed from lines like `Receiving objects:  45% (450/1000), 1.20 MiB | 1.00 MiB/s` or
+/// `remote: Enumerating objects: 5, done.`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Progress {
+    /// Name of the phase, i.e. `Receiving objects`
+    pub phase: String,
+    /// Reported by the remote side
+    pub remote: bool,
+    /// Processed items
+    pub current: u64,
+    /// Total items, if known
+    pub total: Option<u64>,
+    /// Phase is finished
+    pub done: bool,
+}
+
+impl Progress {
+    /// Parse a single `STDERR` line, returns `None` for lines which are not progress updates
+    #[must_use]
+    #[inline]
+    pub fn parse(line: &str) -> Option<Self> {
+        let (remote, line) = match line.strip_prefix("remote: ") {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let (phase, rest) = line.split_once(": ")?;
+        let rest = rest.trim_start();
+        let done = rest.contains(", done");
+        let (current, total) = match rest.split_once("% (") {
+            Some((_, counts)) => {
+                let (counts, _) = counts.split_once(')')?;
+                let (current, total) = counts.split_once('/')?;
+                (current.parse().ok()?, Some(total.parse().ok()?))
+            }
+            None => {
+                let end = rest
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len());
+                (rest[..end].parse().ok()?, None)
+            }
+        };
+        Some(Self {
+            phase: phase.to_owned(),
+            remote,
+            current,
+            total,
+            done,
+        })
+    }
+
+    /// Pass the progress update contained in the git `STDERR` line to `callback`
+    pub(crate) fn notify(callback: &mut Option<ProgressCallback<'_>>, line: &str) {
+        if let (Some(callback), Some(progress)) = (callback.as_mut(), Self::parse(line)) {
+            callback(&progress);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Progress;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            Progress::parse("Receiving objects:  45% (450/1000), 1.20 MiB | 1.00 MiB/s"),
+            Some(Progress {
+                phase: "Receiving objects".to_owned(),
+                remote: false,
+                current: 450,
+                total: Some(1000),
+                done: false,
+            })
+        );
+        assert_eq!(
+            Progress::parse("remote: Enumerating objects: 5, done."),
+            Some(Progress {
+                phase: "Enumerating objects".to_owned(),
+                remote: true,
+                current: 5,
+                total: None,
+                done: true,
+            })
+        );
+        assert_eq!(Progress::parse("From ../origin"), None);
+        assert_eq!(Progress::parse("fatal: no such remote"), None);
+    }
+}
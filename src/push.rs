@@ -0,0 +1,295 @@
+use crate::{GitCommand, GitError};
+
+/// Why the remote refused a reference update, see [`PushStatus::Rejected`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RejectReason {
+    /// Remote reference is not an ancestor of the pushed commit
+    NonFastForward,
+    /// Remote reference contains commits not known locally
+    FetchFirst,
+    /// Remote reference does not match the expected value of `--force-with-lease`
+    StaleInfo,
+    /// Remote reference already exists, i.e. a tag
+    AlreadyExists,
+    /// An other `--atomic` update failed
+    AtomicPushFailed,
+    #[allow(missing_docs)]
+    Other(String),
+}
+
+impl From<&str> for RejectReason {
+    #[inline]
+    fn from(value: &str) -> Self {
+        match value {
+            "non-fast-forward" => Self::NonFastForward,
+            "fetch first" => Self::FetchFirst,
+            "stale info" => Self::StaleInfo,
+            "already exists" => Self::AlreadyExists,
+            "atomic push failed" => Self::AtomicPushFailed,
+            _ => Self::Other(value.to_owned()),
+        }
+    }
+}
+
+/// Outcome of pushing a single reference
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PushStatus {
+    /// `' '` fast-forward update
+    FastForward,
+    /// `'+'` forced update
+    Forced,
+    /// `'-'` reference was deleted
+    Deleted,
+    /// `'*'` new reference
+    New,
+    /// `'='` reference is up to date
+    UpToDate,
+    /// `'!'` update was refused by git before sending it
+    Rejected(RejectReason),
+    /// `'!'` update was refused by the remote, i.e. by a hook
+    RemoteRejected(String),
+}
+
+/// A reference update reported by [`crate::GenericRepository::push`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PushedRef {
+    /// Local reference, `None` for deletions
+    pub source: Option<String>,
+    /// Full name of the remote reference
+    pub destination: String,
+    /// Summary printed by git, i.e. `[new branch]` or `d86eaa6...d71137d`
+    pub summary: String,
+    #[allow(missing_docs)]
+    pub status: PushStatus,
+}
+
+impl PushedRef {
+    /// Returns `true` if the reference was not updated
+    #[must_use]
+    #[inline]
+    pub const fn is_rejected(&self) -> bool {
+        matches!(
+            self.status,
+            PushStatus::Rejected(_) | PushStatus::RemoteRejected(_)
+        )
+    }
+}
+
+/// Options for [`crate::GenericRepository::push`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PushOptions {
+    force_with_lease: Vec<(String, Option<String>)>,
+    atomic: bool,
+    push_options: Vec<String>,
+    tags: bool,
+    dry_run: bool,
+}
+
+impl PushOptions {
+    /// Force the update of remote `reference` only if it points to `expected`. Without an
+    /// expected value, the remote-tracking branch of `reference` is used. May be repeated.
+    #[must_use]
+    #[inline]
+    pub fn force_with_lease(mut self, reference: &str, expected: Option<&str>) -> Self {
+        self.force_with_lease
+            .push((reference.to_owned(), expected.map(ToOwned::to_owned)));
+        self
+    }
+
+    /// Update either all references or none
+    #[must_use]
+    #[inline]
+    pub const fn atomic(mut self, atomic: bool) -> Self {
+        self.atomic = atomic;
+        self
+    }
+
+    /// Transmit the string to the server hooks, see `--push-option`; may be repeated
+    #[must_use]
+    #[inline]
+    pub fn push_option(mut self, option: &str) -> Self {
+        self.push_options.push(option.to_owned());
+        self
+    }
+
+    /// Push all tags in addition to the refspecs
+    #[must_use]
+    #[inline]
+    pub const fn tags(mut self, tags: bool) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// Do everything except actually sending the updates
+    #[must_use]
+    #[inline]
+    pub const fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+}
+
+fn parse_status(flag: &str, summary: &str) -> Result<PushStatus, GitError> {
+    let reason = || {
+        summary
+            .rsplit_once(" (")
+            .and_then(|(_, reason)| reason.strip_suffix(')'))
+            .unwrap_or_default()
+    };
+    Ok(match flag {
+        " " => PushStatus::FastForward,
+        "+" => PushStatus::Forced,
+        "-" => PushStatus::Deleted,
+        "*" => PushStatus::New,
+        "=" => PushStatus::UpToDate,
+        "!" if summary.starts_with("[remote rejected]") => {
+            PushStatus::RemoteRejected(reason().to_owned())
+        }
+        "!" => PushStatus::Rejected(reason().into()),
+        _ => return Err(GitError::Parse(format!("Unknown push flag `{}`", flag))),
+    })
+}
+
+/// Parse `git push --porcelain` output, lines are `<flag>\t<from>:<to>\t<summary> (<reason>)`
+fn parse_pushed(text: &str) -> Result<Vec<PushedRef>, GitError> {
+    let mut result = vec![];
+    for line in text.lines().filter(|l| l.contains('\t')) {
+        let parse_error = || GitError::Parse(format!("Unexpected git-push(1) line `{}`", line));
+        let mut fields = line.splitn(3, '\t');
+        let (flag, refs, summary) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(flag), Some(refs), Some(summary)) => (flag, refs, summary),
+            _ => return Err(parse_error()),
+        };
+        let (source, destination) = refs.rsplit_once(':').ok_or_else(parse_error)?;
+        result.push(PushedRef {
+            source: (!source.is_empty()).then(|| source.to_owned()),
+            destination: destination.to_owned(),
+            summary: summary.to_owned(),
+            status: parse_status(flag, summary)?,
+        });
+    }
+    Ok(result)
+}
+
+/// Push references, see [`crate::GenericRepository::push`]
+pub(crate) fn push(
+    mut cmd: GitCommand,
+    remote: &str,
+    refspecs: &[&str],
+    options: &PushOptions,
+) -> Result<Vec<PushedRef>, GitError> {
+    cmd.args(["push", "--porcelain"]);
+    for (reference, expected) in &options.force_with_lease {
+        match expected {
+            Some(expected) => cmd.arg(format!("--force-with-lease={}:{}", reference, expected)),
+            None => cmd.arg(format!("--force-with-lease={}", reference)),
+        };
+    }
+    if options.atomic {
+        cmd.arg("--atomic");
+    }
+    for option in &options.push_options {
+        cmd.arg(format!("--push-option={}", option));
+    }
+    if options.tags {
+        cmd.arg("--tags");
+    }
+    if options.dry_run {
+        cmd.arg("--dry-run");
+    }
+    cmd.arg("--").arg(remote).args(refspecs);
+
+    let out = cmd.output()?;
+    let pushed = parse_pushed(&String::from_utf8(out.stdout.clone())?)?;
+    if out.status.success() || pushed.iter().any(PushedRef::is_rejected) {
+        Ok(pushed)
+    } else {
+        Err(GitError::from_output(&cmd, &out))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        setup_test_author, BareRepository, GenericRepository, PushOptions, PushStatus,
+        RejectReason, Repository,
+    };
+    use tempfile::TempDir;
+
+    #[test]
+    fn push() {
+        setup_test_author();
+        let tmp_dir = TempDir::new().unwrap();
+        let origin_dir = tmp_dir.path().join("origin.git");
+        let work_dir = tmp_dir.path().join("work");
+        std::fs::create_dir_all(&origin_dir).unwrap();
+        std::fs::create_dir_all(&work_dir).unwrap();
+        BareRepository::create(&origin_dir).expect("Created bare repository");
+        let repo = Repository::create(&work_dir).expect("Created repository");
+        repo.git()
+            .args(["remote", "add", "origin"])
+            .arg(&origin_dir)
+            .run()
+            .unwrap();
+        repo.commit_extended("First", true, true).unwrap();
+        let first = repo.head().unwrap();
+        let options = PushOptions::default();
+        let main = "HEAD:refs/heads/main";
+
+        let dry = repo
+            .push("origin", &[main], &options.clone().dry_run(true))
+            .unwrap();
+        assert_eq!(dry[0].status, PushStatus::New);
+        let pushed = repo.push("origin", &[main], &options).unwrap();
+        assert_eq!(pushed[0].status, PushStatus::New);
+        assert_eq!(pushed[0].source.as_deref(), Some("HEAD"));
+        assert_eq!(pushed[0].destination, "refs/heads/main");
+        let pushed = repo.push("origin", &[main], &options).unwrap();
+        assert_eq!(pushed[0].status, PushStatus::UpToDate);
+
+        repo.git()
+            .args(["commit", "--amend", "--allow-empty", "-m", "Amended"])
+            .run()
+            .unwrap();
+        let pushed = repo.push("origin", &[main], &options).unwrap();
+        assert_eq!(
+            pushed[0].status,
+            PushStatus::Rejected(RejectReason::NonFastForward)
+        );
+        assert!(pushed[0].is_rejected());
+
+        let stale = options
+            .clone()
+            .force_with_lease("refs/heads/main", Some(&repo.head().unwrap()));
+        let pushed = repo.push("origin", &[main], &stale).unwrap();
+        assert_eq!(
+            pushed[0].status,
+            PushStatus::Rejected(RejectReason::StaleInfo)
+        );
+        let lease = options
+            .clone()
+            .force_with_lease("refs/heads/main", Some(&first));
+        let pushed = repo.push("origin", &[main], &lease).unwrap();
+        assert_eq!(pushed[0].status, PushStatus::Forced);
+
+        let hook = origin_dir.join("hooks").join("pre-receive");
+        std::fs::write(&hook, "#!/bin/sh\nexit 1\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&hook, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        let pushed = repo
+            .push("origin", &["HEAD:refs/heads/other"], &options)
+            .unwrap();
+        assert_eq!(
+            pushed[0].status,
+            PushStatus::RemoteRejected("pre-receive hook declined".to_owned())
+        );
+
+        let err = repo
+            .push("does-not-exist", &[main], &options)
+            .expect_err("Expected failure");
+        assert!(err.code().is_some());
+    }
+}
@@ -0,0 +1,307 @@
+use crate::{GitCommand, GitError};
+
+/// Fields requested from [git-for-each-ref(1)](https://git-scm.com/docs/git-for-each-ref)
+const FORMAT: &str = "%(refname)%00%(objectname)%00%(*objectname)%00%(symref)%00%(upstream)%00%(upstream:track,nobracket)%00%(committerdate:unix)%00%(*committerdate:unix)";
+const FIELDS: usize = 8;
+
+/// Kind of a [`Reference`] derived from its name
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RefKind {
+    /// `refs/heads/*`
+    Branch,
+    /// `refs/remotes/*`
+    RemoteBranch,
+    /// `refs/tags/*`
+    Tag,
+    /// `refs/notes/*`
+    Note,
+    /// `refs/stash`
+    Stash,
+    Other,
+}
+
+impl RefKind {
+    fn from_name(name: &str) -> Self {
+        if name.starts_with("refs/heads/") {
+            Self::Branch
+        } else if name.starts_with("refs/remotes/") {
+            Self::RemoteBranch
+        } else if name.starts_with("refs/tags/") {
+            Self::Tag
+        } else if name.starts_with("refs/notes/") {
+            Self::Note
+        } else if name == "refs/stash" {
+            Self::Stash
+        } else {
+            Self::Other
+        }
+    }
+}
+
+/// Tracking state of a branch relative to its upstream
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AheadBehind {
+    /// Branch & upstream point to the same commit
+    InSync,
+    /// The branch has the given number of commits which are not in upstream
+    Ahead(u32),
+    /// Upstream has the given number of commits which are not in the branch
+    Behind(u32),
+    /// Both the branch & upstream have new commits
+    Diverged {
+        /// Commits only in the branch
+        ahead: u32,
+        /// Commits only in upstream
+        behind: u32,
+    },
+    /// Upstream is configured, but does not exist anymore
+    Gone,
+}
+
+/// A reference as returned by [`crate::GenericRepository::refs`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reference {
+    /// Full name, i.e. `refs/heads/main`
+    pub name: String,
+    #[allow(missing_docs)]
+    pub kind: RefKind,
+    /// Object id the reference points to
+    pub target: String,
+    /// Object the target peels to, for annotated tags
+    pub peeled_target: Option<String>,
+    /// Referenced name for symbolic references, i.e. `refs/remotes/origin/HEAD`
+    pub symref_target: Option<String>,
+    /// Full name of the upstream reference
+    pub upstream: Option<String>,
+    /// Tracking state, if an upstream is set
+    pub ahead_behind: Option<AheadBehind>,
+    /// Committer date as seconds since the UNIX epoch of the (peeled) commit
+    pub committer_date: Option<i64>,
+}
+
+impl Reference {
+    /// Name without the `refs/heads/`, `refs/remotes/` or `refs/tags/` prefix
+    #[must_use]
+    #[inline]
+    pub fn short_name(&self) -> &str {
+        ["refs/heads/", "refs/remotes/", "refs/tags/"]
+            .iter()
+            .find_map(|prefix| self.name.strip_prefix(prefix))
+            .unwrap_or(&self.name)
+    }
+
+    /// The commit this reference eventually points to
+    #[must_use]
+    #[inline]
+    pub fn commit(&self) -> &str {
+        self.peeled_target.as_deref().unwrap_or(&self.target)
+    }
+}
+
+/// Options for [`crate::GenericRepository::refs`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RefOptions {
+    sort: Vec<String>,
+    contains: Option<String>,
+    merged: Option<String>,
+    no_merged: Option<String>,
+}
+
+impl RefOptions {
+    /// Sort by the given key, i.e. `refname` or `-committerdate`; may be repeated
+    #[must_use]
+    #[inline]
+    pub fn sort(mut self, key: &str) -> Self {
+        self.sort.push(key.to_owned());
+        self
+    }
+
+    /// Only references which contain the given commit
+    #[must_use]
+    #[inline]
+    pub fn contains(mut self, commit: &str) -> Self {
+        self.contains = Some(commit.to_owned());
+        self
+    }
+
+    /// Only references reachable from the given commit
+    #[must_use]
+    #[inline]
+    pub fn merged(mut self, commit: &str) -> Self {
+        self.merged = Some(commit.to_owned());
+        self
+    }
+
+    /// Only references not reachable from the given commit
+    #[must_use]
+    #[inline]
+    pub fn no_merged(mut self, commit: &str) -> Self {
+        self.no_merged = Some(commit.to_owned());
+        self
+    }
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    (!value.is_empty()).then(|| value.to_owned())
+}
+
+fn parse_track(upstream: &str, value: &str) -> Result<Option<AheadBehind>, GitError> {
+    if upstream.is_empty() {
+        return Ok(None);
+    }
+    if value.is_empty() {
+        return Ok(Some(AheadBehind::InSync));
+    }
+    if value == "gone" {
+        return Ok(Some(AheadBehind::Gone));
+    }
+    let (mut ahead, mut behind) = (0, 0);
+    for part in value.split(", ") {
+        let parse_error = || GitError::Parse(format!("Unexpected tracking info `{}`", value));
+        match part.split_once(' ') {
+            Some(("ahead", n)) => ahead = n.parse().map_err(|_| parse_error())?,
+            Some(("behind", n)) => behind = n.parse().map_err(|_| parse_error())?,
+            _ => return Err(parse_error()),
+        }
+    }
+    Ok(Some(match (ahead, behind) {
+        (0, 0) => AheadBehind::InSync,
+        (ahead, 0) => AheadBehind::Ahead(ahead),
+        (0, behind) => AheadBehind::Behind(behind),
+        (ahead, behind) => AheadBehind::Diverged { ahead, behind },
+    }))
+}
+
+fn parse_refs(text: &str) -> Result<Vec<Reference>, GitError> {
+    let mut result = vec![];
+    for line in text.lines().filter(|l| !l.is_empty()) {
+        let f: Vec<&str> = line.split('\0').collect();
+        if f.len() != FIELDS {
+            return Err(GitError::Parse(format!(
+                "Unexpected git-for-each-ref(1) line `{}`",
+                line
+            )));
+        }
+        let date = non_empty(f[7]).or_else(|| non_empty(f[6]));
+        result.push(Reference {
+            name: f[0].to_owned(),
+            kind: RefKind::from_name(f[0]),
+            target: f[1].to_owned(),
+            peeled_target: non_empty(f[2]),
+            symref_target: non_empty(f[3]),
+            upstream: non_empty(f[4]),
+            ahead_behind: parse_track(f[4], f[5])?,
+            committer_date: date.and_then(|d| d.parse().ok()),
+        });
+    }
+    Ok(result)
+}
+
+pub(crate) fn list_refs(
+    mut cmd: GitCommand,
+    patterns: &[&str],
+    options: &RefOptions,
+) -> Result<Vec<Reference>, GitError> {
+    cmd.arg("for-each-ref").arg(format!("--format={}", FORMAT));
+    for key in &options.sort {
+        cmd.arg(format!("--sort={}", key));
+    }
+    if let Some(commit) = &options.contains {
+        cmd.arg(format!("--contains={}", commit));
+    }
+    if let Some(commit) = &options.merged {
+        cmd.arg(format!("--merged={}", commit));
+    }
+    if let Some(commit) = &options.no_merged {
+        cmd.arg(format!("--no-merged={}", commit));
+    }
+    cmd.arg("--").args(patterns);
+    parse_refs(&cmd.read()?)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        setup_test_author, AheadBehind, BareRepository, GenericRepository, RefKind, RefOptions,
+        Repository,
+    };
+    use tempfile::TempDir;
+
+    #[test]
+    fn refs() {
+        setup_test_author();
+        let tmp_dir = TempDir::new().unwrap();
+        let repo = Repository::create(tmp_dir.path()).expect("Created repository");
+        repo.commit_extended("First", true, true).unwrap();
+        let first = repo.head().unwrap();
+        repo.git().args(["tag", "-m", "Tag", "v1"]).run().unwrap();
+        repo.git().args(["branch", "old"]).run().unwrap();
+        repo.git()
+            .args(["branch", "--set-upstream-to=old"])
+            .run()
+            .unwrap();
+        repo.commit_extended("Second", true, true).unwrap();
+
+        let all = repo.refs(&[], &RefOptions::default()).unwrap();
+        assert_eq!(all.len(), 3);
+
+        let tags = repo.refs(&["refs/tags"], &RefOptions::default()).unwrap();
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[0].kind, RefKind::Tag);
+        assert_eq!(tags[0].short_name(), "v1");
+        assert_eq!(tags[0].peeled_target, Some(first.clone()));
+        assert_ne!(tags[0].target, first);
+        assert!(tags[0].committer_date.is_some());
+
+        let branches = repo
+            .refs(&["refs/heads"], &RefOptions::default().sort("-refname"))
+            .unwrap();
+        assert_eq!(branches[0].short_name(), "old");
+        let current = &branches[1];
+        assert_eq!(current.upstream, Some("refs/heads/old".to_owned()));
+        assert_eq!(current.ahead_behind, Some(AheadBehind::Ahead(1)));
+
+        let no_merged = repo
+            .refs(&["refs/heads"], &RefOptions::default().no_merged("old"))
+            .unwrap();
+        assert_eq!(no_merged.len(), 1);
+        assert_eq!(no_merged[0].target, repo.head().unwrap());
+
+        repo.git()
+            .args(["branch", "--track", "synced", "old"])
+            .run()
+            .unwrap();
+        let synced = repo
+            .refs(&["refs/heads/synced"], &RefOptions::default())
+            .unwrap();
+        assert_eq!(synced[0].ahead_behind, Some(AheadBehind::InSync));
+    }
+
+    #[test]
+    fn bare() {
+        let tmp_dir = TempDir::new().unwrap();
+        let repo = BareRepository::create(tmp_dir.path()).expect("Created bare repository");
+        assert!(repo.refs(&[], &RefOptions::default()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn track() {
+        assert_eq!(super::parse_track("", "").unwrap(), None);
+        assert_eq!(
+            super::parse_track("refs/heads/main", "gone").unwrap(),
+            Some(AheadBehind::Gone)
+        );
+        assert_eq!(
+            super::parse_track("refs/heads/main", "ahead 2, behind 3").unwrap(),
+            Some(AheadBehind::Diverged {
+                ahead: 2,
+                behind: 3
+            })
+        );
+        assert_eq!(
+            super::parse_track("refs/heads/main", "behind 1").unwrap(),
+            Some(AheadBehind::Behind(1))
+        );
+    }
+}
@@ -0,0 +1,341 @@
+use crate::refs::list_refs;
+use crate::{GitCommand, GitError, RefOptions};
+use posix_errors::{PosixError, EEXIST, EINVAL, ENOENT};
+use std::collections::HashMap;
+
+/// Failed to manage a remote
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum RemoteError {
+    /// A remote with this name already exists
+    #[error("Remote `{0}` already exists")]
+    RemoteExists(String),
+    /// Remote or remote branch does not exist
+    #[error("Remote or remote branch `{0}` not found")]
+    NotFound(String),
+    /// Name is not a valid remote name
+    #[error("`{0}` is not a valid remote name")]
+    InvalidName(String),
+    #[allow(missing_docs)]
+    #[error(transparent)]
+    Failure(#[from] GitError),
+}
+
+impl From<RemoteError> for GitError {
+    #[inline]
+    fn from(err: RemoteError) -> Self {
+        match err {
+            RemoteError::Failure(e) => e,
+            RemoteError::RemoteExists(_)
+            | RemoteError::NotFound(_)
+            | RemoteError::InvalidName(_) => Self::Invalid(format!("{}", err)),
+        }
+    }
+}
+
+impl From<RemoteError> for PosixError {
+    #[inline]
+    fn from(err: RemoteError) -> Self {
+        match err {
+            RemoteError::Failure(e) => e.into(),
+            RemoteError::RemoteExists(_) => Self::new(EEXIST, format!("{}", err)),
+            RemoteError::NotFound(_) => Self::new(ENOENT, format!("{}", err)),
+            RemoteError::InvalidName(_) => Self::new(EINVAL, format!("{}", err)),
+        }
+    }
+}
+
+/// Which tags are fetched from a remote, see `remote.<name>.tagOpt`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TagOpt {
+    /// `--tags` fetch all tags
+    All,
+    /// `--no-tags` fetch no tags
+    None,
+}
+
+/// Represents a git remote as configured
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Remote {
+    #[allow(missing_docs)]
+    pub name: String,
+    /// URLs used for pushing, defaults to the fetch URL
+    pub push: Vec<String>,
+    /// URL used for fetching
+    pub fetch: Option<String>,
+    /// Configured fetch refspecs, i.e. `+refs/heads/*:refs/remotes/origin/*`
+    pub fetch_refspecs: Vec<String>,
+    #[allow(missing_docs)]
+    pub tag_opt: Option<TagOpt>,
+    /// Default branch of the remote as recorded in `refs/remotes/<name>/HEAD`
+    pub head: Option<String>,
+}
+
+/// Parse `git config -z --get-regexp` output, entries are `<key>\n<value>\0`
+fn parse_remotes(text: &str) -> Result<HashMap<String, Remote>, GitError> {
+    let mut result: HashMap<String, Remote> = HashMap::new();
+    for entry in text.split('\0').filter(|e| !e.is_empty()) {
+        let (key, value) = entry.split_once('\n').unwrap_or((entry, ""));
+        let (name, var) = key
+            .strip_prefix("remote.")
+            .and_then(|rest| rest.rsplit_once('.'))
+            .ok_or_else(|| GitError::Parse(format!("Unexpected remote config `{}`", key)))?;
+        let remote = result.entry(name.to_owned()).or_insert_with(|| Remote {
+            name: name.to_owned(),
+            ..Remote::default()
+        });
+        match var {
+            "url" if remote.fetch.is_none() => remote.fetch = Some(value.to_owned()),
+            "pushurl" => remote.push.push(value.to_owned()),
+            "fetch" => remote.fetch_refspecs.push(value.to_owned()),
+            "tagopt" => {
+                remote.tag_opt = match value {
+                    "--tags" => Some(TagOpt::All),
+                    "--no-tags" => Some(TagOpt::None),
+                    _ => None,
+                }
+            }
+            _ => {}
+        }
+    }
+    for remote in result.values_mut() {
+        if remote.push.is_empty() {
+            remote.push.extend(remote.fetch.clone());
+        }
+    }
+    Ok(result)
+}
+
+/// Run a git-remote(1) command, translating well known failures into [`RemoteError`]
+fn run_remote(mut cmd: GitCommand, name: &str) -> Result<String, RemoteError> {
+    let out = cmd.output()?;
+    match out.status.code() {
+        Some(0) => Ok(String::from_utf8(out.stdout).map_err(GitError::from)?),
+        Some(2) => Err(RemoteError::NotFound(name.to_owned())),
+        Some(3) => Err(RemoteError::RemoteExists(name.to_owned())),
+        _ if String::from_utf8_lossy(&out.stderr).contains("is not a valid remote name") => {
+            Err(RemoteError::InvalidName(name.to_owned()))
+        }
+        _ => Err(GitError::from_output(&cmd, &out).into()),
+    }
+}
+
+/// See [`crate::GenericRepository::remotes`]
+pub(crate) fn remotes(cmd: GitCommand) -> Result<HashMap<String, Remote>, GitError> {
+    let mut config = cmd.clone();
+    // Only keys with a subsection, i.e. not `remote.pushDefault`
+    config.args(["config", "-z", "--get-regexp", r"^remote\..+\."]);
+    let out = config.output()?;
+    let mut result = match out.status.code() {
+        Some(0) => parse_remotes(&String::from_utf8(out.stdout)?)?,
+        // No remote configured
+        Some(1) => return Ok(HashMap::new()),
+        _ => return Err(GitError::from_output(&config, &out)),
+    };
+
+    let heads = list_refs(cmd, &["refs/remotes"], &RefOptions::default())?;
+    for reference in heads {
+        let name = reference
+            .name
+            .strip_prefix("refs/remotes/")
+            .and_then(|n| n.strip_suffix("/HEAD"));
+        if let (Some(remote), Some(target)) = (
+            name.and_then(|n| result.get_mut(n)),
+            reference.symref_target,
+        ) {
+            let prefix = format!("refs/remotes/{}/", remote.name);
+            remote.head = target.strip_prefix(&prefix).map(ToOwned::to_owned);
+        }
+    }
+    Ok(result)
+}
+
+fn ensure_remote(mut cmd: GitCommand, name: &str) -> Result<(), RemoteError> {
+    cmd.args(["remote", "get-url", "--", name]);
+    run_remote(cmd, name).map(|_| ())
+}
+
+/// See [`crate::GenericRepository::add_remote`]
+pub(crate) fn add_remote(mut cmd: GitCommand, name: &str, url: &str) -> Result<(), RemoteError> {
+    cmd.args(["remote", "add", "--", name, url]);
+    run_remote(cmd, name).map(|_| ())
+}
+
+/// See [`crate::GenericRepository::remove_remote`]
+pub(crate) fn remove_remote(mut cmd: GitCommand, name: &str) -> Result<(), RemoteError> {
+    cmd.args(["remote", "remove", "--", name]);
+    run_remote(cmd, name).map(|_| ())
+}
+
+/// See [`crate::GenericRepository::rename_remote`]
+pub(crate) fn rename_remote(mut cmd: GitCommand, old: &str, new: &str) -> Result<(), RemoteError> {
+    ensure_remote(cmd.clone(), old)?;
+    cmd.args(["remote", "rename", "--", old, new]);
+    run_remote(cmd, new).map(|_| ())
+}
+
+/// See [`crate::GenericRepository::set_url`]
+pub(crate) fn set_url(mut cmd: GitCommand, name: &str, url: &str) -> Result<(), RemoteError> {
+    cmd.args(["remote", "set-url", "--", name, url]);
+    run_remote(cmd, name).map(|_| ())
+}
+
+/// See [`crate::GenericRepository::set_push_urls`]
+pub(crate) fn set_push_urls(cmd: GitCommand, name: &str, urls: &[&str]) -> Result<(), RemoteError> {
+    ensure_remote(cmd.clone(), name)?;
+    let key = format!("remote.{}.pushurl", name);
+    let mut unset = cmd.clone();
+    unset.args(["config", "--unset-all", &key]);
+    let out = unset.output()?;
+    // Exit code 5 means there was no push URL
+    if !out.status.success() && out.status.code() != Some(5) {
+        return Err(GitError::from_output(&unset, &out).into());
+    }
+    for url in urls {
+        cmd.clone().args(["config", "--add", &key, url]).run()?;
+    }
+    Ok(())
+}
+
+/// See [`crate::GenericRepository::remote_refspecs`]
+pub(crate) fn remote_refspecs(mut cmd: GitCommand, name: &str) -> Result<Vec<String>, RemoteError> {
+    ensure_remote(cmd.clone(), name)?;
+    cmd.args(["config", "--get-all"])
+        .arg(format!("remote.{}.fetch", name));
+    let out = cmd.output()?;
+    match out.status.code() {
+        Some(0) => Ok(String::from_utf8(out.stdout)
+            .map_err(GitError::from)?
+            .lines()
+            .map(ToOwned::to_owned)
+            .collect()),
+        Some(1) => Ok(vec![]),
+        _ => Err(GitError::from_output(&cmd, &out).into()),
+    }
+}
+
+/// See [`crate::GenericRepository::set_remote_head`]
+pub(crate) fn set_remote_head(
+    mut cmd: GitCommand,
+    name: &str,
+    branch: Option<&str>,
+) -> Result<(), RemoteError> {
+    ensure_remote(cmd.clone(), name)?;
+    cmd.args(["remote", "set-head", name]);
+    match branch {
+        Some(branch) => cmd.arg(branch),
+        None => cmd.arg("--auto"),
+    };
+    match run_remote(cmd, name) {
+        Err(RemoteError::Failure(e)) if e.code() == Some(1) => Err(RemoteError::NotFound(format!(
+            "{}/{}",
+            name,
+            branch.unwrap_or("HEAD")
+        ))),
+        result => result.map(|_| ()),
+    }
+}
+
+/// See [`crate::GenericRepository::prune_remote`]
+pub(crate) fn prune_remote(mut cmd: GitCommand, name: &str) -> Result<Vec<String>, RemoteError> {
+    ensure_remote(cmd.clone(), name)?;
+    cmd.args(["remote", "prune", name]);
+    let stdout = run_remote(cmd, name)?;
+    Ok(stdout
+        .lines()
+        .filter_map(|line| line.split_once("[pruned] "))
+        .map(|(_, branch)| branch.trim().to_owned())
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        setup_test_author, BareRepository, GenericRepository, RemoteError, Repository, TagOpt,
+    };
+    use tempfile::TempDir;
+
+    #[test]
+    fn manage() {
+        setup_test_author();
+        let tmp_dir = TempDir::new().unwrap();
+        let origin_dir = tmp_dir.path().join("origin.git");
+        let work_dir = tmp_dir.path().join("work");
+        std::fs::create_dir_all(&origin_dir).unwrap();
+        std::fs::create_dir_all(&work_dir).unwrap();
+        let origin = BareRepository::create(&origin_dir).expect("Created bare repository");
+        let repo = Repository::create(&work_dir).expect("Created repository");
+        repo.git()
+            .args(["config", "remote.pushDefault", "origin"])
+            .run()
+            .unwrap();
+        assert!(repo.remotes().unwrap().is_empty());
+
+        let url = origin_dir.to_str().unwrap();
+        repo.add_remote("upstream", url).unwrap();
+        assert_eq!(
+            repo.add_remote("upstream", url),
+            Err(RemoteError::RemoteExists("upstream".to_owned()))
+        );
+        assert_eq!(
+            repo.add_remote("in valid", url),
+            Err(RemoteError::InvalidName("in valid".to_owned()))
+        );
+        repo.rename_remote("upstream", "origin").unwrap();
+        assert_eq!(
+            repo.rename_remote("upstream", "other"),
+            Err(RemoteError::NotFound("upstream".to_owned()))
+        );
+        repo.set_push_urls("origin", &["push-a", "push-b"]).unwrap();
+        repo.git()
+            .args(["config", "remote.origin.tagOpt", "--no-tags"])
+            .run()
+            .unwrap();
+
+        repo.commit_extended("First", true, true).unwrap();
+        repo.git()
+            .args([
+                "push",
+                "-q",
+                "--",
+                url,
+                "HEAD:refs/heads/main",
+                "HEAD:refs/heads/old",
+            ])
+            .run()
+            .unwrap();
+        repo.git().args(["fetch", "-q", "origin"]).run().unwrap();
+        repo.set_remote_head("origin", Some("main")).unwrap();
+        assert_eq!(
+            repo.set_remote_head("origin", Some("nope")),
+            Err(RemoteError::NotFound("origin/nope".to_owned()))
+        );
+
+        let remotes = repo.remotes().unwrap();
+        let remote = &remotes["origin"];
+        assert_eq!(remote.fetch.as_deref(), Some(url));
+        assert_eq!(remote.push, vec!["push-a", "push-b"]);
+        assert_eq!(
+            remote.fetch_refspecs,
+            vec!["+refs/heads/*:refs/remotes/origin/*"]
+        );
+        assert_eq!(remote.tag_opt, Some(TagOpt::None));
+        assert_eq!(remote.head.as_deref(), Some("main"));
+        assert_eq!(
+            repo.remote_refspecs("origin").unwrap(),
+            remote.fetch_refspecs
+        );
+
+        origin.git().args(["branch", "-D", "old"]).run().unwrap();
+        assert_eq!(repo.prune_remote("origin").unwrap(), vec!["origin/old"]);
+
+        repo.set_push_urls("origin", &[]).unwrap();
+        repo.set_url("origin", "elsewhere").unwrap();
+        let remote = &repo.remotes().unwrap()["origin"];
+        assert_eq!(remote.push, vec!["elsewhere"]);
+        repo.remove_remote("origin").unwrap();
+        assert_eq!(
+            repo.remove_remote("origin"),
+            Err(RemoteError::NotFound("origin".to_owned()))
+        );
+    }
+}
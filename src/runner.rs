@@ -0,0 +1,690 @@
+use crate::GitError;
+use std::collections::VecDeque;
+use std::ffi::{OsStr, OsString};
+use std::fmt::Debug;
+use std::io::{BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::sync::{Arc, Mutex};
+
+/// Executes prepared [`GitCommand`]s.
+///
+/// All git invocations of this crate are routed through a runner. The default is
+/// [`ProcessRunner`], which spawns a git process. Use [`RecordingRunner`] to capture the exact
+/// invocations and [`ReplayRunner`] to serve recorded outputs without a git binary.
+pub trait GitRunner: Debug + Send + Sync {
+    /// Run the command to completion and capture `STDOUT` & `STDERR`
+    ///
+    /// # Errors
+    ///
+    /// When the command could not be executed
+    fn output(&self, cmd: &GitCommand) -> std::io::Result<Output>;
+
+    /// Run the command to completion with `STDOUT` & `STDERR` inherited from the current process
+    ///
+    /// The default implementation captures the output via [`GitRunner::output`].
+    ///
+    /// # Errors
+    ///
+    /// When the command could not be executed
+    #[inline]
+    fn status(&self, cmd: &GitCommand) -> std::io::Result<ExitStatus> {
+        self.output(cmd).map(|out| out.status)
+    }
+
+    /// Run the command to completion, passing every `STDERR` line to `progress`
+    ///
+    /// Lines are split on `\r` & `\n`, so each update of a git progress meter is passed
+    /// separately. The returned output still contains the complete `STDERR`. The default
+    /// implementation passes the lines after the command finished.
+    ///
+    /// # Errors
+    ///
+    /// When the command could not be executed
+    #[inline]
+    fn output_with_progress(
+        &self,
+        cmd: &GitCommand,
+        progress: &mut dyn FnMut(&str),
+    ) -> std::io::Result<Output> {
+        let out = self.output(cmd)?;
+        out.stderr
+            .split(|b| *b == b'\r' || *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .for_each(|line| progress(&String::from_utf8_lossy(line)));
+        Ok(out)
+    }
+
+    /// Start a long running process with piped `STDIN`, `STDOUT` & `STDERR`
+    ///
+    /// Used for interactive commands like `git cat-file --batch`. The default implementation
+    /// returns [`std::io::ErrorKind::Unsupported`].
+    ///
+    /// # Errors
+    ///
+    /// When the command could not be executed
+    #[inline]
+    fn spawn(&self, cmd: &GitCommand) -> std::io::Result<Child> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            format!("{:?} can not spawn {:?}", self, cmd.args),
+        ))
+    }
+}
+
+/// Runs git as a child process
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProcessRunner {
+    program: PathBuf,
+}
+
+impl ProcessRunner {
+    /// Use the given git binary instead of the one found in `PATH`
+    #[must_use]
+    #[inline]
+    pub fn new(program: impl Into<PathBuf>) -> Self {
+        Self {
+            program: program.into(),
+        }
+    }
+
+    /// The git binary used by this runner
+    #[must_use]
+    #[inline]
+    pub fn program(&self) -> &Path {
+        &self.program
+    }
+
+    fn command(&self, cmd: &GitCommand) -> Command {
+        let mut result = Command::new(&self.program);
+        result.args(&cmd.args);
+        for (key, value) in &cmd.envs {
+            result.env(key, value);
+        }
+        if let Some(dir) = &cmd.current_dir {
+            result.current_dir(dir);
+        }
+        result
+    }
+}
+
+impl Default for ProcessRunner {
+    #[inline]
+    fn default() -> Self {
+        Self::new("git")
+    }
+}
+
+impl GitRunner for ProcessRunner {
+    #[inline]
+    fn output(&self, cmd: &GitCommand) -> std::io::Result<Output> {
+        let mut command = self.command(cmd);
+        match &cmd.stdin {
+            None => command.stdin(Stdio::null()).output(),
+            Some(input) => {
+                command
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped());
+                let mut child = command.spawn()?;
+                let mut stdin = child.stdin.take().expect("Piped STDIN");
+                let input = input.clone();
+                // Write from a separate thread, so a full STDOUT pipe can not dead lock us
+                let writer = std::thread::spawn(move || stdin.write_all(&input));
+                let out = child.wait_with_output()?;
+                writer
+                    .join()
+                    .map_err(|_| std::io::Error::other("STDIN writer panicked"))??;
+                Ok(out)
+            }
+        }
+    }
+
+    #[inline]
+    fn status(&self, cmd: &GitCommand) -> std::io::Result<ExitStatus> {
+        if cmd.stdin.is_some() {
+            return self.output(cmd).map(|out| out.status);
+        }
+        self.command(cmd).status()
+    }
+
+    #[inline]
+    fn output_with_progress(
+        &self,
+        cmd: &GitCommand,
+        progress: &mut dyn FnMut(&str),
+    ) -> std::io::Result<Output> {
+        let mut command = self.command(cmd);
+        command
+            .stdin(if cmd.stdin.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let mut child = command.spawn()?;
+        let writer = cmd.stdin.clone().map(|input| {
+            let mut stdin = child.stdin.take().expect("Piped STDIN");
+            std::thread::spawn(move || stdin.write_all(&input))
+        });
+        let mut stdout = child.stdout.take().expect("Piped STDOUT");
+        // Drain STDOUT in a separate thread while STDERR is streamed to the callback
+        let reader = std::thread::spawn(move || {
+            let mut buf = vec![];
+            stdout.read_to_end(&mut buf).map(|_| buf)
+        });
+
+        let mut stderr = vec![];
+        let mut line = vec![];
+        for byte in BufReader::new(child.stderr.take().expect("Piped STDERR")).bytes() {
+            let byte = byte?;
+            stderr.push(byte);
+            if byte == b'\r' || byte == b'\n' {
+                if !line.is_empty() {
+                    progress(&String::from_utf8_lossy(&line));
+                    line.clear();
+                }
+            } else {
+                line.push(byte);
+            }
+        }
+        if !line.is_empty() {
+            progress(&String::from_utf8_lossy(&line));
+        }
+
+        let status = child.wait()?;
+        let stdout = reader
+            .join()
+            .map_err(|_| std::io::Error::other("STDOUT reader panicked"))??;
+        if let Some(writer) = writer {
+            writer
+                .join()
+                .map_err(|_| std::io::Error::other("STDIN writer panicked"))??;
+        }
+        Ok(Output {
+            status,
+            stdout,
+            stderr,
+        })
+    }
+
+    #[inline]
+    fn spawn(&self, cmd: &GitCommand) -> std::io::Result<Child> {
+        self.command(cmd)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+    }
+}
+
+/// A single recorded git invocation together with its result
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Invocation {
+    /// Arguments passed to git
+    pub args: Vec<String>,
+    /// Environment variables set for git
+    pub envs: Vec<(String, String)>,
+    /// Working directory of git
+    pub current_dir: Option<PathBuf>,
+    /// Data passed on `STDIN`
+    pub stdin: Option<Vec<u8>>,
+    /// Exit code
+    pub code: i32,
+    /// Captured `STDOUT`
+    pub stdout: Vec<u8>,
+    /// Captured `STDERR`
+    pub stderr: Vec<u8>,
+}
+
+impl Invocation {
+    /// A successful invocation of git with the given arguments printing `stdout`
+    #[must_use]
+    #[inline]
+    pub fn new<I, S>(args: I, stdout: &str) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            args: args.into_iter().map(Into::into).collect(),
+            stdout: stdout.as_bytes().to_vec(),
+            ..Self::default()
+        }
+    }
+
+    /// Set the exit code and `STDERR` of the invocation
+    #[must_use]
+    #[inline]
+    pub fn failing(mut self, code: i32, stderr: &str) -> Self {
+        self.code = code;
+        self.stderr = stderr.as_bytes().to_vec();
+        self
+    }
+
+    fn from_command(cmd: &GitCommand) -> Self {
+        let lossy = |s: &OsString| s.to_string_lossy().to_string();
+        Self {
+            args: cmd.args.iter().map(lossy).collect(),
+            envs: cmd.envs.iter().map(|(k, v)| (lossy(k), lossy(v))).collect(),
+            current_dir: cmd.current_dir.clone(),
+            stdin: cmd.stdin.clone(),
+            ..Self::default()
+        }
+    }
+
+    fn to_output(&self) -> Output {
+        Output {
+            status: exit_status(self.code),
+            stdout: self.stdout.clone(),
+            stderr: self.stderr.clone(),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn exit_status(code: i32) -> ExitStatus {
+    use std::os::unix::process::ExitStatusExt;
+    ExitStatus::from_raw((code & 0xff) << 8)
+}
+
+#[cfg(windows)]
+fn exit_status(code: i32) -> ExitStatus {
+    use std::os::windows::process::ExitStatusExt;
+    #[allow(clippy::cast_sign_loss)]
+    ExitStatus::from_raw(code as u32)
+}
+
+/// Records every invocation executed by the wrapped runner
+#[derive(Debug)]
+pub struct RecordingRunner {
+    inner: Arc<dyn GitRunner>,
+    log: Mutex<Vec<Invocation>>,
+}
+
+impl RecordingRunner {
+    /// Record invocations executed by `inner`
+    #[must_use]
+    #[inline]
+    pub fn new(inner: Arc<dyn GitRunner>) -> Self {
+        Self {
+            inner,
+            log: Mutex::new(vec![]),
+        }
+    }
+
+    /// Return all invocations recorded so far
+    ///
+    /// # Panics
+    ///
+    /// When the log mutex is poisoned
+    #[must_use]
+    #[inline]
+    pub fn invocations(&self) -> Vec<Invocation> {
+        self.log.lock().expect("Unpoisoned log").clone()
+    }
+
+    fn record(&self, cmd: &GitCommand, out: &Output) {
+        let mut invocation = Invocation::from_command(cmd);
+        invocation.code = out.status.code().unwrap_or(1);
+        invocation.stdout.clone_from(&out.stdout);
+        invocation.stderr.clone_from(&out.stderr);
+        self.log.lock().expect("Unpoisoned log").push(invocation);
+    }
+}
+
+impl Default for RecordingRunner {
+    #[inline]
+    fn default() -> Self {
+        Self::new(Arc::new(ProcessRunner::default()))
+    }
+}
+
+impl GitRunner for RecordingRunner {
+    #[inline]
+    fn output(&self, cmd: &GitCommand) -> std::io::Result<Output> {
+        let out = self.inner.output(cmd)?;
+        self.record(cmd, &out);
+        Ok(out)
+    }
+
+    #[inline]
+    fn output_with_progress(
+        &self,
+        cmd: &GitCommand,
+        progress: &mut dyn FnMut(&str),
+    ) -> std::io::Result<Output> {
+        let out = self.inner.output_with_progress(cmd, progress)?;
+        self.record(cmd, &out);
+        Ok(out)
+    }
+
+    /// Records only the invocation, the output of the spawned process is not captured.
+    #[inline]
+    fn spawn(&self, cmd: &GitCommand) -> std::io::Result<Child> {
+        let child = self.inner.spawn(cmd)?;
+        let invocation = Invocation::from_command(cmd);
+        self.log.lock().expect("Unpoisoned log").push(invocation);
+        Ok(child)
+    }
+}
+
+/// Serves previously recorded invocations in order without executing git
+///
+/// Each executed command must have the same arguments as the next expected invocation,
+/// otherwise an [`std::io::ErrorKind::InvalidInput`] error is returned.
+#[derive(Debug, Default)]
+pub struct ReplayRunner {
+    expected: Mutex<VecDeque<Invocation>>,
+}
+
+impl ReplayRunner {
+    /// Replay the given invocations in order
+    #[must_use]
+    #[inline]
+    pub fn new(invocations: impl IntoIterator<Item = Invocation>) -> Self {
+        Self {
+            expected: Mutex::new(invocations.into_iter().collect()),
+        }
+    }
+
+    /// Number of invocations not yet replayed
+    ///
+    /// # Panics
+    ///
+    /// When the queue mutex is poisoned
+    #[must_use]
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.expected.lock().expect("Unpoisoned queue").len()
+    }
+}
+
+impl GitRunner for ReplayRunner {
+    #[inline]
+    fn output(&self, cmd: &GitCommand) -> std::io::Result<Output> {
+        let actual = Invocation::from_command(cmd);
+        let mut queue = self.expected.lock().expect("Unpoisoned queue");
+        match queue.pop_front() {
+            Some(expected) if expected.args == actual.args => Ok(expected.to_output()),
+            Some(expected) => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Expected git {:?}, got {:?}", expected.args, actual.args),
+            )),
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Unexpected git {:?}", actual.args),
+            )),
+        }
+    }
+}
+
+/// A prepared git invocation, executed through a [`GitRunner`]
+///
+/// The builder methods mirror [`std::process::Command`].
+#[derive(Clone, Debug)]
+pub struct GitCommand {
+    runner: Arc<dyn GitRunner>,
+    args: Vec<OsString>,
+    envs: Vec<(OsString, OsString)>,
+    current_dir: Option<PathBuf>,
+    stdin: Option<Vec<u8>>,
+}
+
+impl GitCommand {
+    /// Prepare an invocation executed by `runner`
+    #[must_use]
+    #[inline]
+    pub fn new(runner: Arc<dyn GitRunner>) -> Self {
+        Self {
+            runner,
+            args: vec![],
+            envs: vec![],
+            current_dir: None,
+            stdin: None,
+        }
+    }
+
+    /// Add an argument
+    #[inline]
+    pub fn arg<S: AsRef<OsStr>>(&mut self, arg: S) -> &mut Self {
+        self.args.push(arg.as_ref().to_os_string());
+        self
+    }
+
+    /// Add multiple arguments
+    #[inline]
+    pub fn args<I, S>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        for arg in args {
+            self.arg(arg);
+        }
+        self
+    }
+
+    /// Set an environment variable
+    #[inline]
+    pub fn env<K: AsRef<OsStr>, V: AsRef<OsStr>>(&mut self, key: K, value: V) -> &mut Self {
+        let key = key.as_ref().to_os_string();
+        self.envs.retain(|(k, _)| *k != key);
+        self.envs.push((key, value.as_ref().to_os_string()));
+        self
+    }
+
+    /// Set the working directory
+    #[inline]
+    pub fn current_dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Self {
+        self.current_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Pass the given data on `STDIN`
+    #[inline]
+    pub fn stdin(&mut self, input: impl Into<Vec<u8>>) -> &mut Self {
+        self.stdin = Some(input.into());
+        self
+    }
+
+    /// Arguments passed to git
+    #[inline]
+    pub fn get_args(&self) -> impl Iterator<Item = &OsStr> {
+        self.args.iter().map(OsString::as_os_str)
+    }
+
+    /// Environment variables set for git
+    #[inline]
+    pub fn get_envs(&self) -> impl Iterator<Item = (&OsStr, &OsStr)> {
+        self.envs
+            .iter()
+            .map(|(k, v)| (k.as_os_str(), v.as_os_str()))
+    }
+
+    /// Working directory of git
+    #[must_use]
+    #[inline]
+    pub fn get_current_dir(&self) -> Option<&Path> {
+        self.current_dir.as_deref()
+    }
+
+    /// Data passed on `STDIN`
+    #[must_use]
+    #[inline]
+    pub fn get_stdin(&self) -> Option<&[u8]> {
+        self.stdin.as_deref()
+    }
+
+    /// The runner executing this command
+    #[must_use]
+    #[inline]
+    pub fn runner(&self) -> &Arc<dyn GitRunner> {
+        &self.runner
+    }
+
+    /// Execute and return the output, regardless of the exit code
+    ///
+    /// # Errors
+    ///
+    /// Returns [`GitError::Spawn`] when git could not be executed
+    #[inline]
+    pub fn output(&mut self) -> Result<Output, GitError> {
+        self.runner.output(self).map_err(GitError::Spawn)
+    }
+
+    /// Execute and return the output if git exited successfully
+    ///
+    /// # Errors
+    ///
+    /// See [`GitError`]
+    #[inline]
+    pub fn run(&mut self) -> Result<Output, GitError> {
+        let out = self.output()?;
+        if out.status.success() {
+            Ok(out)
+        } else {
+            Err(GitError::from_output(self, &out))
+        }
+    }
+
+    /// Execute and return `STDOUT` if git exited successfully
+    ///
+    /// # Errors
+    ///
+    /// See [`GitError`]
+    #[inline]
+    pub fn read(&mut self) -> Result<String, GitError> {
+        let out = self.run()?;
+        Ok(String::from_utf8(out.stdout)?)
+    }
+
+    /// Execute and return the output regardless of the exit code, passing every `STDERR` line
+    /// to `progress` as soon as it is printed
+    ///
+    /// # Errors
+    ///
+    /// Returns [`GitError::Spawn`] when git could not be executed
+    #[inline]
+    pub fn output_with_progress(
+        &mut self,
+        progress: &mut dyn FnMut(&str),
+    ) -> Result<Output, GitError> {
+        self.runner
+            .output_with_progress(self, progress)
+            .map_err(GitError::Spawn)
+    }
+
+    /// Execute and return the output if git exited successfully, passing every `STDERR` line
+    /// to `progress` as soon as it is printed
+    ///
+    /// # Errors
+    ///
+    /// See [`GitError`]
+    #[inline]
+    pub fn run_with_progress(
+        &mut self,
+        progress: &mut dyn FnMut(&str),
+    ) -> Result<Output, GitError> {
+        let out = self.output_with_progress(progress)?;
+        if out.status.success() {
+            Ok(out)
+        } else {
+            Err(GitError::from_output(self, &out))
+        }
+    }
+
+    /// Start a long running process with piped `STDIN`, `STDOUT` & `STDERR`
+    ///
+    /// # Errors
+    ///
+    /// Returns [`GitError::Spawn`] when git could not be executed
+    #[inline]
+    pub fn spawn(&mut self) -> Result<Child, GitError> {
+        self.runner.spawn(self).map_err(GitError::Spawn)
+    }
+
+    /// Execute with `STDOUT` & `STDERR` inherited from the current process
+    ///
+    /// # Errors
+    ///
+    /// Returns [`GitError::Spawn`] when git could not be executed
+    #[inline]
+    pub fn status(&mut self) -> Result<ExitStatus, GitError> {
+        self.runner.status(self).map_err(GitError::Spawn)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        GenericRepository, GitCommand, GitError, Invocation, ProcessRunner, RecordingRunner,
+        ReplayRunner, Repository,
+    };
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    #[test]
+    fn record() {
+        let tmp_dir = TempDir::new().unwrap();
+        let runner = Arc::new(RecordingRunner::default());
+        let repo = Repository::create(tmp_dir.path())
+            .unwrap()
+            .with_runner(runner.clone());
+        assert!(!repo.is_shallow().unwrap());
+        let invocations = runner.invocations();
+        assert_eq!(invocations.len(), 1);
+        assert_eq!(
+            invocations[0].args,
+            vec!["rev-parse", "--is-shallow-repository"]
+        );
+        assert_eq!(invocations[0].stdout, b"false\n");
+        assert!(invocations[0].envs.iter().any(|(k, _)| k == "GIT_DIR"));
+        assert!(invocations[0]
+            .envs
+            .iter()
+            .any(|(k, v)| k == "LC_ALL" && v == "C"));
+    }
+
+    #[test]
+    fn replay() {
+        let tmp_dir = TempDir::new().unwrap();
+        let runner = Arc::new(ReplayRunner::new([
+            Invocation::new(["rev-parse", "HEAD"], "1234\n"),
+            Invocation::new(["rev-parse", "HEAD"], "").failing(128, "fatal: bad HEAD"),
+        ]));
+        let repo = Repository::create(tmp_dir.path())
+            .unwrap()
+            .with_runner(runner.clone());
+        assert_eq!(repo.head().unwrap(), "1234");
+        let err = repo.head().expect_err("Expected failure");
+        assert_eq!(err.code(), Some(128));
+        assert_eq!(err.stderr(), Some("fatal: bad HEAD"));
+        assert_eq!(runner.remaining(), 0);
+        assert!(matches!(repo.head(), Err(GitError::Spawn(_))));
+    }
+
+    #[test]
+    fn progress() {
+        let runner = Arc::new(ProcessRunner::default());
+        let mut lines = vec![];
+        let out = GitCommand::new(runner)
+            .args(["-c", "alias.progress=!printf 'a\\rb\\nc' >&2", "progress"])
+            .run_with_progress(&mut |line| lines.push(line.to_owned()))
+            .unwrap();
+        assert_eq!(lines, vec!["a", "b", "c"]);
+        assert_eq!(out.stderr, b"a\rb\nc");
+
+        let runner = Arc::new(ReplayRunner::new([
+            Invocation::new(["fetch"], "").failing(1, "x\ry\n")
+        ]));
+        lines.clear();
+        let err = GitCommand::new(runner)
+            .arg("fetch")
+            .run_with_progress(&mut |line| lines.push(line.to_owned()))
+            .expect_err("Expected failure");
+        assert_eq!(err.code(), Some(1));
+        assert_eq!(lines, vec!["x", "y"]);
+    }
+}
@@ -0,0 +1,421 @@
+use crate::{GenericRepository, GitError, Repository};
+use std::path::PathBuf;
+
+/// State of a file in the index or work tree as reported by `XY` in
+/// [git-status(1)](https://git-scm.com/docs/git-status#_short_format)
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileState {
+    Unmodified,
+    Modified,
+    TypeChanged,
+    Added,
+    Deleted,
+    Renamed,
+    Copied,
+    UpdatedUnmerged,
+}
+
+impl TryFrom<char> for FileState {
+    type Error = GitError;
+
+    #[inline]
+    fn try_from(value: char) -> Result<Self, Self::Error> {
+        match value {
+            '.' => Ok(Self::Unmodified),
+            'M' => Ok(Self::Modified),
+            'T' => Ok(Self::TypeChanged),
+            'A' => Ok(Self::Added),
+            'D' => Ok(Self::Deleted),
+            'R' => Ok(Self::Renamed),
+            'C' => Ok(Self::Copied),
+            'U' => Ok(Self::UpdatedUnmerged),
+            c => Err(GitError::Parse(format!("Unknown file state `{}`", c))),
+        }
+    }
+}
+
+/// Whether an entry is renamed or copied
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenameKind {
+    Renamed,
+    Copied,
+}
+
+/// An entry of [`Status`]
+#[allow(missing_docs)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StatusEntry {
+    /// Changed tracked file
+    Ordinary {
+        index: FileState,
+        work_tree: FileState,
+        /// Submodule state, `N...` for non-submodules
+        submodule: String,
+        mode_head: String,
+        mode_index: String,
+        mode_work_tree: String,
+        id_head: String,
+        id_index: String,
+        path: PathBuf,
+    },
+    /// Renamed or copied tracked file
+    RenamedOrCopied {
+        index: FileState,
+        work_tree: FileState,
+        submodule: String,
+        mode_head: String,
+        mode_index: String,
+        mode_work_tree: String,
+        id_head: String,
+        id_index: String,
+        kind: RenameKind,
+        /// Similarity score in percent
+        score: u8,
+        path: PathBuf,
+        original_path: PathBuf,
+    },
+    /// File with merge conflicts
+    Unmerged {
+        index: FileState,
+        work_tree: FileState,
+        submodule: String,
+        mode_stage1: String,
+        mode_stage2: String,
+        mode_stage3: String,
+        mode_work_tree: String,
+        id_stage1: String,
+        id_stage2: String,
+        id_stage3: String,
+        path: PathBuf,
+    },
+    Untracked(PathBuf),
+    Ignored(PathBuf),
+}
+
+impl StatusEntry {
+    /// Path of the entry in the work tree
+    #[must_use]
+    #[inline]
+    pub const fn path(&self) -> &PathBuf {
+        match self {
+            Self::Ordinary { path, .. }
+            | Self::RenamedOrCopied { path, .. }
+            | Self::Unmerged { path, .. }
+            | Self::Untracked(path)
+            | Self::Ignored(path) => path,
+        }
+    }
+}
+
+/// Branch information from the `# branch.*` headers
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BranchInfo {
+    /// Current commit, `None` on an unborn branch
+    pub oid: Option<String>,
+    /// Current branch, `None` if `HEAD` is detached
+    pub head: Option<String>,
+    /// Upstream branch if set
+    pub upstream: Option<String>,
+    /// Commits ahead & behind of upstream, if upstream is set and exists
+    pub ahead_behind: Option<(u32, u32)>,
+}
+
+/// Work tree status as returned by [`Repository::status`]
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Status {
+    pub branch: BranchInfo,
+    pub entries: Vec<StatusEntry>,
+}
+
+impl Status {
+    /// Returns `true` if there are no entries, except ignored files.
+    #[must_use]
+    #[inline]
+    pub fn is_clean(&self) -> bool {
+        self.entries
+            .iter()
+            .all(|e| matches!(e, StatusEntry::Ignored(_)))
+    }
+}
+
+/// How to report untracked files, see `--untracked-files`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UntrackedFiles {
+    /// Do not show untracked files
+    No,
+    /// Show untracked files and directories
+    #[default]
+    Normal,
+    /// Also show individual files in untracked directories
+    All,
+}
+
+/// How to report ignored files, see `--ignored`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IgnoredFiles {
+    /// Do not show ignored files
+    #[default]
+    No,
+    /// Show ignored files and directories
+    Traditional,
+    /// Show ignored files and directories matching an ignore pattern
+    Matching,
+}
+
+/// Options for [`Repository::status`]
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StatusOptions {
+    pub untracked: UntrackedFiles,
+    pub ignored: IgnoredFiles,
+}
+
+impl StatusOptions {
+    fn to_args(self) -> [&'static str; 2] {
+        let untracked = match self.untracked {
+            UntrackedFiles::No => "--untracked-files=no",
+            UntrackedFiles::Normal => "--untracked-files=normal",
+            UntrackedFiles::All => "--untracked-files=all",
+        };
+        let ignored = match self.ignored {
+            IgnoredFiles::No => "--ignored=no",
+            IgnoredFiles::Traditional => "--ignored=traditional",
+            IgnoredFiles::Matching => "--ignored=matching",
+        };
+        [untracked, ignored]
+    }
+}
+
+fn parse_error(record: &str) -> GitError {
+    GitError::Parse(format!("Unexpected git-status(1) record: `{}`", record))
+}
+
+fn parse_xy(xy: &str) -> Result<(FileState, FileState), GitError> {
+    let mut chars = xy.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some(x), Some(y), None) => Ok((x.try_into()?, y.try_into()?)),
+        _ => Err(parse_error(xy)),
+    }
+}
+
+fn parse_header(branch: &mut BranchInfo, record: &str) -> Result<(), GitError> {
+    let header = record.trim_start_matches("# ");
+    let (key, value) = header.split_once(' ').ok_or_else(|| parse_error(record))?;
+    match key {
+        "branch.oid" => branch.oid = (value != "(initial)").then(|| value.to_owned()),
+        "branch.head" => branch.head = (value != "(detached)").then(|| value.to_owned()),
+        "branch.upstream" => branch.upstream = Some(value.to_owned()),
+        "branch.ab" => {
+            let (ahead, behind) = value.split_once(' ').ok_or_else(|| parse_error(record))?;
+            let ahead = ahead.trim_start_matches('+').parse();
+            let behind = behind.trim_start_matches('-').parse();
+            match (ahead, behind) {
+                (Ok(a), Ok(b)) => branch.ahead_behind = Some((a, b)),
+                _ => return Err(parse_error(record)),
+            }
+        }
+        // Ignore unknown headers, i.e. `# stash`
+        _ => {}
+    }
+    Ok(())
+}
+
+fn parse_status(text: &str) -> Result<Status, GitError> {
+    let mut result = Status::default();
+    let mut records = text.split('\0').filter(|r| !r.is_empty());
+    while let Some(record) = records.next() {
+        if record.starts_with("# ") {
+            parse_header(&mut result.branch, record)?;
+            continue;
+        }
+        let (kind, rest) = record
+            .split_at_checked(2)
+            .ok_or_else(|| parse_error(record))?;
+        let entry = match kind {
+            "1 " => {
+                let f: Vec<&str> = rest.splitn(8, ' ').collect();
+                if f.len() != 8 {
+                    return Err(parse_error(record));
+                }
+                let (index, work_tree) = parse_xy(f[0])?;
+                StatusEntry::Ordinary {
+                    index,
+                    work_tree,
+                    submodule: f[1].to_owned(),
+                    mode_head: f[2].to_owned(),
+                    mode_index: f[3].to_owned(),
+                    mode_work_tree: f[4].to_owned(),
+                    id_head: f[5].to_owned(),
+                    id_index: f[6].to_owned(),
+                    path: PathBuf::from(f[7]),
+                }
+            }
+            "2 " => {
+                let f: Vec<&str> = rest.splitn(9, ' ').collect();
+                if f.len() != 9 {
+                    return Err(parse_error(record));
+                }
+                let (index, work_tree) = parse_xy(f[0])?;
+                let (kind, score) = f[7]
+                    .split_at_checked(1)
+                    .ok_or_else(|| parse_error(record))?;
+                let kind = match kind {
+                    "R" => RenameKind::Renamed,
+                    "C" => RenameKind::Copied,
+                    _ => return Err(parse_error(record)),
+                };
+                let score = score.parse().map_err(|_| parse_error(record))?;
+                let original_path = records.next().ok_or_else(|| parse_error(record))?;
+                StatusEntry::RenamedOrCopied {
+                    index,
+                    work_tree,
+                    submodule: f[1].to_owned(),
+                    mode_head: f[2].to_owned(),
+                    mode_index: f[3].to_owned(),
+                    mode_work_tree: f[4].to_owned(),
+                    id_head: f[5].to_owned(),
+                    id_index: f[6].to_owned(),
+                    kind,
+                    score,
+                    path: PathBuf::from(f[8]),
+                    original_path: PathBuf::from(original_path),
+                }
+            }
+            "u " => {
+                let f: Vec<&str> = rest.splitn(10, ' ').collect();
+                if f.len() != 10 {
+                    return Err(parse_error(record));
+                }
+                let (index, work_tree) = parse_xy(f[0])?;
+                StatusEntry::Unmerged {
+                    index,
+                    work_tree,
+                    submodule: f[1].to_owned(),
+                    mode_stage1: f[2].to_owned(),
+                    mode_stage2: f[3].to_owned(),
+                    mode_stage3: f[4].to_owned(),
+                    mode_work_tree: f[5].to_owned(),
+                    id_stage1: f[6].to_owned(),
+                    id_stage2: f[7].to_owned(),
+                    id_stage3: f[8].to_owned(),
+                    path: PathBuf::from(f[9]),
+                }
+            }
+            "? " => StatusEntry::Untracked(PathBuf::from(rest)),
+            "! " => StatusEntry::Ignored(PathBuf::from(rest)),
+            _ => return Err(parse_error(record)),
+        };
+        result.entries.push(entry);
+    }
+    Ok(result)
+}
+
+/// Work tree status
+impl Repository {
+    /// Return the work tree status using
+    /// [git-status(1)](https://git-scm.com/docs/git-status) porcelain v2 format
+    ///
+    /// # Errors
+    ///
+    /// See [`GitError`]
+    #[inline]
+    pub fn status(&self, options: StatusOptions) -> Result<Status, GitError> {
+        let text = self
+            .git()
+            .args(["status", "--porcelain=v2", "-z", "--branch"])
+            .args(options.to_args())
+            .read()?;
+        parse_status(&text)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        setup_test_author, FileState, GenericRepository, IgnoredFiles, RenameKind, Repository,
+        StatusEntry, StatusOptions, UntrackedFiles,
+    };
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    #[test]
+    fn unborn() {
+        let tmp_dir = TempDir::new().unwrap();
+        let repo = Repository::create(tmp_dir.path()).expect("Created repository");
+        let status = repo.status(StatusOptions::default()).unwrap();
+        assert!(status.is_clean());
+        assert_eq!(status.branch.oid, None);
+        assert!(repo.is_clean().unwrap());
+    }
+
+    #[test]
+    fn entries() {
+        setup_test_author();
+        let tmp_dir = TempDir::new().unwrap();
+        let repo = Repository::create(tmp_dir.path()).expect("Created repository");
+        let readme = tmp_dir.path().join("README.md");
+        std::fs::write(&readme, "# README\n\nSome longer content\n").unwrap();
+        repo.stage(&readme).unwrap();
+        repo.commit("Test").unwrap();
+
+        repo.git()
+            .args(["mv", "README.md", "README"])
+            .run()
+            .unwrap();
+        std::fs::write(tmp_dir.path().join("new.txt"), "new").unwrap();
+        std::fs::write(tmp_dir.path().join(".gitignore"), "*.log\n").unwrap();
+        std::fs::write(tmp_dir.path().join("debug.log"), "").unwrap();
+
+        let status = repo.status(StatusOptions::default()).unwrap();
+        assert!(!status.is_clean());
+        assert_eq!(status.branch.oid, Some(repo.head().unwrap()));
+        assert!(status.branch.head.is_some());
+        assert!(status
+            .entries
+            .contains(&StatusEntry::Untracked(PathBuf::from("new.txt"))));
+        assert!(!status
+            .entries
+            .contains(&StatusEntry::Ignored(PathBuf::from("debug.log"))));
+        let renamed = status
+            .entries
+            .iter()
+            .find(|e| matches!(e, StatusEntry::RenamedOrCopied { .. }))
+            .expect("Renamed entry");
+        if let StatusEntry::RenamedOrCopied {
+            index,
+            kind,
+            score,
+            path,
+            original_path,
+            ..
+        } = renamed
+        {
+            assert_eq!(*index, FileState::Renamed);
+            assert_eq!(*kind, RenameKind::Renamed);
+            assert_eq!(*score, 100);
+            assert_eq!(path, &PathBuf::from("README"));
+            assert_eq!(original_path, &PathBuf::from("README.md"));
+        }
+
+        let options = StatusOptions {
+            untracked: UntrackedFiles::No,
+            ignored: IgnoredFiles::No,
+        };
+        let status = repo.status(options).unwrap();
+        assert!(!status
+            .entries
+            .contains(&StatusEntry::Untracked(PathBuf::from("new.txt"))));
+
+        let options = StatusOptions {
+            untracked: UntrackedFiles::All,
+            ignored: IgnoredFiles::Traditional,
+        };
+        let status = repo.status(options).unwrap();
+        assert!(status
+            .entries
+            .contains(&StatusEntry::Ignored(PathBuf::from("debug.log"))));
+    }
+}
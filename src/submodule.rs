@@ -0,0 +1,466 @@
+use crate::discover::dot_git;
+use crate::{AbsoluteDirPath, GenericRepository, GitCommand, GitError, RepoError, Repository};
+use posix_errors::{PosixError, EBUSY, EEXIST, ENOENT};
+use std::path::{Path, PathBuf};
+
+/// Failed to manage a submodule
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum SubmoduleError {
+    /// Path is already tracked or exists & is not a git repository
+    #[error("`{0}` already exists")]
+    AlreadyExists(PathBuf),
+    /// Path is not a submodule of this repository
+    #[error("Submodule `{0}` not found")]
+    NotFound(PathBuf),
+    /// Submodule work tree contains local modifications, use `force` to discard them
+    #[error("Submodule `{0}` contains local modifications")]
+    Dirty(PathBuf),
+    #[allow(missing_docs)]
+    #[error(transparent)]
+    Failure(#[from] GitError),
+}
+
+impl From<SubmoduleError> for GitError {
+    #[inline]
+    fn from(err: SubmoduleError) -> Self {
+        match err {
+            SubmoduleError::Failure(e) => e,
+            SubmoduleError::AlreadyExists(_)
+            | SubmoduleError::NotFound(_)
+            | SubmoduleError::Dirty(_) => Self::Invalid(format!("{}", err)),
+        }
+    }
+}
+
+impl From<SubmoduleError> for PosixError {
+    #[inline]
+    fn from(err: SubmoduleError) -> Self {
+        match err {
+            SubmoduleError::Failure(e) => e.into(),
+            SubmoduleError::AlreadyExists(_) => Self::new(EEXIST, format!("{}", err)),
+            SubmoduleError::NotFound(_) => Self::new(ENOENT, format!("{}", err)),
+            SubmoduleError::Dirty(_) => Self::new(EBUSY, format!("{}", err)),
+        }
+    }
+}
+
+/// A submodule as returned by [`Repository::submodules`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Submodule {
+    /// Name of the `submodule.<name>` section in `.gitmodules`
+    pub name: String,
+    /// Path relative to the work tree
+    pub path: PathBuf,
+    #[allow(missing_docs)]
+    pub url: Option<String>,
+    /// Branch followed by `submodule_update` with `remote`
+    pub branch: Option<String>,
+    /// Commit recorded in the index of the superproject
+    pub recorded: Option<String>,
+    /// Commit checked out in the submodule, `None` if it is not initialized
+    pub checked_out: Option<String>,
+    /// Submodule is initialized & checked out
+    pub initialized: bool,
+    /// Checked out commit differs from the recorded one
+    pub modified: bool,
+}
+
+/// Options for [`Repository::submodule_update`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SubmoduleUpdateOptions {
+    init: bool,
+    recursive: bool,
+    remote: bool,
+    force: bool,
+    paths: Vec<PathBuf>,
+}
+
+impl SubmoduleUpdateOptions {
+    /// Initialize submodules which are not initialized yet
+    #[must_use]
+    #[inline]
+    pub const fn init(mut self, init: bool) -> Self {
+        self.init = init;
+        self
+    }
+
+    /// Update nested submodules too
+    #[must_use]
+    #[inline]
+    pub const fn recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// Check out the tip of the remote tracking branch instead of the recorded commit
+    #[must_use]
+    #[inline]
+    pub const fn remote(mut self, remote: bool) -> Self {
+        self.remote = remote;
+        self
+    }
+
+    /// Discard local changes in the submodule work trees
+    #[must_use]
+    #[inline]
+    pub const fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Only update the submodule at `path`; may be repeated
+    #[must_use]
+    #[inline]
+    pub fn path(mut self, path: &Path) -> Self {
+        self.paths.push(path.to_path_buf());
+        self
+    }
+}
+
+/// Parse the submodule sections of `git config -z --get-regexp` output
+fn parse_gitmodules(output: &str) -> Vec<Submodule> {
+    let mut result: Vec<Submodule> = vec![];
+    for entry in output.split_terminator('\0') {
+        let (key, value) = entry.split_once('\n').unwrap_or((entry, ""));
+        let (name, variable) = match key
+            .strip_prefix("submodule.")
+            .and_then(|rest| rest.rsplit_once('.'))
+        {
+            Some(parts) => parts,
+            None => continue,
+        };
+        let index = if let Some(i) = result.iter().position(|s| s.name == name) {
+            i
+        } else {
+            result.push(Submodule {
+                name: name.to_owned(),
+                ..Submodule::default()
+            });
+            result.len() - 1
+        };
+        let submodule = &mut result[index];
+        match variable {
+            "path" => submodule.path = PathBuf::from(value),
+            "url" => submodule.url = Some(value.to_owned()),
+            "branch" => submodule.branch = Some(value.to_owned()),
+            _ => {}
+        }
+    }
+    result.retain(|s| !s.path.as_os_str().is_empty());
+    result
+}
+
+/// Parse a [git-submodule(1)](https://git-scm.com/docs/git-submodule) status line into the
+/// state flag, commit & the remainder starting with the path
+fn parse_status_line(line: &str) -> Option<(char, &str, &str)> {
+    let mut chars = line.chars();
+    let flag = chars.next()?;
+    let (id, rest) = chars.as_str().split_once(' ')?;
+    Some((flag, id, rest))
+}
+
+/// Returns `true` if the status remainder `rest` belongs to the submodule at `path`
+fn is_status_of(rest: &str, path: &Path) -> bool {
+    let path = path.to_string_lossy();
+    rest.strip_prefix(path.as_ref())
+        .is_some_and(|tail| tail.is_empty() || tail.starts_with(" ("))
+}
+
+/// Run a git-submodule(1) command, translating well known failures into [`SubmoduleError`]
+fn run_submodule(mut cmd: GitCommand, path: Option<&Path>) -> Result<(), SubmoduleError> {
+    let out = cmd.output()?;
+    if out.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    let path = path.map(Path::to_path_buf).unwrap_or_default();
+    if stderr.contains("already exists") {
+        Err(SubmoduleError::AlreadyExists(path))
+    } else if stderr.contains("did not match any file(s) known to git") {
+        Err(SubmoduleError::NotFound(path))
+    } else if stderr.contains("contains local modifications") {
+        Err(SubmoduleError::Dirty(path))
+    } else {
+        Err(GitError::from_output(&cmd, &out).into())
+    }
+}
+
+/// Submodules
+impl Repository {
+    /// List the submodules configured in `.gitmodules` with their state
+    ///
+    /// # Errors
+    ///
+    /// See [`GitError`]
+    #[inline]
+    pub fn submodules(&self) -> Result<Vec<Submodule>, GitError> {
+        let gitmodules = self.work_tree.0.join(".gitmodules");
+        if !gitmodules.is_file() {
+            return Ok(vec![]);
+        }
+        let mut cmd = self.git();
+        cmd.args(["config", "-z", "--file"])
+            .arg(&gitmodules)
+            .args(["--get-regexp", r"^submodule\."]);
+        let out = cmd.output()?;
+        let mut result = match out.status.code() {
+            Some(0) => parse_gitmodules(&String::from_utf8(out.stdout)?),
+            Some(1) => vec![],
+            _ => return Err(GitError::from_output(&cmd, &out)),
+        };
+
+        let recorded = self
+            .git()
+            .args(["submodule", "status", "--cached"])
+            .read()?;
+        let checked_out = self.git().args(["submodule", "status"]).read()?;
+        for (cached, line) in recorded
+            .lines()
+            .map(|line| (true, line))
+            .chain(checked_out.lines().map(|line| (false, line)))
+        {
+            let (flag, id, rest) = parse_status_line(line).ok_or_else(|| {
+                GitError::Parse(format!("Unexpected git-submodule(1) status `{}`", line))
+            })?;
+            if let Some(submodule) = result.iter_mut().find(|s| is_status_of(rest, &s.path)) {
+                if cached {
+                    submodule.recorded = Some(id.to_owned());
+                } else if flag != '-' {
+                    submodule.initialized = true;
+                    submodule.modified = flag == '+';
+                    submodule.checked_out = Some(id.to_owned());
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Add the repository at `url` as submodule at `path`, optionally following `branch`.
+    /// Returns the [`Repository`] of the new submodule.
+    ///
+    /// # Errors
+    ///
+    /// Throws [`SubmoduleError`] on errors
+    #[inline]
+    pub fn submodule_add(
+        &self,
+        url: &str,
+        path: &Path,
+        branch: Option<&str>,
+    ) -> Result<Self, SubmoduleError> {
+        let mut cmd = self.git();
+        cmd.args(["submodule", "add", "--quiet"]);
+        if let Some(branch) = branch {
+            cmd.arg("--branch").arg(branch);
+        }
+        cmd.arg("--").arg(url).arg(path);
+        run_submodule(cmd, Some(path))?;
+        Ok(self.open_submodule(path).map_err(GitError::from)?)
+    }
+
+    /// Run `git submodule update` with the given options
+    ///
+    /// # Errors
+    ///
+    /// Throws [`SubmoduleError`] on errors
+    #[inline]
+    pub fn submodule_update(&self, options: &SubmoduleUpdateOptions) -> Result<(), SubmoduleError> {
+        let mut cmd = self.git();
+        cmd.args(["submodule", "update", "--quiet"]);
+        if options.init {
+            cmd.arg("--init");
+        }
+        if options.recursive {
+            cmd.arg("--recursive");
+        }
+        if options.remote {
+            cmd.arg("--remote");
+        }
+        if options.force {
+            cmd.arg("--force");
+        }
+        cmd.arg("--").args(&options.paths);
+        run_submodule(cmd, options.paths.first().map(PathBuf::as_path))
+    }
+
+    /// Copy the submodule URLs from `.gitmodules` into the configuration
+    ///
+    /// # Errors
+    ///
+    /// See [`GitError`]
+    #[inline]
+    pub fn submodule_sync(&self, recursive: bool) -> Result<(), GitError> {
+        let mut cmd = self.git();
+        cmd.args(["submodule", "sync", "--quiet"]);
+        if recursive {
+            cmd.arg("--recursive");
+        }
+        cmd.run()?;
+        Ok(())
+    }
+
+    /// Run the shell `command` in each checked out submodule and return its combined `STDOUT`.
+    /// The command can use the variables `$name`, `$sm_path`, `$displaypath`, `$sha1` &
+    /// `$toplevel`.
+    ///
+    /// # Errors
+    ///
+    /// See [`GitError`]
+    #[inline]
+    pub fn submodule_foreach(&self, command: &str, recursive: bool) -> Result<String, GitError> {
+        let mut cmd = self.git();
+        cmd.args(["submodule", "foreach", "--quiet"]);
+        if recursive {
+            cmd.arg("--recursive");
+        }
+        cmd.arg(command).read()
+    }
+
+    /// Unregister the submodule at `path` & remove its work tree; `force` discards local
+    /// modifications
+    ///
+    /// # Errors
+    ///
+    /// Throws [`SubmoduleError`] on errors
+    #[inline]
+    pub fn submodule_deinit(&self, path: &Path, force: bool) -> Result<(), SubmoduleError> {
+        let mut cmd = self.git();
+        cmd.args(["submodule", "deinit", "--quiet"]);
+        if force {
+            cmd.arg("--force");
+        }
+        cmd.arg("--").arg(path);
+        run_submodule(cmd, Some(path))
+    }
+
+    /// Return the [`Repository`] of the checked out submodule at `path`
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RepoError::GitDirNotFound`] if the submodule is not initialized
+    #[inline]
+    pub fn open_submodule(&self, path: &Path) -> Result<Self, RepoError> {
+        let dir = self.work_tree.0.join(path);
+        let git_dir = dot_git(&dir)?.ok_or(RepoError::GitDirNotFound)?;
+        let work_tree = dir
+            .canonicalize()
+            .map_err(|_| RepoError::InvalidDirectory(dir.clone()))?;
+        Ok(Self::new(
+            AbsoluteDirPath::try_from(git_dir.as_path())?,
+            AbsoluteDirPath::try_from(work_tree.as_path())?,
+        )
+        .with_runner(self.runner.clone()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        setup_test_author, GenericRepository, GitCommand, GitRunner, ProcessRunner, RepoError,
+        Repository, SubmoduleError, SubmoduleUpdateOptions,
+    };
+    use std::path::Path;
+    use std::process::Output;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    /// Runs git like `git -c protocol.file.allow=always`, required to clone local submodules
+    #[derive(Debug, Default)]
+    struct AllowFileProtocol(ProcessRunner);
+
+    impl GitRunner for AllowFileProtocol {
+        fn output(&self, cmd: &GitCommand) -> std::io::Result<Output> {
+            let mut cmd = cmd.clone();
+            cmd.env("GIT_CONFIG_COUNT", "1")
+                .env("GIT_CONFIG_KEY_0", "protocol.file.allow")
+                .env("GIT_CONFIG_VALUE_0", "always");
+            self.0.output(&cmd)
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let output = "submodule.a.b.path\nlibs/a\0submodule.a.b.url\n../a\0submodule.c.url\n../c\0";
+        let submodules = super::parse_gitmodules(output);
+        assert_eq!(submodules.len(), 1);
+        assert_eq!(submodules[0].name, "a.b");
+        assert_eq!(submodules[0].path, Path::new("libs/a"));
+        assert_eq!(submodules[0].url.as_deref(), Some("../a"));
+
+        let line = "+1234 libs/a (heads/main)";
+        let (flag, id, rest) = super::parse_status_line(line).unwrap();
+        assert_eq!((flag, id), ('+', "1234"));
+        assert!(super::is_status_of(rest, Path::new("libs/a")));
+        assert!(!super::is_status_of(rest, Path::new("libs")));
+    }
+
+    #[test]
+    fn manage() {
+        setup_test_author();
+        let tmp_dir = TempDir::new().unwrap();
+        let root = tmp_dir.path().canonicalize().unwrap();
+        let sub = Repository::create(&root.join("sub")).expect("Created repository");
+        sub.commit_extended("Sub", true, true).unwrap();
+        let branch = sub.current_branch().unwrap().unwrap();
+        let repo = Repository::create(&root.join("super"))
+            .expect("Created repository")
+            .with_runner(Arc::new(AllowFileProtocol::default()));
+        repo.commit_extended("First", true, true).unwrap();
+        assert!(repo.submodules().unwrap().is_empty());
+
+        let path = Path::new("libs/sub");
+        let url = root.join("sub").to_string_lossy().to_string();
+        let module = repo.submodule_add(&url, path, Some(&branch)).unwrap();
+        assert_eq!(module.work_tree(), Some(root.join("super/libs/sub")));
+        assert_eq!(module.head().unwrap(), sub.head().unwrap());
+        assert_eq!(
+            repo.submodule_add(&url, path, None).unwrap_err(),
+            SubmoduleError::AlreadyExists(path.to_path_buf())
+        );
+        repo.commit_extended("Add submodule", false, true).unwrap();
+
+        let submodules = repo.submodules().unwrap();
+        assert_eq!(submodules.len(), 1);
+        assert_eq!(submodules[0].name, "libs/sub");
+        assert_eq!(submodules[0].url.as_deref(), Some(url.as_str()));
+        assert_eq!(submodules[0].branch.as_deref(), Some(branch.as_str()));
+        assert!(submodules[0].initialized);
+        assert!(!submodules[0].modified);
+        assert_eq!(
+            repo.submodule_foreach("echo $name:$sm_path", false)
+                .unwrap(),
+            "libs/sub:libs/sub\n"
+        );
+
+        sub.commit_extended("Second", true, true).unwrap();
+        let options = SubmoduleUpdateOptions::default().remote(true);
+        repo.submodule_update(&options).unwrap();
+        let submodules = repo.submodules().unwrap();
+        assert!(submodules[0].modified);
+        assert_eq!(submodules[0].checked_out, Some(sub.head().unwrap()));
+        assert_ne!(submodules[0].recorded, submodules[0].checked_out);
+        repo.submodule_sync(true).unwrap();
+
+        assert_eq!(
+            repo.submodule_deinit(path, false).unwrap_err(),
+            SubmoduleError::Dirty(path.to_path_buf())
+        );
+        repo.submodule_deinit(path, true).unwrap();
+        assert!(!repo.submodules().unwrap()[0].initialized);
+        assert_eq!(
+            repo.open_submodule(path).unwrap_err(),
+            RepoError::GitDirNotFound
+        );
+        assert_eq!(
+            repo.submodule_deinit(Path::new("nope"), false).unwrap_err(),
+            SubmoduleError::NotFound(Path::new("nope").to_path_buf())
+        );
+
+        let options = SubmoduleUpdateOptions::default().init(true).path(path);
+        repo.submodule_update(&options).unwrap();
+        let submodules = repo.submodules().unwrap();
+        assert!(submodules[0].initialized);
+        assert!(!submodules[0].modified);
+        assert!(repo.open_submodule(path).is_ok());
+    }
+}
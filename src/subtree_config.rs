@@ -0,0 +1,398 @@
+use crate::{
+    config_file_set, resolve_head, Config, ConfigLocation, ConfigReadError, ConfigSetError,
+    ConfigWriter, GenericRepository, GitError, Repository, SubtreeAddError, SubtreePullError,
+    SubtreePushError,
+};
+use posix_errors::{PosixError, EINVAL};
+use std::path::{Path, PathBuf};
+
+/// Name of the manifest files describing the subtrees in their directory
+pub const SUBTREE_MANIFEST: &str = ".gitsubtrees";
+
+/// Failed to read or write a [`SUBTREE_MANIFEST`]
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum SubtreeConfigError {
+    /// Subtree entry without an `url`
+    #[error("Subtree `{0}` has no url")]
+    MissingUrl(String),
+    /// Prefix does not end with the id of the subtree
+    #[error("Prefix `{0}` does not match the subtree id")]
+    InvalidPrefix(PathBuf),
+    #[allow(missing_docs)]
+    #[error(transparent)]
+    Read(#[from] ConfigReadError),
+    #[allow(missing_docs)]
+    #[error(transparent)]
+    Write(#[from] ConfigSetError),
+    #[allow(missing_docs)]
+    #[error(transparent)]
+    Failure(#[from] GitError),
+}
+
+impl From<SubtreeConfigError> for GitError {
+    #[inline]
+    fn from(err: SubtreeConfigError) -> Self {
+        match err {
+            SubtreeConfigError::Failure(e) => e,
+            SubtreeConfigError::Read(e) => e.into(),
+            SubtreeConfigError::Write(e) => e.into(),
+            SubtreeConfigError::MissingUrl(_) | SubtreeConfigError::InvalidPrefix(_) => {
+                Self::Invalid(format!("{}", err))
+            }
+        }
+    }
+}
+
+impl From<SubtreeConfigError> for PosixError {
+    #[inline]
+    fn from(err: SubtreeConfigError) -> Self {
+        match err {
+            SubtreeConfigError::Failure(e) => e.into(),
+            SubtreeConfigError::Read(e) => e.into(),
+            SubtreeConfigError::Write(e) => e.into(),
+            SubtreeConfigError::MissingUrl(_) | SubtreeConfigError::InvalidPrefix(_) => {
+                Self::new(EINVAL, format!("{}", err))
+            }
+        }
+    }
+}
+
+/// A `subtree.<id>` entry of a [`SUBTREE_MANIFEST`]
+///
+/// ```ini
+/// [subtree "bar"]
+///     url = https://example.com/foo/bar
+///     follow = main
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SubtreeConfig {
+    /// Path of the subtree relative to the directory containing the manifest
+    pub id: String,
+    /// Path of the subtree relative to the work tree
+    pub prefix: PathBuf,
+    /// Repository the subtree was added from
+    pub url: String,
+    /// Repository to pull from, if it differs from `url`
+    pub upstream: Option<String>,
+    /// Repository to push to, if it differs from `url`
+    pub origin: Option<String>,
+    /// Branch or tag to pull, the remote `HEAD` if not set
+    pub follow: Option<String>,
+    /// Also pull pre-release tags
+    pub pull_pre_releases: bool,
+}
+
+impl SubtreeConfig {
+    #[allow(missing_docs)]
+    #[must_use]
+    #[inline]
+    pub fn pull_url(&self) -> &str {
+        self.upstream.as_deref().unwrap_or(&self.url)
+    }
+
+    #[allow(missing_docs)]
+    #[must_use]
+    #[inline]
+    pub fn push_url(&self) -> &str {
+        self.origin.as_deref().unwrap_or(&self.url)
+    }
+
+    /// The reference to pull or push, falls back to the remote `HEAD` of `url`
+    fn reference(&self, url: &str) -> Result<String, GitError> {
+        match &self.follow {
+            Some(follow) => Ok(follow.clone()),
+            None => Ok(resolve_head(url)?),
+        }
+    }
+
+    /// Directory of the manifest this subtree belongs to, relative to the work tree
+    fn manifest_dir(&self) -> Result<&Path, SubtreeConfigError> {
+        let invalid = || SubtreeConfigError::InvalidPrefix(self.prefix.clone());
+        let depth = Path::new(&self.id).components().count();
+        let dir = self.prefix.ancestors().nth(depth).ok_or_else(invalid)?;
+        if depth > 0 && dir.join(&self.id) == self.prefix {
+            Ok(dir)
+        } else {
+            Err(invalid())
+        }
+    }
+}
+
+/// Read all subtree entries of the manifest read by `config`, prefixes are relative to `dir`
+fn read_manifest(config: &Config, dir: &Path) -> Result<Vec<SubtreeConfig>, SubtreeConfigError> {
+    let mut result: Vec<SubtreeConfig> = vec![];
+    for entry in config.get_regexp(r"^subtree\.")? {
+        let (id, variable) = match entry
+            .key
+            .strip_prefix("subtree.")
+            .and_then(|rest| rest.rsplit_once('.'))
+        {
+            Some(parts) => parts,
+            None => continue,
+        };
+        let index = if let Some(i) = result.iter().position(|s| s.id == id) {
+            i
+        } else {
+            result.push(SubtreeConfig {
+                id: id.to_owned(),
+                prefix: dir.join(id),
+                ..SubtreeConfig::default()
+            });
+            result.len() - 1
+        };
+        let subtree = &mut result[index];
+        match variable {
+            "url" => subtree.url = entry.value.unwrap_or_default(),
+            "upstream" => subtree.upstream = entry.value,
+            "origin" => subtree.origin = entry.value,
+            "follow" => subtree.follow = entry.value,
+            "pull-pre-releases" => {
+                subtree.pull_pre_releases = config.get_bool(&entry.key)?.unwrap_or_default();
+            }
+            _ => {}
+        }
+    }
+    if let Some(subtree) = result.iter().find(|s| s.url.is_empty()) {
+        return Err(SubtreeConfigError::MissingUrl(subtree.id.clone()));
+    }
+    Ok(result)
+}
+
+/// Subtree manifests
+impl Repository {
+    /// Return all [`SUBTREE_MANIFEST`] files in the work tree, relative to it.
+    ///
+    /// Uses [git-ls-files(1)](https://git-scm.com/docs/git-ls-files), so only tracked
+    /// manifests are returned.
+    ///
+    /// # Errors
+    ///
+    /// See [`GitError`]
+    #[inline]
+    pub fn subtree_manifests(&self) -> Result<Vec<PathBuf>, GitError> {
+        let out = self
+            .git()
+            .args(["ls-files", "-z", "--", SUBTREE_MANIFEST])
+            .arg(format!(":(glob)**/{}", SUBTREE_MANIFEST))
+            .read()?;
+        Ok(out.split_terminator('\0').map(PathBuf::from).collect())
+    }
+
+    /// Read the subtree entries of all [`SUBTREE_MANIFEST`] files in the work tree
+    ///
+    /// # Errors
+    ///
+    /// Throws [`SubtreeConfigError`] on errors
+    #[inline]
+    pub fn subtree_configs(&self) -> Result<Vec<SubtreeConfig>, SubtreeConfigError> {
+        let mut result = vec![];
+        for manifest in self.subtree_manifests()? {
+            let dir = manifest.parent().unwrap_or_else(|| Path::new(""));
+            let config = self
+                .configuration()
+                .with_file(&self.work_tree.0.join(&manifest));
+            result.extend(read_manifest(&config, dir)?);
+        }
+        Ok(result)
+    }
+
+    /// Write `subtree` to the [`SUBTREE_MANIFEST`] in the directory its id is relative to.
+    /// Unset optional values are removed from the manifest.
+    ///
+    /// # Errors
+    ///
+    /// Throws [`SubtreeConfigError`] on errors
+    #[inline]
+    pub fn write_subtree_config(&self, subtree: &SubtreeConfig) -> Result<(), SubtreeConfigError> {
+        let file = self
+            .work_tree
+            .0
+            .join(subtree.manifest_dir()?)
+            .join(SUBTREE_MANIFEST);
+        let key = |name: &str| format!("subtree.{}.{}", subtree.id, name);
+        config_file_set(&file, &key("url"), &subtree.url)?;
+        let writer = ConfigWriter::new(self.git(), &ConfigLocation::File(file.clone()));
+        let pull_pre_releases = subtree.pull_pre_releases.then(|| "true".to_owned());
+        for (name, value) in [
+            ("upstream", &subtree.upstream),
+            ("origin", &subtree.origin),
+            ("follow", &subtree.follow),
+            ("pull-pre-releases", &pull_pre_releases),
+        ] {
+            match value {
+                Some(value) => config_file_set(&file, &key(name), value)?,
+                None => match writer.unset_all(&key(name), None) {
+                    Ok(()) | Err(ConfigSetError::NotFound(_)) => {}
+                    Err(e) => return Err(e.into()),
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// Add the subtree described by `subtree`, see [`Repository::subtree_add`]
+    ///
+    /// # Errors
+    ///
+    /// Fails if current repo is bare or dirty. In error cases see the provided string.
+    #[inline]
+    pub fn subtree_add_from_config(
+        &self,
+        subtree: &SubtreeConfig,
+        message: &str,
+    ) -> Result<(), SubtreeAddError> {
+        let url = subtree.pull_url();
+        let revision = subtree.reference(url)?;
+        self.subtree_add(url, &subtree.prefix.to_string_lossy(), &revision, message)
+    }
+
+    /// Pull the changes of `subtree` from its upstream, see [`Repository::subtree_pull`]
+    ///
+    /// # Errors
+    ///
+    /// Fails if current repo is bare or dirty. In error cases see the provided string.
+    #[inline]
+    pub fn subtree_pull_from_config(
+        &self,
+        subtree: &SubtreeConfig,
+        message: &str,
+    ) -> Result<(), SubtreePullError> {
+        let url = subtree.pull_url();
+        let git_ref = subtree.reference(url)?;
+        self.subtree_pull(url, &subtree.prefix.to_string_lossy(), &git_ref, message)
+    }
+
+    /// Push the changes of `subtree` to its origin, see [`Repository::subtree_push`]
+    ///
+    /// # Errors
+    ///
+    /// Fails if current repo is bare. In other error cases see the provided message string.
+    #[inline]
+    pub fn subtree_push_from_config(
+        &self,
+        subtree: &SubtreeConfig,
+    ) -> Result<(), SubtreePushError> {
+        let url = subtree.push_url();
+        let git_ref = subtree.reference(url)?;
+        self.subtree_push(url, &subtree.prefix.to_string_lossy(), &git_ref)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        setup_test_author, BareRepository, GenericRepository, RefOptions, Repository,
+        SubtreeConfig, SubtreeConfigError,
+    };
+    use std::path::{Path, PathBuf};
+    use tempfile::TempDir;
+
+    #[test]
+    fn manifests() {
+        setup_test_author();
+        let tmp_dir = TempDir::new().unwrap();
+        let root = tmp_dir.path().canonicalize().unwrap();
+        let repo = Repository::create(&root.join("repo")).expect("Created repository");
+        let bar = SubtreeConfig {
+            id: "bar".to_owned(),
+            prefix: PathBuf::from("bar"),
+            url: "https://example.com/bar".to_owned(),
+            follow: Some("main".to_owned()),
+            pull_pre_releases: true,
+            ..SubtreeConfig::default()
+        };
+        let foo = SubtreeConfig {
+            id: "vendor/foo".to_owned(),
+            prefix: PathBuf::from("libs/vendor/foo"),
+            url: "https://example.com/foo".to_owned(),
+            upstream: Some("https://example.com/upstream/foo".to_owned()),
+            origin: Some("https://example.com/fork/foo".to_owned()),
+            ..SubtreeConfig::default()
+        };
+        repo.write_subtree_config(&bar).unwrap();
+        std::fs::create_dir(root.join("repo/libs")).unwrap();
+        repo.write_subtree_config(&foo).unwrap();
+        let invalid = SubtreeConfig {
+            prefix: PathBuf::from("libs/other"),
+            ..foo.clone()
+        };
+        assert_eq!(
+            repo.write_subtree_config(&invalid).unwrap_err(),
+            SubtreeConfigError::InvalidPrefix(PathBuf::from("libs/other"))
+        );
+        assert!(repo.subtree_manifests().unwrap().is_empty());
+        repo.git().args(["add", "--all"]).run().unwrap();
+
+        assert_eq!(
+            repo.subtree_manifests().unwrap(),
+            vec![
+                PathBuf::from(".gitsubtrees"),
+                PathBuf::from("libs/.gitsubtrees")
+            ]
+        );
+        assert_eq!(repo.subtree_configs().unwrap(), vec![bar.clone(), foo]);
+        assert_eq!(bar.push_url(), "https://example.com/bar");
+
+        let bar = SubtreeConfig {
+            follow: None,
+            pull_pre_releases: false,
+            ..bar
+        };
+        repo.write_subtree_config(&bar).unwrap();
+        assert_eq!(repo.subtree_configs().unwrap()[0], bar);
+
+        std::fs::write(
+            root.join("repo/.gitsubtrees"),
+            "[subtree \"baz\"]\nfollow = main\n",
+        )
+        .unwrap();
+        assert_eq!(
+            repo.subtree_configs().unwrap_err(),
+            SubtreeConfigError::MissingUrl("baz".to_owned())
+        );
+    }
+
+    #[test]
+    fn drive_subtree() {
+        setup_test_author();
+        let tmp_dir = TempDir::new().unwrap();
+        let root = tmp_dir.path().canonicalize().unwrap();
+        let upstream = Repository::create(&root.join("upstream")).expect("Created repository");
+        std::fs::write(root.join("upstream/lib.rs"), "").unwrap();
+        upstream.stage(Path::new("lib.rs")).unwrap();
+        upstream.commit("First").unwrap();
+        upstream.create_branch("stable", None, false).unwrap();
+        let origin = BareRepository::create(&root.join("origin.git")).expect("Created repository");
+        let repo = Repository::create(&root.join("repo")).expect("Created repository");
+        repo.commit_extended("Init", true, true).unwrap();
+
+        let subtree = SubtreeConfig {
+            id: "lib".to_owned(),
+            prefix: PathBuf::from("lib"),
+            url: root.join("upstream").to_string_lossy().to_string(),
+            origin: Some(root.join("origin.git").to_string_lossy().to_string()),
+            follow: Some("stable".to_owned()),
+            ..SubtreeConfig::default()
+        };
+        repo.subtree_add_from_config(&subtree, "Add lib").unwrap();
+        assert!(root.join("repo/lib/lib.rs").is_file());
+
+        upstream
+            .git()
+            .args(["checkout", "-q", "stable"])
+            .run()
+            .unwrap();
+        std::fs::write(root.join("upstream/README.md"), "# lib").unwrap();
+        upstream.stage(Path::new("README.md")).unwrap();
+        upstream.commit("Readme").unwrap();
+        repo.subtree_pull_from_config(&subtree, "Update lib")
+            .unwrap();
+        assert!(root.join("repo/lib/README.md").is_file());
+
+        repo.subtree_push_from_config(&subtree).unwrap();
+        let pushed = origin
+            .refs(&["refs/heads/stable"], &RefOptions::default())
+            .unwrap();
+        assert_eq!(pushed.len(), 1);
+    }
+}
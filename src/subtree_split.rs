@@ -0,0 +1,248 @@
+use crate::{GitCommand, GitError, Progress, ProgressCallback, SubtreeSplitError};
+
+/// Options for [`crate::Repository::subtree_split`]
+#[derive(Default)]
+pub struct SubtreeSplitOptions<'a> {
+    rev: Option<String>,
+    branch: Option<String>,
+    annotate: Option<String>,
+    onto: Option<String>,
+    ignore_joins: bool,
+    rejoin: bool,
+    squash: bool,
+    progress: Option<ProgressCallback<'a>>,
+}
+
+impl std::fmt::Debug for SubtreeSplitOptions<'_> {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SubtreeSplitOptions")
+            .field("rev", &self.rev)
+            .field("branch", &self.branch)
+            .field("annotate", &self.annotate)
+            .field("onto", &self.onto)
+            .field("ignore_joins", &self.ignore_joins)
+            .field("rejoin", &self.rejoin)
+            .field("squash", &self.squash)
+            .field("progress", &self.progress.is_some())
+            .finish()
+    }
+}
+
+impl<'a> SubtreeSplitOptions<'a> {
+    /// Split the history of `rev` instead of `HEAD`
+    #[must_use]
+    #[inline]
+    pub fn rev(mut self, rev: &str) -> Self {
+        self.rev = Some(rev.to_owned());
+        self
+    }
+
+    /// Create or update `branch` to point to the split commit
+    #[must_use]
+    #[inline]
+    pub fn branch(mut self, branch: &str) -> Self {
+        self.branch = Some(branch.to_owned());
+        self
+    }
+
+    /// Prefix the subject of every synthesized commit with `annotation`
+    #[must_use]
+    #[inline]
+    pub fn annotate(mut self, annotation: &str) -> Self {
+        self.annotate = Some(annotation.to_owned());
+        self
+    }
+
+    /// Connect the split history to the existing history of `commit`
+    #[must_use]
+    #[inline]
+    pub fn onto(mut self, commit: &str) -> Self {
+        self.onto = Some(commit.to_owned());
+        self
+    }
+
+    /// Ignore previous splits with rejoin & regenerate the whole history
+    #[must_use]
+    #[inline]
+    pub const fn ignore_joins(mut self, ignore_joins: bool) -> Self {
+        self.ignore_joins = ignore_joins;
+        self
+    }
+
+    /// Merge the split history back into the current branch, which speeds up later splits.
+    /// Requires a clean work tree.
+    #[must_use]
+    #[inline]
+    pub const fn rejoin(mut self, rejoin: bool) -> Self {
+        self.rejoin = rejoin;
+        self
+    }
+
+    /// Squash the rejoined history into a single commit, requires
+    /// [`SubtreeSplitOptions::rejoin`] or the split fails with
+    /// [`SubtreeSplitError::SquashRequiresRejoin`]
+    #[must_use]
+    #[inline]
+    pub const fn squash(mut self, squash: bool) -> Self {
+        self.squash = squash;
+        self
+    }
+
+    /// Call `callback` for every processed commit
+    #[must_use]
+    #[inline]
+    pub fn progress(mut self, callback: impl FnMut(&Progress) + 'a) -> Self {
+        self.progress = Some(Box::new(callback));
+        self
+    }
+
+    pub(crate) const fn get_rejoin(&self) -> bool {
+        self.rejoin
+    }
+}
+
+/// Parse a git-subtree(1) split progress line `<current>/<total> (<created>) [<extra>]`
+fn parse_progress(line: &str) -> Option<Progress> {
+    let (counts, _) = line.split_once(" (")?;
+    let (current, total) = counts.split_once('/')?;
+    let current: u64 = current.trim().parse().ok()?;
+    let total: u64 = total.trim().parse().ok()?;
+    Some(Progress {
+        phase: "Splitting commits".to_owned(),
+        remote: false,
+        current,
+        total: Some(total),
+        done: current == total,
+    })
+}
+
+/// Run `git subtree split` for `prefix` & return the split commit id
+pub(crate) fn run_split(
+    mut cmd: GitCommand,
+    prefix: &str,
+    mut options: SubtreeSplitOptions<'_>,
+) -> Result<String, SubtreeSplitError> {
+    if options.squash && !options.rejoin {
+        return Err(SubtreeSplitError::SquashRequiresRejoin);
+    }
+    cmd.args(["subtree", "split", "-P", prefix]);
+    if options.progress.is_none() {
+        cmd.arg("-q");
+    }
+    if let Some(branch) = &options.branch {
+        cmd.arg("--branch").arg(branch);
+    }
+    if let Some(annotation) = &options.annotate {
+        cmd.arg(format!("--annotate={}", annotation));
+    }
+    if let Some(onto) = &options.onto {
+        cmd.arg(format!("--onto={}", onto));
+    }
+    if options.ignore_joins {
+        cmd.arg("--ignore-joins");
+    }
+    if options.rejoin {
+        cmd.arg("--rejoin");
+    }
+    if options.squash {
+        cmd.arg("--squash");
+    }
+    cmd.arg(options.rev.as_deref().unwrap_or("HEAD"));
+
+    let out = cmd.output_with_progress(&mut |line| {
+        if let (Some(callback), Some(progress)) = (options.progress.as_mut(), parse_progress(line))
+        {
+            callback(&progress);
+        }
+    })?;
+    if !out.status.success() {
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        if stderr.contains("does not exist") {
+            return Err(SubtreeSplitError::PrefixNotFound(prefix.to_owned()));
+        }
+        return Err(GitError::from_output(&cmd, &out).into());
+    }
+    let stdout = String::from_utf8(out.stdout).map_err(GitError::from)?;
+    stdout
+        .lines()
+        .next_back()
+        .map(|id| id.trim().to_owned())
+        .filter(|id| !id.is_empty())
+        .ok_or_else(|| {
+            GitError::Parse(format!("Unexpected git-subtree(1) output `{}`", stdout)).into()
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        setup_test_author, GenericRepository, RefOptions, Repository, SubtreeSplitError,
+        SubtreeSplitOptions,
+    };
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    #[test]
+    fn parse() {
+        let progress = super::parse_progress("2/2 (1) [0]").unwrap();
+        assert_eq!((progress.current, progress.total), (2, Some(2)));
+        assert!(progress.done);
+        assert!(super::parse_progress("Created branch 'foo'").is_none());
+    }
+
+    #[test]
+    fn split() {
+        setup_test_author();
+        let tmp_dir = TempDir::new().unwrap();
+        let repo = Repository::create(tmp_dir.path()).expect("Created repository");
+        for content in ["1", "2"] {
+            std::fs::create_dir_all(tmp_dir.path().join("lib")).unwrap();
+            std::fs::write(tmp_dir.path().join("lib/lib.rs"), content).unwrap();
+            repo.stage(Path::new("lib/lib.rs")).unwrap();
+            repo.commit(content).unwrap();
+        }
+        let head = repo.head().unwrap();
+
+        let mut updates = vec![];
+        let options = SubtreeSplitOptions::default()
+            .branch("split")
+            .annotate("(lib) ")
+            .progress(|p| updates.push(p.current));
+        let id = repo.subtree_split("lib", options).unwrap();
+        assert_eq!(updates.last(), Some(&2));
+        let branches = repo
+            .refs(&["refs/heads/split"], &RefOptions::default())
+            .unwrap();
+        assert_eq!(branches[0].target, id);
+        let subject = repo
+            .git()
+            .args(["log", "-1", "--format=%s", &id])
+            .read()
+            .unwrap();
+        assert_eq!(subject.trim(), "(lib) 2");
+        assert_eq!(repo.head().unwrap(), head);
+
+        let options = SubtreeSplitOptions::default().rev("HEAD~");
+        assert_ne!(repo.subtree_split("lib", options).unwrap(), id);
+
+        std::fs::write(tmp_dir.path().join("untracked"), "").unwrap();
+        let options = SubtreeSplitOptions::default()
+            .annotate("(lib) ")
+            .rejoin(true)
+            .squash(true);
+        assert_eq!(repo.subtree_split("lib", options).unwrap(), id);
+        assert_ne!(repo.head().unwrap(), head);
+
+        assert_eq!(
+            repo.subtree_split("nope", SubtreeSplitOptions::default())
+                .unwrap_err(),
+            SubtreeSplitError::PrefixNotFound("nope".to_owned())
+        );
+        assert_eq!(
+            repo.subtree_split("lib", SubtreeSplitOptions::default().squash(true))
+                .unwrap_err(),
+            SubtreeSplitError::SquashRequiresRejoin
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use crate::{GenericRepository, GitError, RefSearchError, Repository};
+
+/// State of a subtree compared to its remote, see [`Repository::subtree_status`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SubtreeStatus {
+    /// The remote reference is merged & there are no local changes
+    UpToDate,
+    /// The remote has the given number of commits which are not merged yet
+    Behind(u32),
+    /// The subtree has the given number of local commits which are not pushed yet
+    Ahead(u32),
+    /// Both the remote & the subtree have new commits
+    Diverged {
+        #[allow(missing_docs)]
+        ahead: u32,
+        #[allow(missing_docs)]
+        behind: u32,
+    },
+    /// History contains no `git-subtree-dir` trailer for the prefix
+    NeverSplit,
+}
+
+impl SubtreeStatus {
+    const fn new(ahead: u32, behind: u32) -> Self {
+        match (ahead, behind) {
+            (0, 0) => Self::UpToDate,
+            (0, behind) => Self::Behind(behind),
+            (ahead, 0) => Self::Ahead(ahead),
+            (ahead, behind) => Self::Diverged { ahead, behind },
+        }
+    }
+}
+
+/// Escape `text` for use in a basic regular expression
+fn escape_regex(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '.' | '[' | ']' | '*' | '^' | '$' | '\\') {
+            result.push('\\');
+        }
+        result.push(c);
+    }
+    result
+}
+
+/// Subtree status
+impl Repository {
+    /// Count the non-merge commits in `revs`
+    fn count_commits(&self, revs: &[&str]) -> Result<u32, GitError> {
+        let mut cmd = self.git();
+        cmd.args(["rev-list", "--count", "--no-merges"])
+            .args(revs)
+            .arg("--");
+        let out = cmd.read()?;
+        out.trim()
+            .parse()
+            .map_err(|_| GitError::Parse(format!("Unexpected git-rev-list(1) output `{}`", out)))
+    }
+
+    /// Compare the subtree at `prefix` with `git_ref` of `remote`.
+    ///
+    /// Splits the current history of `prefix` like [`Repository::subtree_split`] & compares the
+    /// split commit with the remote commit. The subtree is ahead by the split commits missing
+    /// on the remote & behind by the remote commits missing in the split. Returns
+    /// [`SubtreeStatus::NeverSplit`] if no commit has a `git-subtree-dir` trailer for `prefix`,
+    /// as created by [`Repository::subtree_add`], squashed pulls & splits with rejoin.
+    ///
+    /// Fetches `git_ref` if the remote commit is not available locally.
+    ///
+    /// # Errors
+    ///
+    /// See [`RefSearchError`]
+    #[inline]
+    pub fn subtree_status(
+        &self,
+        prefix: &str,
+        remote: &str,
+        git_ref: &str,
+    ) -> Result<SubtreeStatus, RefSearchError> {
+        let prefix = prefix.trim_end_matches('/');
+        let mut cmd = self.git();
+        cmd.args(["log", "-1", "--format=%H"])
+            .arg(format!(
+                "--grep=^git-subtree-dir: {}/*$",
+                escape_regex(prefix)
+            ))
+            .args(["HEAD", "--"]);
+        if cmd.read()?.trim().is_empty() {
+            return Ok(SubtreeStatus::NeverSplit);
+        }
+
+        let remote_id = self.remote_ref_to_id(remote, git_ref)?;
+        let present = self
+            .git()
+            .args(["cat-file", "-e"])
+            .arg(format!("{}^{{commit}}", remote_id))
+            .output()?
+            .status
+            .success();
+        if !present {
+            self.git()
+                .args(["fetch", "--quiet", "--no-tags", remote, git_ref])
+                .run()?;
+        }
+
+        let out = self
+            .git()
+            .args(["subtree", "split", "-P", prefix, "HEAD"])
+            .read()?;
+        let split = out.trim();
+        let ahead = self.count_commits(&[split, &format!("^{}", remote_id)])?;
+        let behind = self.count_commits(&[&remote_id, &format!("^{}", split)])?;
+        Ok(SubtreeStatus::new(ahead, behind))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{setup_test_author, GenericRepository, Repository, SubtreeStatus};
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    fn commit_file(repo: &Repository, path: &str, content: &str) {
+        let file = repo.work_tree().unwrap().join(path);
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::write(&file, content).unwrap();
+        repo.stage(Path::new(path)).unwrap();
+        repo.commit(&format!("Change {}", path)).unwrap();
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(super::escape_regex("a.b[1]"), r"a\.b\[1\]");
+    }
+
+    #[test]
+    fn status() {
+        setup_test_author();
+        let tmp_dir = TempDir::new().unwrap();
+        let root = tmp_dir.path().canonicalize().unwrap();
+        let upstream = Repository::create(&root.join("upstream")).expect("Created repository");
+        commit_file(&upstream, "lib.rs", "1");
+        let branch = upstream.current_branch().unwrap().unwrap();
+        let url = root.join("upstream").to_string_lossy().to_string();
+        let repo = Repository::create(&root.join("repo")).expect("Created repository");
+        commit_file(&repo, "README.md", "");
+
+        let status = || repo.subtree_status("lib", &url, &branch).unwrap();
+        assert_eq!(status(), SubtreeStatus::NeverSplit);
+        repo.subtree_add(&url, "lib", &branch, "Add lib").unwrap();
+        assert_eq!(status(), SubtreeStatus::UpToDate);
+
+        commit_file(&upstream, "lib.rs", "2");
+        assert_eq!(status(), SubtreeStatus::Behind(1));
+        commit_file(&repo, "lib/local.rs", "local");
+        commit_file(&repo, "README.md", "not in lib");
+        assert_eq!(
+            status(),
+            SubtreeStatus::Diverged {
+                ahead: 1,
+                behind: 1
+            }
+        );
+
+        commit_file(&upstream, "other.rs", "");
+        repo.subtree_pull(&url, "lib", &branch, "Pull lib").unwrap();
+        assert_eq!(status(), SubtreeStatus::Ahead(1));
+
+        repo.subtree_push(&url, "lib", "pushed").unwrap();
+        assert_eq!(
+            repo.subtree_status("lib", &url, "pushed").unwrap(),
+            SubtreeStatus::UpToDate
+        );
+        commit_file(&repo, "lib/local.rs", "changed");
+        assert_eq!(
+            repo.subtree_status("lib", &url, "pushed").unwrap(),
+            SubtreeStatus::Ahead(1)
+        );
+        assert!(repo.subtree_status("lib", &url, "nope").is_err());
+    }
+}
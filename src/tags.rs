@@ -0,0 +1,299 @@
+use crate::log::parse_signature;
+use crate::{GitCommand, GitError, ObjectType, Signature};
+use posix_errors::{PosixError, EEXIST, EINVAL, ENOENT};
+
+/// Fields requested from [git-for-each-ref(1)](https://git-scm.com/docs/git-for-each-ref),
+/// records are terminated by `RS` because tag messages span multiple lines.
+const FORMAT: &str = "%(refname:strip=2)%00%(objectname)%00%(objecttype)%00%(*objectname)%00%(taggername)%00%(taggeremail:trim)%00%(taggerdate:raw)%00%(contents)%1e";
+const FIELDS: usize = 8;
+
+/// Failed to manage a tag
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum TagError {
+    /// A tag with this name already exists
+    #[error("Tag `{0}` already exists")]
+    TagExists(String),
+    /// Tag or revision does not exist
+    #[error("Tag or revision `{0}` not found")]
+    NotFound(String),
+    /// Name is not a valid tag name, see
+    /// [git-check-ref-format(1)](https://git-scm.com/docs/git-check-ref-format)
+    #[error("`{0}` is not a valid tag name")]
+    InvalidName(String),
+    #[allow(missing_docs)]
+    #[error(transparent)]
+    Failure(#[from] GitError),
+}
+
+impl From<TagError> for GitError {
+    #[inline]
+    fn from(err: TagError) -> Self {
+        match err {
+            TagError::Failure(e) => e,
+            TagError::TagExists(_) | TagError::NotFound(_) | TagError::InvalidName(_) => {
+                Self::Invalid(format!("{}", err))
+            }
+        }
+    }
+}
+
+impl From<TagError> for PosixError {
+    #[inline]
+    fn from(err: TagError) -> Self {
+        match err {
+            TagError::Failure(e) => e.into(),
+            TagError::TagExists(_) => Self::new(EEXIST, format!("{}", err)),
+            TagError::NotFound(_) => Self::new(ENOENT, format!("{}", err)),
+            TagError::InvalidName(_) => Self::new(EINVAL, format!("{}", err)),
+        }
+    }
+}
+
+/// A local tag as returned by [`crate::GenericRepository::tags`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Tag {
+    /// Name without the `refs/tags/` prefix
+    pub name: String,
+    /// Object id the tag reference points to
+    pub target: String,
+    /// Type of the target object, [`ObjectType::Tag`] for annotated tags
+    pub kind: ObjectType,
+    /// Object the annotated tag points to
+    pub peeled: Option<String>,
+    /// Creator of an annotated tag
+    pub tagger: Option<Signature>,
+    /// Message of an annotated tag
+    pub message: Option<String>,
+}
+
+impl Tag {
+    /// Returns `true` for annotated tags
+    #[must_use]
+    #[inline]
+    pub fn is_annotated(&self) -> bool {
+        self.kind == ObjectType::Tag
+    }
+
+    /// The object this tag eventually points to
+    #[must_use]
+    #[inline]
+    pub fn commit(&self) -> &str {
+        self.peeled.as_deref().unwrap_or(&self.target)
+    }
+}
+
+/// Nearest tag reachable from a revision as returned by [`crate::GenericRepository::describe`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Description {
+    /// Tag name
+    pub tag: String,
+    /// Number of commits on top of the tag
+    pub distance: u32,
+    /// Abbreviated commit id of the described revision
+    pub id: String,
+}
+
+fn parse_tags(text: &str) -> Result<Vec<Tag>, GitError> {
+    let mut result = vec![];
+    for record in text.split("\x1e\n").filter(|r| !r.is_empty()) {
+        let f: Vec<&str> = record.splitn(FIELDS, '\0').collect();
+        if f.len() != FIELDS {
+            return Err(GitError::Parse(format!(
+                "Unexpected git-for-each-ref(1) record `{}`",
+                record
+            )));
+        }
+        let kind: ObjectType = f[2].parse()?;
+        let (tagger, message) = if kind == ObjectType::Tag {
+            let tagger = if f[6].is_empty() {
+                None
+            } else {
+                Some(parse_signature(f[4], f[5], f[6])?)
+            };
+            (tagger, Some(f[7].to_owned()))
+        } else {
+            (None, None)
+        };
+        result.push(Tag {
+            name: f[0].to_owned(),
+            target: f[1].to_owned(),
+            kind,
+            peeled: (!f[3].is_empty()).then(|| f[3].to_owned()),
+            tagger,
+            message,
+        });
+    }
+    Ok(result)
+}
+
+pub(crate) fn list_tags(mut cmd: GitCommand) -> Result<Vec<Tag>, GitError> {
+    cmd.arg("for-each-ref")
+        .arg(format!("--format={}", FORMAT))
+        .args(["--", "refs/tags"]);
+    parse_tags(&cmd.read()?)
+}
+
+/// Parse `git describe --long` output like `v1.0-rc1-3-gabc1234`
+fn parse_description(text: &str) -> Result<Description, GitError> {
+    let parse_error = || GitError::Parse(format!("Unexpected git-describe(1) output `{}`", text));
+    let mut parts = text.trim().rsplitn(3, '-');
+    let id = parts.next().and_then(|id| id.strip_prefix('g'));
+    let distance = parts.next().and_then(|d| d.parse().ok());
+    match (id, distance, parts.next()) {
+        (Some(id), Some(distance), Some(tag)) => Ok(Description {
+            tag: tag.to_owned(),
+            distance,
+            id: id.to_owned(),
+        }),
+        _ => Err(parse_error()),
+    }
+}
+
+/// Create a tag, see [`crate::GenericRepository::create_tag`]
+pub(crate) fn create_tag(
+    cmd: GitCommand,
+    name: &str,
+    target: Option<&str>,
+    message: Option<&str>,
+) -> Result<(), TagError> {
+    let out = cmd
+        .clone()
+        .arg("check-ref-format")
+        .arg(format!("refs/tags/{}", name))
+        .output()?;
+    if !out.status.success() || name.starts_with('-') {
+        return Err(TagError::InvalidName(name.to_owned()));
+    }
+
+    let mut cmd = cmd;
+    cmd.arg("tag");
+    if let Some(message) = message {
+        cmd.args(["--annotate", "--file=-", "--cleanup=verbatim"])
+            .stdin(message.as_bytes().to_vec());
+    }
+    cmd.arg(name);
+    if let Some(target) = target {
+        cmd.arg(target);
+    }
+    let out = cmd.output()?;
+    if out.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    if stderr.contains("already exists") {
+        Err(TagError::TagExists(name.to_owned()))
+    } else if stderr.contains("Failed to resolve") {
+        Err(TagError::NotFound(target.unwrap_or("HEAD").to_owned()))
+    } else {
+        Err(GitError::from_output(&cmd, &out).into())
+    }
+}
+
+/// Delete a tag, see [`crate::GenericRepository::delete_tag`]
+pub(crate) fn delete_tag(mut cmd: GitCommand, name: &str) -> Result<(), TagError> {
+    cmd.args(["tag", "--delete", "--", name]);
+    let out = cmd.output()?;
+    if out.status.success() {
+        return Ok(());
+    }
+    if String::from_utf8_lossy(&out.stderr).contains("not found") {
+        Err(TagError::NotFound(name.to_owned()))
+    } else {
+        Err(GitError::from_output(&cmd, &out).into())
+    }
+}
+
+/// Describe a revision, see [`crate::GenericRepository::describe`]
+pub(crate) fn describe(mut cmd: GitCommand, rev: &str) -> Result<Option<Description>, TagError> {
+    cmd.args(["describe", "--long", "--end-of-options", rev]);
+    let out = cmd.output()?;
+    if out.status.success() {
+        let stdout = String::from_utf8(out.stdout).map_err(GitError::from)?;
+        return Ok(Some(parse_description(&stdout)?));
+    }
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    if stderr.contains("No names found") || stderr.contains("No annotated tags") {
+        Ok(None)
+    } else if stderr.contains("Not a valid object name") {
+        Err(TagError::NotFound(rev.to_owned()))
+    } else {
+        Err(GitError::from_output(&cmd, &out).into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        setup_test_author, Description, GenericRepository, ObjectType, Repository, TagError,
+    };
+    use tempfile::TempDir;
+
+    #[test]
+    fn tags() {
+        setup_test_author();
+        let tmp_dir = TempDir::new().unwrap();
+        let repo = Repository::create(tmp_dir.path()).expect("Created repository");
+        repo.commit_extended("First", true, true).unwrap();
+        let first = repo.head().unwrap();
+        assert_eq!(repo.describe("HEAD").unwrap(), None);
+
+        repo.create_tag("light", None, None).unwrap();
+        repo.create_tag("v1.0-rc1", Some(&first), Some("Release\n\nNotes\n"))
+            .unwrap();
+        assert_eq!(
+            repo.create_tag("light", None, None),
+            Err(TagError::TagExists("light".to_owned()))
+        );
+        assert_eq!(
+            repo.create_tag("in..valid", None, None),
+            Err(TagError::InvalidName("in..valid".to_owned()))
+        );
+        assert_eq!(
+            repo.create_tag("other", Some("nope"), None),
+            Err(TagError::NotFound("nope".to_owned()))
+        );
+
+        let tags = repo.tags().unwrap();
+        assert_eq!(tags.len(), 2);
+        assert_eq!(tags[0].name, "light");
+        assert!(!tags[0].is_annotated());
+        assert_eq!(tags[0].kind, ObjectType::Commit);
+        assert_eq!(tags[0].message, None);
+        assert_eq!(tags[0].commit(), first);
+        assert_eq!(tags[1].name, "v1.0-rc1");
+        assert!(tags[1].is_annotated());
+        assert_eq!(tags[1].message.as_deref(), Some("Release\n\nNotes\n"));
+        assert_eq!(tags[1].peeled.as_deref(), Some(first.as_str()));
+        assert!(tags[1].tagger.is_some());
+
+        repo.commit_extended("Second", true, true).unwrap();
+        let description = repo.describe("HEAD").unwrap().expect("Found a tag");
+        assert_eq!(description.tag, "v1.0-rc1");
+        assert_eq!(description.distance, 1);
+        assert!(repo.head().unwrap().starts_with(&description.id));
+        assert_eq!(
+            repo.describe("nope"),
+            Err(TagError::NotFound("nope".to_owned()))
+        );
+
+        repo.delete_tag("light").unwrap();
+        assert_eq!(
+            repo.delete_tag("light"),
+            Err(TagError::NotFound("light".to_owned()))
+        );
+        assert_eq!(repo.tags().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn description() {
+        assert_eq!(
+            super::parse_description("v1.0-rc1-3-gabc1234\n").unwrap(),
+            Description {
+                tag: "v1.0-rc1".to_owned(),
+                distance: 3,
+                id: "abc1234".to_owned()
+            }
+        );
+        assert!(super::parse_description("abc1234").is_err());
+    }
+}
@@ -0,0 +1,461 @@
+use crate::{GitCommand, GitError};
+use posix_errors::{PosixError, EAGAIN};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout};
+
+/// Failed to apply a [`RefTransaction`]
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum RefTransactionError {
+    /// The reference could not be locked, i.e. its current value does not match the expected
+    /// old value or it already exists
+    #[error("Failed to update reference `{name}`: {message}")]
+    CompareAndSwap {
+        /// Full name of the reference
+        name: String,
+        /// Message reported by git
+        message: String,
+    },
+    #[allow(missing_docs)]
+    #[error(transparent)]
+    Failure(#[from] GitError),
+}
+
+impl From<RefTransactionError> for GitError {
+    #[inline]
+    fn from(err: RefTransactionError) -> Self {
+        match err {
+            RefTransactionError::Failure(e) => e,
+            RefTransactionError::CompareAndSwap { .. } => Self::Invalid(format!("{}", err)),
+        }
+    }
+}
+
+impl From<RefTransactionError> for PosixError {
+    #[inline]
+    fn from(err: RefTransactionError) -> Self {
+        match err {
+            RefTransactionError::Failure(e) => e.into(),
+            RefTransactionError::CompareAndSwap { .. } => Self::new(EAGAIN, format!("{}", err)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Operation {
+    Create {
+        name: String,
+        new: String,
+    },
+    Update {
+        name: String,
+        new: String,
+        old: Option<String>,
+    },
+    Delete {
+        name: String,
+        old: Option<String>,
+    },
+    Verify {
+        name: String,
+        old: Option<String>,
+    },
+}
+
+impl Operation {
+    fn write(&self, script: &mut Vec<u8>) {
+        let mut field = |value: &str| {
+            script.extend_from_slice(value.as_bytes());
+            script.push(0);
+        };
+        match self {
+            Self::Create { name, new } => {
+                field(&format!("create {}", name));
+                field(new);
+            }
+            Self::Update { name, new, old } => {
+                field(&format!("update {}", name));
+                field(new);
+                field(old.as_deref().unwrap_or_default());
+            }
+            Self::Delete { name, old } => {
+                field(&format!("delete {}", name));
+                field(old.as_deref().unwrap_or_default());
+            }
+            Self::Verify { name, old } => {
+                field(&format!("verify {}", name));
+                field(old.as_deref().unwrap_or_default());
+            }
+        }
+    }
+}
+
+/// Atomically update multiple references using
+/// [git-update-ref(1)](https://git-scm.com/docs/git-update-ref) `--stdin`
+///
+/// Either all queued operations are applied or none.
+#[derive(Clone, Debug)]
+#[must_use]
+pub struct RefTransaction {
+    cmd: GitCommand,
+    message: Option<String>,
+    operations: Vec<Operation>,
+}
+
+impl RefTransaction {
+    pub(crate) fn new(cmd: GitCommand) -> Self {
+        Self {
+            cmd,
+            message: None,
+            operations: vec![],
+        }
+    }
+
+    /// Reflog message for all updates
+    #[inline]
+    pub fn message(mut self, message: &str) -> Self {
+        self.message = Some(message.to_owned());
+        self
+    }
+
+    /// Create `name` pointing to `new`, fails if it already exists
+    #[inline]
+    pub fn create(mut self, name: &str, new: &str) -> Self {
+        self.operations.push(Operation::Create {
+            name: name.to_owned(),
+            new: new.to_owned(),
+        });
+        self
+    }
+
+    /// Point `name` to `new`; if `old` is given, the current value must match it
+    #[inline]
+    pub fn update(mut self, name: &str, new: &str, old: Option<&str>) -> Self {
+        self.operations.push(Operation::Update {
+            name: name.to_owned(),
+            new: new.to_owned(),
+            old: old.map(ToOwned::to_owned),
+        });
+        self
+    }
+
+    /// Delete `name`; if `old` is given, the current value must match it
+    #[inline]
+    pub fn delete(mut self, name: &str, old: Option<&str>) -> Self {
+        self.operations.push(Operation::Delete {
+            name: name.to_owned(),
+            old: old.map(ToOwned::to_owned),
+        });
+        self
+    }
+
+    /// Verify `name` points to `old` without changing it; `None` verifies it does not exist
+    #[inline]
+    pub fn verify(mut self, name: &str, old: Option<&str>) -> Self {
+        self.operations.push(Operation::Verify {
+            name: name.to_owned(),
+            old: old.map(ToOwned::to_owned),
+        });
+        self
+    }
+
+    /// Returns `true` if no operations are queued
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    fn command(&self) -> GitCommand {
+        let mut cmd = self.cmd.clone();
+        cmd.arg("update-ref");
+        if let Some(message) = &self.message {
+            cmd.args(["-m", message]);
+        }
+        cmd.args(["--stdin", "-z"]);
+        cmd
+    }
+
+    /// `start`, all queued operations & `prepare` as NUL terminated commands
+    fn script(&self) -> Vec<u8> {
+        let mut script = b"start\0".to_vec();
+        for op in &self.operations {
+            op.write(&mut script);
+        }
+        script.extend_from_slice(b"prepare\0");
+        script
+    }
+
+    /// Lock all references and verify their old values.
+    ///
+    /// The references stay locked by a running git-update-ref(1) process until the returned
+    /// [`PreparedRefTransaction`] is committed, aborted or dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RefTransactionError::CompareAndSwap`] naming the first reference which can not
+    /// be updated.
+    #[inline]
+    pub fn prepare(self) -> Result<PreparedRefTransaction, RefTransactionError> {
+        let mut cmd = self.command();
+        let mut child = cmd.spawn()?;
+        let missing = || GitError::Spawn(std::io::Error::other("git update-ref without pipes"));
+        let mut stdin = child.stdin.take().ok_or_else(missing)?;
+        let stdout = child.stdout.take().ok_or_else(missing)?;
+        // A failed write means git already exited, the reason is read from STDERR below
+        let _ = stdin.write_all(&self.script()).and_then(|()| stdin.flush());
+        let mut prepared = PreparedRefTransaction {
+            cmd,
+            child,
+            stdin: Some(stdin),
+            stdout: BufReader::new(stdout),
+        };
+        prepared.expect("start")?;
+        prepared.expect("prepare")?;
+        Ok(prepared)
+    }
+
+    /// Apply all queued operations atomically
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RefTransactionError::CompareAndSwap`] naming the first reference which can not
+    /// be updated. In this case no reference was changed.
+    #[inline]
+    pub fn commit(self) -> Result<(), RefTransactionError> {
+        let mut script = self.script();
+        script.extend_from_slice(b"commit\0");
+        let mut cmd = self.command();
+        let out = cmd.stdin(script).output()?;
+        if out.status.success() {
+            return Ok(());
+        }
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        match failed_ref(&stderr) {
+            Some(name) => Err(compare_and_swap(name, &stderr)),
+            None => Err(GitError::from_output(&cmd, &out).into()),
+        }
+    }
+}
+
+/// A [`RefTransaction`] with all references locked, see [`RefTransaction::prepare`]
+///
+/// Dropping it without calling [`PreparedRefTransaction::commit`] aborts the transaction.
+#[derive(Debug)]
+pub struct PreparedRefTransaction {
+    cmd: GitCommand,
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl PreparedRefTransaction {
+    /// Read the `<command>: ok` answer of git
+    fn expect(&mut self, command: &str) -> Result<(), RefTransactionError> {
+        let mut line = String::new();
+        self.stdout.read_line(&mut line).map_err(GitError::Spawn)?;
+        if line.is_empty() {
+            return Err(self.failure());
+        }
+        let line = line.trim_end_matches('\n');
+        if line == format!("{}: ok", command) {
+            Ok(())
+        } else {
+            Err(GitError::Parse(format!("Unexpected git-update-ref(1) output `{}`", line)).into())
+        }
+    }
+
+    /// Wait for the exited git process and build an error from its `STDERR`
+    fn failure(&mut self) -> RefTransactionError {
+        drop(self.stdin.take());
+        let mut stderr = String::new();
+        if let Some(mut pipe) = self.child.stderr.take() {
+            let _ = pipe.read_to_string(&mut stderr);
+        }
+        let status = match self.child.wait() {
+            Ok(status) => status,
+            Err(e) => return GitError::Spawn(e).into(),
+        };
+        match failed_ref(&stderr) {
+            Some(name) => compare_and_swap(name, &stderr),
+            None => GitError::Failure {
+                args: self
+                    .cmd
+                    .get_args()
+                    .map(|a| a.to_string_lossy().to_string())
+                    .collect(),
+                code: status.code().unwrap_or(1),
+                stdout: String::new(),
+                stderr,
+            }
+            .into(),
+        }
+    }
+
+    fn finish(mut self, command: &str) -> Result<(), RefTransactionError> {
+        if let Some(mut stdin) = self.stdin.take() {
+            let _ = stdin.write_all(format!("{}\0", command).as_bytes());
+        }
+        self.expect(command)?;
+        match self.child.wait() {
+            Ok(status) if status.success() => Ok(()),
+            Ok(_) => Err(self.failure()),
+            Err(e) => Err(GitError::Spawn(e).into()),
+        }
+    }
+
+    /// Apply all operations and release the locks
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RefTransactionError`] if git fails to update the references
+    #[inline]
+    pub fn commit(self) -> Result<(), RefTransactionError> {
+        self.finish("commit")
+    }
+
+    /// Release the locks without changing any reference
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RefTransactionError::Failure`] if git fails to abort the transaction
+    #[inline]
+    pub fn abort(self) -> Result<(), RefTransactionError> {
+        self.finish("abort")
+    }
+}
+
+impl Drop for PreparedRefTransaction {
+    fn drop(&mut self) {
+        // Closing STDIN makes git-update-ref(1) abort a prepared transaction and exit
+        drop(self.stdin.take());
+        if self.child.wait().is_err() {
+            let _ = self.child.kill();
+        }
+    }
+}
+
+fn compare_and_swap(name: String, stderr: &str) -> RefTransactionError {
+    RefTransactionError::CompareAndSwap {
+        name,
+        message: stderr.trim().trim_start_matches("fatal: ").to_owned(),
+    }
+}
+
+/// Extract the reference name from messages like `cannot lock ref 'refs/heads/main': …`
+fn failed_ref(stderr: &str) -> Option<String> {
+    stderr.lines().find_map(|line| {
+        let (_, rest) = line
+            .split_once("cannot lock ref '")
+            .or_else(|| line.split_once("for ref '"))?;
+        let (name, _) = rest.split_once('\'')?;
+        Some(name.to_owned())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        setup_test_author, GenericRepository, RefOptions, RefTransactionError, Repository,
+    };
+    use tempfile::TempDir;
+
+    fn branch(repo: &Repository, name: &str) -> Option<String> {
+        repo.refs(&[name], &RefOptions::default())
+            .unwrap()
+            .first()
+            .map(|r| r.target.clone())
+    }
+
+    #[test]
+    fn commit() {
+        setup_test_author();
+        let tmp_dir = TempDir::new().unwrap();
+        let repo = Repository::create(tmp_dir.path()).expect("Created repository");
+        repo.commit_extended("First", true, true).unwrap();
+        let first = repo.head().unwrap();
+        repo.commit_extended("Second", true, true).unwrap();
+        let second = repo.head().unwrap();
+
+        repo.ref_transaction()
+            .create("refs/heads/a", &first)
+            .create("refs/heads/b", &first)
+            .commit()
+            .unwrap();
+        assert_eq!(branch(&repo, "refs/heads/a"), Some(first.clone()));
+
+        repo.ref_transaction()
+            .message("Move a & b")
+            .update("refs/heads/a", &second, Some(&first))
+            .delete("refs/heads/b", Some(&first))
+            .commit()
+            .unwrap();
+        assert_eq!(branch(&repo, "refs/heads/a"), Some(second.clone()));
+        assert_eq!(branch(&repo, "refs/heads/b"), None);
+
+        let transaction = repo
+            .ref_transaction()
+            .create("refs/heads/c", &first)
+            .verify("refs/heads/a", Some(&second));
+        let prepared = transaction.prepare().unwrap();
+        assert!(repo
+            .ref_transaction()
+            .create("refs/heads/c", &second)
+            .commit()
+            .is_err());
+        prepared.abort().unwrap();
+        assert_eq!(branch(&repo, "refs/heads/c"), None);
+
+        let prepared = repo
+            .ref_transaction()
+            .create("refs/heads/c", &first)
+            .prepare()
+            .unwrap();
+        prepared.commit().unwrap();
+        assert_eq!(branch(&repo, "refs/heads/c"), Some(first.clone()));
+
+        let prepared = repo
+            .ref_transaction()
+            .delete("refs/heads/c", Some(&first))
+            .prepare()
+            .unwrap();
+        drop(prepared);
+        assert_eq!(branch(&repo, "refs/heads/c"), Some(first));
+    }
+
+    #[test]
+    fn compare_and_swap() {
+        setup_test_author();
+        let tmp_dir = TempDir::new().unwrap();
+        let repo = Repository::create(tmp_dir.path()).expect("Created repository");
+        repo.commit_extended("First", true, true).unwrap();
+        let first = repo.head().unwrap();
+        repo.commit_extended("Second", true, true).unwrap();
+        let second = repo.head().unwrap();
+        repo.ref_transaction()
+            .create("refs/heads/a", &first)
+            .commit()
+            .unwrap();
+
+        let err = repo
+            .ref_transaction()
+            .create("refs/heads/new", &second)
+            .update("refs/heads/a", &second, Some(&second))
+            .commit()
+            .expect_err("Expected compare-and-swap failure");
+        assert!(
+            matches!(err, RefTransactionError::CompareAndSwap { ref name, .. } if name == "refs/heads/a")
+        );
+        assert_eq!(branch(&repo, "refs/heads/new"), None);
+        assert_eq!(branch(&repo, "refs/heads/a"), Some(first.clone()));
+
+        let err = repo
+            .ref_transaction()
+            .update("refs/heads/a", &second, Some(&second))
+            .prepare()
+            .expect_err("Expected compare-and-swap failure");
+        assert!(
+            matches!(err, RefTransactionError::CompareAndSwap { ref name, .. } if name == "refs/heads/a")
+        );
+        assert_eq!(branch(&repo, "refs/heads/a"), Some(first));
+    }
+}
@@ -0,0 +1,480 @@
+use crate::discover::git_dir_from_work_tree;
+use crate::{AbsoluteDirPath, GenericRepository, GitCommand, GitError, Repository};
+use posix_errors::{PosixError, EBUSY, EEXIST, EINVAL, ENOENT};
+use std::path::{Path, PathBuf};
+
+/// Failed to manage a worktree
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum WorktreeError {
+    /// Target path of a new or moved worktree already exists
+    #[error("`{0}` already exists")]
+    PathExists(PathBuf),
+    /// Branch is already checked out in another worktree
+    #[error("Branch `{0}` is already checked out")]
+    CheckedOut(String),
+    /// Path is not a linked worktree of this repository
+    #[error("`{0}` is not a working tree")]
+    NotAWorktree(PathBuf),
+    /// The main worktree can not be removed, moved or locked
+    #[error("`{0}` is the main working tree")]
+    MainWorktree(PathBuf),
+    #[allow(missing_docs)]
+    #[error("`{0}` is locked")]
+    Locked(PathBuf),
+    #[allow(missing_docs)]
+    #[error("`{0}` is not locked")]
+    NotLocked(PathBuf),
+    /// Worktree contains modified or untracked files, use `force` to remove it anyway
+    #[error("`{0}` contains modified or untracked files")]
+    Dirty(PathBuf),
+    #[allow(missing_docs)]
+    #[error(transparent)]
+    Failure(#[from] GitError),
+}
+
+impl From<WorktreeError> for GitError {
+    #[inline]
+    fn from(err: WorktreeError) -> Self {
+        match err {
+            WorktreeError::Failure(e) => e,
+            WorktreeError::PathExists(_)
+            | WorktreeError::CheckedOut(_)
+            | WorktreeError::NotAWorktree(_)
+            | WorktreeError::MainWorktree(_)
+            | WorktreeError::Locked(_)
+            | WorktreeError::NotLocked(_)
+            | WorktreeError::Dirty(_) => Self::Invalid(format!("{}", err)),
+        }
+    }
+}
+
+impl From<WorktreeError> for PosixError {
+    #[inline]
+    fn from(err: WorktreeError) -> Self {
+        match err {
+            WorktreeError::Failure(e) => e.into(),
+            WorktreeError::PathExists(_) => Self::new(EEXIST, format!("{}", err)),
+            WorktreeError::NotAWorktree(_) => Self::new(ENOENT, format!("{}", err)),
+            WorktreeError::CheckedOut(_) | WorktreeError::Locked(_) | WorktreeError::Dirty(_) => {
+                Self::new(EBUSY, format!("{}", err))
+            }
+            WorktreeError::MainWorktree(_) | WorktreeError::NotLocked(_) => {
+                Self::new(EINVAL, format!("{}", err))
+            }
+        }
+    }
+}
+
+/// A worktree as listed by [`Repository::worktrees`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Worktree {
+    /// Absolute path of the work tree or of the git directory for a bare repository
+    pub path: PathBuf,
+    /// Checked out commit, `None` for a bare repository
+    pub head: Option<String>,
+    /// Checked out branch without the `refs/heads/` prefix
+    pub branch: Option<String>,
+    #[allow(missing_docs)]
+    pub bare: bool,
+    /// `HEAD` does not point to a branch
+    pub detached: bool,
+    /// Lock reason, empty if the worktree was locked without one
+    pub locked: Option<String>,
+    /// Reason why [`Repository::prune_worktrees`] would remove this worktree
+    pub prunable: Option<String>,
+}
+
+/// Options for [`Repository::add_worktree`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WorktreeAddOptions {
+    new_branch: Option<String>,
+    detach: bool,
+    force: bool,
+    lock: Option<String>,
+    no_checkout: bool,
+}
+
+impl WorktreeAddOptions {
+    /// Create a new branch `name` starting at the given commit-ish & check it out
+    #[must_use]
+    #[inline]
+    pub fn new_branch(mut self, name: &str) -> Self {
+        self.new_branch = Some(name.to_owned());
+        self
+    }
+
+    /// Detach `HEAD` in the new worktree
+    #[must_use]
+    #[inline]
+    pub const fn detach(mut self, detach: bool) -> Self {
+        self.detach = detach;
+        self
+    }
+
+    /// Check out a branch even if it is already checked out by another worktree
+    #[must_use]
+    #[inline]
+    pub const fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Lock the new worktree with the given, possibly empty, reason
+    #[must_use]
+    #[inline]
+    pub fn lock(mut self, reason: &str) -> Self {
+        self.lock = Some(reason.to_owned());
+        self
+    }
+
+    /// Do not populate the new work tree, i.e. to set up a sparse checkout first
+    #[must_use]
+    #[inline]
+    pub const fn no_checkout(mut self, no_checkout: bool) -> Self {
+        self.no_checkout = no_checkout;
+        self
+    }
+}
+
+/// Parse the NUL separated output of `git worktree list --porcelain -z`
+fn parse_worktrees(output: &str) -> Result<Vec<Worktree>, GitError> {
+    let mut result = vec![];
+    let mut current: Option<Worktree> = None;
+    for field in output.split('\0') {
+        if field.is_empty() {
+            result.extend(current.take());
+            continue;
+        }
+        let (key, value) = field.split_once(' ').unwrap_or((field, ""));
+        if key == "worktree" {
+            result.extend(current.take());
+            current = Some(Worktree {
+                path: PathBuf::from(value),
+                ..Worktree::default()
+            });
+            continue;
+        }
+        let worktree = current.as_mut().ok_or_else(|| {
+            GitError::Parse(format!("Unexpected git-worktree(1) field `{}`", field))
+        })?;
+        match key {
+            "HEAD" => worktree.head = Some(value.to_owned()),
+            "branch" => {
+                worktree.branch = Some(
+                    value
+                        .strip_prefix("refs/heads/")
+                        .unwrap_or(value)
+                        .to_owned(),
+                );
+            }
+            "bare" => worktree.bare = true,
+            "detached" => worktree.detached = true,
+            "locked" => worktree.locked = Some(value.to_owned()),
+            "prunable" => worktree.prunable = Some(value.to_owned()),
+            _ => {}
+        }
+    }
+    result.extend(current);
+    Ok(result)
+}
+
+/// Run a git-worktree(1) command, translating well known failures into [`WorktreeError`]
+fn run_worktree(
+    mut cmd: GitCommand,
+    path: &Path,
+    branch: Option<&str>,
+) -> Result<(), WorktreeError> {
+    let out = cmd.output()?;
+    if out.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    // git quotes the path as passed on the command line
+    let quoted = format!("'{}'", path.display());
+    let about_path = |message: &str| stderr.contains(&format!("{} {}", quoted, message));
+    if stderr.contains("is already checked out") || stderr.contains("is already used by worktree") {
+        Err(WorktreeError::CheckedOut(
+            branch.unwrap_or_default().to_owned(),
+        ))
+    } else if about_path("already exists") {
+        Err(WorktreeError::PathExists(path.to_path_buf()))
+    } else if about_path("is not a working tree") {
+        Err(WorktreeError::NotAWorktree(path.to_path_buf()))
+    } else if about_path("is a main working tree") {
+        Err(WorktreeError::MainWorktree(path.to_path_buf()))
+    } else if about_path("is not locked") {
+        Err(WorktreeError::NotLocked(path.to_path_buf()))
+    } else if about_path("is already locked")
+        || stderr.contains("cannot remove a locked working tree")
+        || stderr.contains("cannot move a locked working tree")
+    {
+        Err(WorktreeError::Locked(path.to_path_buf()))
+    } else if about_path("contains modified or untracked files") {
+        Err(WorktreeError::Dirty(path.to_path_buf()))
+    } else {
+        Err(GitError::from_output(&cmd, &out).into())
+    }
+}
+
+/// Worktrees
+impl Repository {
+    /// Resolve `path` relative to the work tree like git does
+    fn worktree_path(&self, path: &Path) -> PathBuf {
+        self.work_tree.0.join(path)
+    }
+
+    /// Open the linked worktree at `path` with the same runner as this repository
+    fn open_worktree(&self, path: &Path) -> Result<Self, GitError> {
+        let canonical = path
+            .canonicalize()
+            .map_err(|e| GitError::Invalid(format!("{}: {}", path.display(), e)))?;
+        let work_tree = AbsoluteDirPath::try_from(canonical.as_path())?;
+        let git_dir = git_dir_from_work_tree(&work_tree)?;
+        Ok(Self::new(git_dir, work_tree).with_runner(self.runner.clone()))
+    }
+
+    /// List the main & all linked worktrees
+    ///
+    /// # Errors
+    ///
+    /// See [`GitError`]
+    #[inline]
+    pub fn worktrees(&self) -> Result<Vec<Worktree>, GitError> {
+        let out = self
+            .git()
+            .args(["worktree", "list", "--porcelain", "-z"])
+            .read()?;
+        parse_worktrees(&out)
+    }
+
+    /// Create a new worktree at `path` checking out `branch`, a new branch named after the
+    /// last path component or the one configured in the `options`
+    ///
+    /// # Errors
+    ///
+    /// Throws [`WorktreeError`] on errors
+    #[inline]
+    pub fn add_worktree(
+        &self,
+        path: &Path,
+        branch: Option<&str>,
+        options: &WorktreeAddOptions,
+    ) -> Result<Self, WorktreeError> {
+        let path = self.worktree_path(path);
+        let mut cmd = self.git();
+        cmd.args(["worktree", "add", "--quiet"]);
+        if let Some(name) = &options.new_branch {
+            cmd.arg("-b").arg(name);
+        }
+        if options.detach {
+            cmd.arg("--detach");
+        }
+        if options.force {
+            cmd.arg("--force");
+        }
+        if let Some(reason) = &options.lock {
+            cmd.arg("--lock");
+            if !reason.is_empty() {
+                cmd.arg("--reason").arg(reason);
+            }
+        }
+        if options.no_checkout {
+            cmd.arg("--no-checkout");
+        }
+        cmd.arg("--").arg(&path);
+        if let Some(branch) = branch {
+            cmd.arg(branch);
+        }
+        run_worktree(cmd, &path, branch)?;
+        Ok(self.open_worktree(&path)?)
+    }
+
+    /// Remove the worktree at `path`; `force` removes it even with uncommitted changes
+    ///
+    /// # Errors
+    ///
+    /// Throws [`WorktreeError`] on errors
+    #[inline]
+    pub fn remove_worktree(&self, path: &Path, force: bool) -> Result<(), WorktreeError> {
+        let path = self.worktree_path(path);
+        let mut cmd = self.git();
+        cmd.args(["worktree", "remove"]);
+        if force {
+            cmd.arg("--force");
+        }
+        cmd.arg("--").arg(&path);
+        run_worktree(cmd, &path, None)
+    }
+
+    /// Protect the worktree at `path` from being pruned, moved or removed
+    ///
+    /// # Errors
+    ///
+    /// Throws [`WorktreeError`] on errors
+    #[inline]
+    pub fn lock_worktree(&self, path: &Path, reason: Option<&str>) -> Result<(), WorktreeError> {
+        let path = self.worktree_path(path);
+        let mut cmd = self.git();
+        cmd.args(["worktree", "lock"]);
+        if let Some(reason) = reason {
+            cmd.arg("--reason").arg(reason);
+        }
+        cmd.arg("--").arg(&path);
+        run_worktree(cmd, &path, None)
+    }
+
+    /// # Errors
+    ///
+    /// Throws [`WorktreeError`] on errors
+    #[inline]
+    pub fn unlock_worktree(&self, path: &Path) -> Result<(), WorktreeError> {
+        let path = self.worktree_path(path);
+        let mut cmd = self.git();
+        cmd.args(["worktree", "unlock", "--"]).arg(&path);
+        run_worktree(cmd, &path, None)
+    }
+
+    /// Move the worktree at `path` to `destination`, which must not exist
+    ///
+    /// # Errors
+    ///
+    /// Throws [`WorktreeError`] on errors
+    #[inline]
+    pub fn move_worktree(&self, path: &Path, destination: &Path) -> Result<Self, WorktreeError> {
+        let path = self.worktree_path(path);
+        let destination = self.worktree_path(destination);
+        if destination.exists() {
+            return Err(WorktreeError::PathExists(destination));
+        }
+        let mut cmd = self.git();
+        cmd.args(["worktree", "move", "--"])
+            .arg(&path)
+            .arg(&destination);
+        run_worktree(cmd, &path, None)?;
+        Ok(self.open_worktree(&destination)?)
+    }
+
+    /// Remove administrative files of worktrees whose directories are gone, returns the
+    /// names of the pruned worktrees
+    ///
+    /// # Errors
+    ///
+    /// See [`GitError`]
+    #[inline]
+    pub fn prune_worktrees(&self) -> Result<Vec<String>, GitError> {
+        let mut cmd = self.git();
+        cmd.args(["worktree", "prune", "--verbose"]);
+        let out = cmd.output()?;
+        if !out.status.success() {
+            return Err(GitError::from_output(&cmd, &out));
+        }
+        Ok(String::from_utf8(out.stderr)?
+            .lines()
+            .filter_map(|line| line.strip_prefix("Removing worktrees/"))
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, _)| name.to_owned())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        setup_test_author, GenericRepository, Repository, WorktreeAddOptions, WorktreeError,
+    };
+    use tempfile::TempDir;
+
+    #[test]
+    fn parse() {
+        let output = "worktree /main\0HEAD 1234\0branch refs/heads/main\0\0worktree /wt\0HEAD 5678\0detached\0locked multi\nline\0prunable gone\0\0worktree /bare\0bare\0\0";
+        let worktrees = super::parse_worktrees(output).unwrap();
+        assert_eq!(worktrees.len(), 3);
+        assert_eq!(worktrees[0].branch.as_deref(), Some("main"));
+        assert!(worktrees[1].detached);
+        assert_eq!(worktrees[1].locked.as_deref(), Some("multi\nline"));
+        assert_eq!(worktrees[1].prunable.as_deref(), Some("gone"));
+        assert!(worktrees[2].bare);
+        assert_eq!(worktrees[2].head, None);
+    }
+
+    #[test]
+    fn manage() {
+        setup_test_author();
+        let tmp_dir = TempDir::new().unwrap();
+        let root = tmp_dir.path().canonicalize().unwrap();
+        let repo = Repository::create(&root.join("main")).expect("Created repository");
+        repo.commit_extended("First", true, true).unwrap();
+        let main = repo.current_branch().unwrap().unwrap();
+
+        let wt_dir = root.join("wt");
+        let options = WorktreeAddOptions::default().new_branch("feature");
+        let wt = repo.add_worktree(&wt_dir, None, &options).unwrap();
+        assert!(wt.is_linked_worktree());
+        assert_eq!(wt.work_tree(), Some(wt_dir.clone()));
+        assert_eq!(wt.current_branch().unwrap().as_deref(), Some("feature"));
+        assert_eq!(
+            repo.add_worktree(
+                &root.join("other"),
+                Some(&main),
+                &WorktreeAddOptions::default()
+            )
+            .unwrap_err(),
+            WorktreeError::CheckedOut(main.clone())
+        );
+        assert_eq!(
+            repo.add_worktree(&wt_dir, None, &WorktreeAddOptions::default().detach(true))
+                .unwrap_err(),
+            WorktreeError::PathExists(wt_dir.clone())
+        );
+        assert!(matches!(
+            repo.add_worktree(&root.join("other"), None, &options),
+            Err(WorktreeError::Failure(_))
+        ));
+
+        repo.lock_worktree(&wt_dir, Some("building")).unwrap();
+        assert_eq!(
+            repo.lock_worktree(&wt_dir, None),
+            Err(WorktreeError::Locked(wt_dir.clone()))
+        );
+        let worktrees = repo.worktrees().unwrap();
+        assert_eq!(worktrees.len(), 2);
+        assert_eq!(worktrees[0].path, root.join("main"));
+        assert_eq!(worktrees[1].branch.as_deref(), Some("feature"));
+        assert_eq!(worktrees[1].locked.as_deref(), Some("building"));
+        assert_eq!(
+            repo.remove_worktree(&wt_dir, false),
+            Err(WorktreeError::Locked(wt_dir.clone()))
+        );
+        repo.unlock_worktree(&wt_dir).unwrap();
+        assert_eq!(
+            repo.unlock_worktree(&wt_dir),
+            Err(WorktreeError::NotLocked(wt_dir.clone()))
+        );
+
+        let moved_dir = root.join("moved");
+        let moved = repo.move_worktree(&wt_dir, &moved_dir).unwrap();
+        assert_eq!(moved.work_tree(), Some(moved_dir.clone()));
+        std::fs::write(moved_dir.join("dirty"), "").unwrap();
+        assert_eq!(
+            repo.remove_worktree(&moved_dir, false),
+            Err(WorktreeError::Dirty(moved_dir.clone()))
+        );
+        repo.remove_worktree(&moved_dir, true).unwrap();
+        assert_eq!(
+            repo.remove_worktree(&moved_dir, false),
+            Err(WorktreeError::NotAWorktree(moved_dir))
+        );
+        assert_eq!(
+            repo.remove_worktree(&root.join("main"), false),
+            Err(WorktreeError::MainWorktree(root.join("main")))
+        );
+
+        let gone_dir = root.join("gone");
+        repo.add_worktree(&gone_dir, None, &WorktreeAddOptions::default().detach(true))
+            .unwrap();
+        std::fs::remove_dir_all(&gone_dir).unwrap();
+        assert!(repo.worktrees().unwrap()[1].prunable.is_some());
+        assert_eq!(repo.prune_worktrees().unwrap(), vec!["gone".to_owned()]);
+        assert_eq!(repo.worktrees().unwrap().len(), 1);
+    }
+}